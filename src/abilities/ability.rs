@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+use crate::status_effects::status_effect::StatusEffectDefinition;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AbilityCost {
+    pub pool: ResourcePoolEnum,
+    // madness costs are gained rather than spent
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TargetingRule {
+    SelfOnly,
+    Single { range: i32 },
+    // everyone within `radius` of a cell up to `range` away
+    Area { range: i32, radius: i32 },
+    // everyone on the cells in a straight line from the caster towards the target
+    Line { length: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TargetFilter {
+    Enemies,
+    Allies,
+    Everyone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageKind {
+    // resisted by physical resistance and taken from health
+    Physical,
+    // resisted by psychological resistance and added to madness
+    Psychological,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AbilityEffect {
+    Damage { kind: DamageKind, min: i32, max: i32 },
+    Restore { pool: ResourcePoolEnum, min: i32, max: i32 },
+    ApplyStatus(StatusEffectDefinition),
+    RemoveStatus(String),
+}
+
+/// Every point of the stat above (or below) 10 adds `percent_per_point` percent to rolled amounts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatScaling {
+    pub stat: StatsEnum,
+    pub percent_per_point: i32,
+}
+
+/// The price of magic the church has forbidden.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ForbiddenRisk {
+    pub madness_chance_percent: i32,
+    pub madness_amount: i32,
+    pub notoriety_faction_id: String,
+    pub notoriety: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AbilityDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub costs: Vec<AbilityCost>,
+    pub cooldown_turns: u32,
+    pub targeting: TargetingRule,
+    pub affects: TargetFilter,
+    pub effects: Vec<AbilityEffect>,
+    pub scaling: Option<StatScaling>,
+    pub forbidden: Option<ForbiddenRisk>,
+}
+
+impl AbilityDefinition {
+    pub fn can_pay_costs<T: ResourcePoolValues>(&self, caster: &T) -> bool {
+        self.costs
+            .iter()
+            .all(|cost| match cost.pool {
+                ResourcePoolEnum::Madness => caster.get_resource(cost.pool) + cost.amount <= caster.get_resource_limit(cost.pool),
+                // paying with blood may not kill the caster
+                ResourcePoolEnum::Health => caster.get_resource(cost.pool) > cost.amount,
+                _ => caster.get_resource(cost.pool) >= cost.amount,
+            })
+    }
+
+    pub fn pay_costs<T: ResourcePoolValues>(&self, caster: &mut T) {
+        for cost in &self.costs {
+            let change = match cost.pool {
+                ResourcePoolEnum::Madness => cost.amount,
+                _ => -cost.amount,
+            };
+            caster.modify_resource(cost.pool, change);
+        }
+    }
+
+    /// Scales the amount by the caster's stat, `get_stat` giving the stat with any buffs and debuffs applied.
+    pub fn get_scaled_amount(&self, amount: i32, get_stat: impl Fn(StatsEnum) -> i32) -> i32 {
+        match &self.scaling {
+            Some(scaling) => amount + amount * (get_stat(scaling.stat) - 10) * scaling.percent_per_point / 100,
+            None => amount,
+        }
+    }
+
+    pub fn get_range(&self) -> i32 {
+        match self.targeting {
+            TargetingRule::SelfOnly => 0,
+            TargetingRule::Single { range } | TargetingRule::Area { range, .. } => range,
+            TargetingRule::Line { length } => length,
+        }
+    }
+
+    pub fn is_offensive(&self) -> bool {
+        self.effects
+            .iter()
+            .any(|effect| matches!(effect, AbilityEffect::Damage { .. }))
+    }
+}
+
+/// An ability a combatant knows along with how long until it can be used again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownAbility {
+    pub definition: AbilityDefinition,
+    pub cooldown_remaining: u32,
+}
+
+impl KnownAbility {
+    pub fn new(definition: AbilityDefinition) -> Self {
+        KnownAbility {
+            definition,
+            cooldown_remaining: 0,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.cooldown_remaining == 0
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AbilityRegistry {
+    abilities: HashMap<String, AbilityDefinition>,
+}
+
+impl AbilityRegistry {
+    pub fn new() -> Self {
+        AbilityRegistry {
+            abilities: HashMap::new(),
+        }
+    }
+
+    pub fn register_ability(&mut self, ability: AbilityDefinition) {
+        self.abilities.insert(ability.id.clone(), ability);
+    }
+
+    pub fn get_ability(&self, ability_id: &str) -> Option<&AbilityDefinition> {
+        self.abilities.get(ability_id)
+    }
+
+    /// The known abilities for a set of skill ids (e.g. the player's learned skills), skipping unknown ids.
+    pub fn get_known_abilities<'a, I: IntoIterator<Item = &'a String>>(&self, skill_ids: I) -> Vec<KnownAbility> {
+        let mut known: Vec<KnownAbility> = skill_ids
+            .into_iter()
+            .filter_map(|skill_id| self.get_ability(skill_id))
+            .map(|definition| KnownAbility::new(definition.clone()))
+            .collect();
+
+        // skill sets are often hashed, sorting keeps ability indices stable between runs
+        known.sort_by(|a, b| a.definition.id.cmp(&b.definition.id));
+        known
+    }
+}
@@ -0,0 +1 @@
+pub mod ability;
\ No newline at end of file
@@ -1,10 +1,13 @@
-use crate::actors::models::{ComputedAttributeEnum, ComputedAttributes, ComputedAttributeValues, StatBlock, StatsEnum, StatValues};
+use crate::actors::models::{ComputedAttributeEnum, ComputedAttributes, ComputedAttributeValues, ResourcePoolEnum, ResourcePools, ResourcePoolValues, StatBlock, StatsEnum, StatValues};
 
 #[derive(Debug, Copy, Clone)]
 pub struct BaseNpcStats {
     pub level: u32,
     pub base_stats: StatBlock,
-    pub computed_attributes: ComputedAttributes
+    pub computed_attributes: ComputedAttributes,
+    pub resources: ResourcePools,
+    // scales the health computed from the stats, e.g. for elite and champion ranks
+    pub health_percent: i32,
 }
 
 impl StatValues for BaseNpcStats {
@@ -39,19 +42,51 @@ impl ComputedAttributeValues for BaseNpcStats {
     }
 }
 
+impl ResourcePoolValues for BaseNpcStats {
+    fn get_resource(&self, pool: ResourcePoolEnum) -> i32 {
+        self.resources.get_resource(pool)
+    }
+
+    fn get_resource_limit(&self, pool: ResourcePoolEnum) -> i32 {
+        self.computed_attributes.get_computed_attribute(pool.limiting_attribute())
+    }
+
+    fn set_resource(&mut self, pool: ResourcePoolEnum, value: i32) {
+        self.resources.set_resource(pool, value);
+    }
+}
+
 impl BaseNpcStats {
     pub fn new(level: u32, stats: Option<StatBlock>) -> Self {
-        let base_stats = stats.unwrap_or_else(|| StatBlock::default());
+        let base_stats = stats.unwrap_or_default();
+        let computed_attributes = ComputedAttributes::new(base_stats);
         BaseNpcStats {
             level,
             base_stats,
-            computed_attributes: ComputedAttributes::new(base_stats)
+            computed_attributes,
+            resources: ResourcePools::new(computed_attributes),
+            health_percent: 100,
         }
     }
 
+    /// Scales health by the given percent from now on, starting the NPC at full resources.
+    pub fn with_health_percent(mut self, health_percent: i32) -> Self {
+        self.health_percent = health_percent;
+        self.recompute_attributes();
+        self.resources = ResourcePools::new(self.computed_attributes);
+        self
+    }
+
     pub fn increase_stat(&mut self, stat: StatsEnum, value: i32) {
         self.base_stats.set_stat(stat, self.base_stats.get_stat(stat) + value);
+        self.recompute_attributes();
+        self.resources.clamp_to(&self.computed_attributes);
+    }
+
+    fn recompute_attributes(&mut self) {
         self.computed_attributes = ComputedAttributes::new(self.base_stats);
+        let health = self.computed_attributes.get_computed_attribute(ComputedAttributeEnum::Health);
+        self.computed_attributes.set_computed_attribute(ComputedAttributeEnum::Health, health * self.health_percent / 100);
     }
 }
 
@@ -60,7 +95,9 @@ impl Default for BaseNpcStats {
         Self {
             level: 1,
             base_stats: StatBlock::default(),
-            computed_attributes: ComputedAttributes::new(StatBlock::default())
+            computed_attributes: ComputedAttributes::new(StatBlock::default()),
+            resources: ResourcePools::new(ComputedAttributes::new(StatBlock::default())),
+            health_percent: 100,
         }
     }
 }
@@ -1,3 +1,4 @@
 pub mod models;
 pub mod player;
-pub mod base_npc;
\ No newline at end of file
+pub mod base_npc;
+pub mod npc_template;
\ No newline at end of file
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum StatsEnum {
     Strength,
     Dexterity,
@@ -9,7 +11,7 @@ pub enum StatsEnum {
     Luck
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ComputedAttributeEnum {
     Health,
     Mana,
@@ -19,7 +21,9 @@ pub enum ComputedAttributeEnum {
     MadnessLimit
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+// stats left out of data files fall back to the default of 10
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StatBlock {
     strength: i32,
     dexterity: i32,
@@ -80,7 +84,7 @@ impl Default for StatBlock {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash,)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ComputedAttributes {
     pub(crate) health: i32,
     pub(crate) mana: i32,
@@ -143,3 +147,117 @@ pub trait ComputedAttributeValues {
     fn set_computed_attribute(&mut self, attribute: ComputedAttributeEnum, value: i32);
     fn temp_modify_computed_attribute(&self, attribute: ComputedAttributeEnum, multiplier: f32) -> i32;
 }
+
+
+/// The resource pools an actor spends and recovers during play.
+/// Health, mana and stamina are capped by their computed attribute,
+/// madness builds up from zero towards the madness limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResourcePoolEnum {
+    Health,
+    Mana,
+    Stamina,
+    Madness
+}
+
+impl ResourcePoolEnum {
+    pub fn limiting_attribute(&self) -> ComputedAttributeEnum {
+        match self {
+            ResourcePoolEnum::Health => ComputedAttributeEnum::Health,
+            ResourcePoolEnum::Mana => ComputedAttributeEnum::Mana,
+            ResourcePoolEnum::Stamina => ComputedAttributeEnum::Stamina,
+            ResourcePoolEnum::Madness => ComputedAttributeEnum::MadnessLimit,
+        }
+    }
+}
+
+/// How far gone an actor is, from the share of their madness limit they have filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MadnessTier {
+    Lucid,
+    Uneasy,
+    Frightened,
+    Unhinged,
+    Broken
+}
+
+impl MadnessTier {
+    pub fn from_values(madness: i32, madness_limit: i32) -> Self {
+        if madness_limit <= 0 {
+            return MadnessTier::Broken;
+        }
+
+        match madness * 100 / madness_limit {
+            percent if percent >= 100 => MadnessTier::Broken,
+            percent if percent >= 75 => MadnessTier::Unhinged,
+            percent if percent >= 50 => MadnessTier::Frightened,
+            percent if percent >= 25 => MadnessTier::Uneasy,
+            _ => MadnessTier::Lucid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResourcePools {
+    pub(crate) health: i32,
+    pub(crate) mana: i32,
+    pub(crate) stamina: i32,
+    pub(crate) madness: i32,
+}
+
+impl ResourcePools {
+    /// Creates full pools from the given computed attributes with no madness.
+    pub fn new(computed_attributes: ComputedAttributes) -> Self {
+        Self {
+            health: computed_attributes.get_computed_attribute(ComputedAttributeEnum::Health),
+            mana: computed_attributes.get_computed_attribute(ComputedAttributeEnum::Mana),
+            stamina: computed_attributes.get_computed_attribute(ComputedAttributeEnum::Stamina),
+            madness: 0,
+        }
+    }
+
+    pub fn get_resource(&self, pool: ResourcePoolEnum) -> i32 {
+        match pool {
+            ResourcePoolEnum::Health => self.health,
+            ResourcePoolEnum::Mana => self.mana,
+            ResourcePoolEnum::Stamina => self.stamina,
+            ResourcePoolEnum::Madness => self.madness,
+        }
+    }
+
+    pub fn set_resource(&mut self, pool: ResourcePoolEnum, value: i32) {
+        match pool {
+            ResourcePoolEnum::Health => self.health = value,
+            ResourcePoolEnum::Mana => self.mana = value,
+            ResourcePoolEnum::Stamina => self.stamina = value,
+            ResourcePoolEnum::Madness => self.madness = value,
+        }
+    }
+
+    /// Clamps every pool between zero and its limit, used after the limits change (e.g. on level up).
+    pub fn clamp_to(&mut self, computed_attributes: &ComputedAttributes) {
+        for pool in [ResourcePoolEnum::Health, ResourcePoolEnum::Mana, ResourcePoolEnum::Stamina, ResourcePoolEnum::Madness] {
+            let limit = computed_attributes.get_computed_attribute(pool.limiting_attribute());
+            self.set_resource(pool, self.get_resource(pool).clamp(0, limit.max(0)));
+        }
+    }
+}
+
+pub trait ResourcePoolValues {
+    fn get_resource(&self, pool: ResourcePoolEnum) -> i32;
+    fn get_resource_limit(&self, pool: ResourcePoolEnum) -> i32;
+    fn set_resource(&mut self, pool: ResourcePoolEnum, value: i32);
+
+    /// Adds `amount` (which may be negative) to the pool, clamped between zero and the pool limit.
+    /// Returns the change that was actually applied.
+    fn modify_resource(&mut self, pool: ResourcePoolEnum, amount: i32) -> i32 {
+        let current = self.get_resource(pool);
+        let updated = (current + amount).clamp(0, self.get_resource_limit(pool).max(0));
+        self.set_resource(pool, updated);
+        updated - current
+    }
+
+    fn get_madness_tier(&self) -> MadnessTier {
+        MadnessTier::from_values(self.get_resource(ResourcePoolEnum::Madness), self.get_resource_limit(ResourcePoolEnum::Madness))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::abilities::ability::{AbilityRegistry, KnownAbility};
+use crate::actors::base_npc::BaseNpcStats;
+use crate::actors::models::{StatBlock, StatsEnum};
+use crate::combat::combatant::{Combatant, CombatTeam};
+use crate::combat::psychological::PsychologicalAttack;
+use crate::equipment::equipment_manager::{WeaponStats, WeaponType};
+use crate::loot::loot_container::LootContainer;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NpcTemplateError {
+    #[error("Unknown NPC template: {0}")]
+    UnknownTemplate(String),
+    #[error("Unknown ability {ability_id} on NPC template {template_id}")]
+    UnknownAbility { template_id: String, ability_id: String },
+    #[error("Invalid NPC template data: {0}")]
+    InvalidData(String),
+}
+
+/// How much a stat grows for every level above the first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatGrowth {
+    pub stat: StatsEnum,
+    pub per_level: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NpcWeapon {
+    pub weapon_type: WeaponType,
+    pub min_damage: i32,
+    pub max_damage: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NpcRank {
+    #[default]
+    Normal,
+    Elite,
+    Champion,
+}
+
+/// The bonuses a rank stacks on top of the levelled template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RankModifiers {
+    pub stat_bonus: i32,
+    pub health_percent: i32,
+    pub damage_percent: i32,
+    pub loot_rolls: u32,
+}
+
+impl NpcRank {
+    pub fn get_modifiers(&self) -> RankModifiers {
+        match self {
+            NpcRank::Normal => RankModifiers { stat_bonus: 0, health_percent: 100, damage_percent: 100, loot_rolls: 1 },
+            NpcRank::Elite => RankModifiers { stat_bonus: 2, health_percent: 150, damage_percent: 125, loot_rolls: 2 },
+            NpcRank::Champion => RankModifiers { stat_bonus: 4, health_percent: 250, damage_percent: 150, loot_rolls: 3 },
+        }
+    }
+
+    pub fn get_name_prefix(&self) -> Option<&'static str> {
+        match self {
+            NpcRank::Normal => None,
+            NpcRank::Elite => Some("Elite"),
+            NpcRank::Champion => Some("Champion"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NpcTemplate {
+    pub id: String,
+    pub name: String,
+    pub faction_id: String,
+    // the stats at level 1
+    pub base_stats: StatBlock,
+    pub growth: Vec<StatGrowth>,
+    pub weapon: Option<NpcWeapon>,
+    pub psychological_attack: Option<PsychologicalAttack>,
+    pub ability_ids: Vec<String>,
+    pub loot_table_id: Option<String>,
+    pub dialogue_id: Option<String>,
+}
+
+impl NpcTemplate {
+    /// The template's stats grown to the given level.
+    pub fn get_stats_at_level(&self, level: u32) -> StatBlock {
+        let levels_gained = level.max(1) as i32 - 1;
+        let mut stats = self.base_stats;
+
+        for growth in &self.growth {
+            stats.set_stat(growth.stat, stats.get_stat(growth.stat) + growth.per_level * levels_gained);
+        }
+
+        stats
+    }
+}
+
+/// A line of an encounter (or party) file: `count` NPCs made from one template at the same level and rank.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NpcGroupEntry {
+    pub template_id: String,
+    pub level: u32,
+    #[serde(default)]
+    pub rank: NpcRank,
+    #[serde(default = "default_group_count")]
+    pub count: u32,
+}
+
+fn default_group_count() -> u32 {
+    1
+}
+
+/// A concrete NPC made from a template, ready to be placed in the world or dropped into a fight.
+#[derive(Debug, Clone)]
+pub struct SpawnedNpc {
+    pub template_id: String,
+    pub name: String,
+    pub faction_id: String,
+    pub rank: NpcRank,
+    pub stats: BaseNpcStats,
+    pub weapon: WeaponStats,
+    pub psychological_attack: Option<PsychologicalAttack>,
+    pub abilities: Vec<KnownAbility>,
+    pub loot_table_id: Option<String>,
+    // how many times the loot table is rolled when the NPC is looted
+    pub loot_rolls: u32,
+    pub dialogue_id: Option<String>,
+}
+
+impl SpawnedNpc {
+    /// The corpse the NPC leaves behind, rolling its loot table once per loot roll of its rank.
+    pub fn create_corpse(&self) -> LootContainer {
+        LootContainer::new_corpse(&self.name, self.loot_table_id.clone()).with_rolls(self.loot_rolls)
+    }
+
+    pub fn to_combatant(&self, team: CombatTeam) -> Combatant {
+        let combatant = Combatant::from_npc(&self.name, team, &self.stats)
+            .with_faction(&self.faction_id)
+            .with_npc_id(&self.template_id)
+            .with_weapon(self.weapon)
+            .with_abilities(self.abilities.clone());
+
+        match &self.psychological_attack {
+            Some(attack) => combatant.with_psychological_attack(attack.clone()),
+            None => combatant,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NpcTemplateRegistry {
+    templates: HashMap<String, NpcTemplate>,
+}
+
+impl NpcTemplateRegistry {
+    pub fn new() -> Self {
+        NpcTemplateRegistry {
+            templates: HashMap::new(),
+        }
+    }
+
+    pub fn register_template(&mut self, template: NpcTemplate) {
+        self.templates.insert(template.id.clone(), template);
+    }
+
+    pub fn get_template(&self, template_id: &str) -> Option<&NpcTemplate> {
+        self.templates.get(template_id)
+    }
+
+    /// Registers every template in a JSON array, returning how many were loaded.
+    pub fn load_from_json(&mut self, json: &str) -> Result<usize, NpcTemplateError> {
+        let templates: Vec<NpcTemplate> = serde_json::from_str(json)
+            .map_err(|error| NpcTemplateError::InvalidData(error.to_string()))?;
+        let count = templates.len();
+
+        templates
+            .into_iter()
+            .for_each(|template| self.register_template(template));
+
+        Ok(count)
+    }
+
+    /// Makes an NPC from the template at the given level, with the rank's bonuses applied on top.
+    /// The NPC starts with full resources.
+    pub fn spawn(&self, template_id: &str, level: u32, rank: NpcRank, abilities: &AbilityRegistry) -> Result<SpawnedNpc, NpcTemplateError> {
+        let template = self.get_template(template_id)
+            .ok_or_else(|| NpcTemplateError::UnknownTemplate(template_id.to_string()))?;
+        let modifiers = rank.get_modifiers();
+
+        let mut base_stats = template.get_stats_at_level(level);
+        for stat in [StatsEnum::Strength, StatsEnum::Dexterity, StatsEnum::Constitution, StatsEnum::Intelligence, StatsEnum::Wisdom, StatsEnum::Charisma, StatsEnum::Luck] {
+            base_stats.set_stat(stat, base_stats.get_stat(stat) + modifiers.stat_bonus);
+        }
+
+        let stats = BaseNpcStats::new(level.max(1), Some(base_stats)).with_health_percent(modifiers.health_percent);
+
+        let weapon = match &template.weapon {
+            Some(weapon) => WeaponStats::new(
+                &weapon.weapon_type,
+                weapon.min_damage * modifiers.damage_percent / 100,
+                weapon.max_damage * modifiers.damage_percent / 100
+            ),
+            None => WeaponStats::unarmed(),
+        };
+
+        let known_abilities = template.ability_ids
+            .iter()
+            .map(|ability_id| abilities
+                .get_ability(ability_id)
+                .map(|definition| KnownAbility::new(definition.clone()))
+                .ok_or_else(|| NpcTemplateError::UnknownAbility { template_id: template.id.clone(), ability_id: ability_id.clone() }))
+            .collect::<Result<Vec<KnownAbility>, NpcTemplateError>>()?;
+
+        Ok(SpawnedNpc {
+            template_id: template.id.clone(),
+            name: match rank.get_name_prefix() {
+                Some(prefix) => format!("{prefix} {}", template.name),
+                None => template.name.clone(),
+            },
+            faction_id: template.faction_id.clone(),
+            rank,
+            stats,
+            weapon,
+            psychological_attack: template.psychological_attack.clone(),
+            abilities: known_abilities,
+            loot_table_id: template.loot_table_id.clone(),
+            loot_rolls: modifiers.loot_rolls,
+            dialogue_id: template.dialogue_id.clone(),
+        })
+    }
+
+    /// Spawns every NPC in a group as combatants on the given team. NPCs sharing a template are numbered.
+    pub fn spawn_group(&self, entries: &[NpcGroupEntry], team: CombatTeam, abilities: &AbilityRegistry) -> Result<Vec<Combatant>, NpcTemplateError> {
+        let mut combatants = Vec::new();
+
+        for entry in entries {
+            let npc = self.spawn(&entry.template_id, entry.level, entry.rank, abilities)?;
+            for index in 1..=entry.count {
+                let mut combatant = npc.to_combatant(team);
+                if entry.count > 1 {
+                    combatant.name = format!("{} {index}", npc.name);
+                }
+                combatants.push(combatant);
+            }
+        }
+
+        Ok(combatants)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::abilities::ability::AbilityRegistry;
+    use crate::actors::models::{ComputedAttributeEnum, ComputedAttributeValues, ResourcePoolEnum, ResourcePoolValues, StatsEnum, StatValues};
+    use crate::actors::npc_template::{NpcGroupEntry, NpcRank, NpcTemplateError, NpcTemplateRegistry};
+    use crate::combat::combatant::CombatTeam;
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::loot::loot_table::{LootContext, LootDrop, LootEntry, LootTable, LootTableRegistry};
+    use crate::utils::rng::SeededRng;
+
+    const BANDIT_JSON: &str = r#"[
+        {
+            "id": "bandit",
+            "name": "Bandit",
+            "faction_id": "roadside_bandits",
+            "base_stats": { "strength": 8, "dexterity": 12 },
+            "growth": [
+                { "stat": "Strength", "per_level": 1 },
+                { "stat": "Constitution", "per_level": 2 }
+            ],
+            "weapon": { "weapon_type": "Dagger", "min_damage": 4, "max_damage": 8 },
+            "psychological_attack": null,
+            "ability_ids": [],
+            "loot_table_id": "bandit_loot",
+            "dialogue_id": null
+        }
+    ]"#;
+
+    #[test]
+    fn test_spawn_scales_with_level_and_rank() {
+        let mut registry = NpcTemplateRegistry::new();
+        assert_eq!(registry.load_from_json(BANDIT_JSON), Ok(1));
+
+        let abilities = AbilityRegistry::new();
+        let bandit = registry.spawn("bandit", 1, NpcRank::Normal, &abilities).unwrap();
+        let veteran = registry.spawn("bandit", 5, NpcRank::Normal, &abilities).unwrap();
+        let champion = registry.spawn("bandit", 5, NpcRank::Champion, &abilities).unwrap();
+
+        assert_eq!(bandit.stats.get_stat(StatsEnum::Strength), 8);
+        assert_eq!(bandit.stats.get_stat(StatsEnum::Dexterity), 12);
+        assert_eq!(bandit.stats.get_stat(StatsEnum::Wisdom), 10);
+        assert_eq!(veteran.stats.get_stat(StatsEnum::Strength), 12);
+        assert_eq!(veteran.stats.get_stat(StatsEnum::Constitution), 18);
+        assert_eq!(veteran.stats.level, 5);
+        assert!(veteran.stats.get_computed_attribute(ComputedAttributeEnum::Health) > bandit.stats.get_computed_attribute(ComputedAttributeEnum::Health));
+
+        assert_eq!(champion.name, "Champion Bandit");
+        assert_eq!(champion.stats.get_stat(StatsEnum::Strength), 16);
+        assert_eq!(champion.weapon.max_damage, 12);
+        assert_eq!(champion.loot_rolls, 3);
+        assert_eq!(
+            champion.stats.get_resource(ResourcePoolEnum::Health),
+            champion.stats.get_computed_attribute(ComputedAttributeEnum::Health)
+        );
+        assert!(champion.stats.get_computed_attribute(ComputedAttributeEnum::Health) > veteran.stats.get_computed_attribute(ComputedAttributeEnum::Health) * 2);
+
+        assert_eq!(registry.spawn("ghoul", 1, NpcRank::Normal, &abilities).unwrap_err(), NpcTemplateError::UnknownTemplate("ghoul".to_string()));
+    }
+
+    #[test]
+    fn test_spawn_group_numbers_copies() {
+        let mut registry = NpcTemplateRegistry::new();
+        registry.load_from_json(BANDIT_JSON).unwrap();
+        let abilities = AbilityRegistry::new();
+
+        let entries: Vec<NpcGroupEntry> = serde_json::from_str(r#"[
+            { "template_id": "bandit", "level": 2, "count": 2 },
+            { "template_id": "bandit", "level": 4, "rank": "Elite" }
+        ]"#).unwrap();
+        let group = registry.spawn_group(&entries, CombatTeam::Hostile, &abilities).unwrap();
+        let names: Vec<&str> = group.iter().map(|combatant| combatant.name.as_str()).collect();
+        assert_eq!(names, vec!["Bandit 1", "Bandit 2", "Elite Bandit"]);
+        assert!(group.iter().all(|combatant| combatant.team == CombatTeam::Hostile && combatant.faction_id.as_deref() == Some("roadside_bandits")));
+        assert!(group.iter().all(|combatant| combatant.npc_id.as_deref() == Some("bandit")));
+        assert_eq!(group[2].level, 4);
+
+        let missing = vec![NpcGroupEntry { template_id: "ghoul".to_string(), level: 1, rank: NpcRank::Normal, count: 1 }];
+        assert_eq!(registry.spawn_group(&missing, CombatTeam::Hostile, &abilities).unwrap_err(), NpcTemplateError::UnknownTemplate("ghoul".to_string()));
+    }
+
+    #[test]
+    fn test_rank_bonuses_last_and_drop_more_loot() {
+        let mut registry = NpcTemplateRegistry::new();
+        registry.load_from_json(BANDIT_JSON).unwrap();
+        let abilities = AbilityRegistry::new();
+
+        let bandit = registry.spawn("bandit", 3, NpcRank::Normal, &abilities).unwrap();
+        let mut elite = registry.spawn("bandit", 3, NpcRank::Elite, &abilities).unwrap();
+        let health = elite.stats.get_computed_attribute(ComputedAttributeEnum::Health);
+
+        // growing a stat recomputes the attributes without losing the rank's health
+        elite.stats.increase_stat(StatsEnum::Strength, 1);
+        assert_eq!(elite.stats.get_computed_attribute(ComputedAttributeEnum::Health), health + 15);
+        assert!(elite.stats.get_computed_attribute(ComputedAttributeEnum::Health) > bandit.stats.get_computed_attribute(ComputedAttributeEnum::Health) * 3 / 2);
+
+        let mut tables = LootTableRegistry::new();
+        tables.register_table(LootTable {
+            id: "bandit_loot".to_string(),
+            guaranteed: vec![LootEntry { drop: LootDrop::Item { item_id: "copper_crown".to_string(), min_quantity: 2, max_quantity: 2 }, weight: 1, conditions: Vec::new() }],
+            entries: Vec::new(),
+            min_rolls: 0,
+            max_rolls: 0,
+        });
+        let mut items = ItemRegistry::new();
+        items.register_item(ItemDefinition::new("copper_crown", "Copper Crown", "Worn smooth.", ItemCategory::Miscellaneous, 1, 100));
+        let flags = HashSet::new();
+        let context = LootContext { level: 3, story_flags: &flags };
+
+        let mut corpse = bandit.create_corpse();
+        corpse.open(&tables, &items, &context, &mut SeededRng::new(1)).unwrap();
+        let mut elite_corpse = elite.create_corpse();
+        elite_corpse.open(&tables, &items, &context, &mut SeededRng::new(1)).unwrap();
+        assert_eq!(corpse.name, "Corpse of Bandit");
+        assert_eq!(corpse.contents.get_item_count("copper_crown"), 2);
+        assert_eq!(elite_corpse.contents.get_item_count("copper_crown"), 4);
+    }
+}
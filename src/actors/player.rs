@@ -1,11 +1,13 @@
 use std::sync::{Arc, Mutex};
-use crate::actors::models::{ComputedAttributeEnum, ComputedAttributes, ComputedAttributeValues, StatBlock, StatsEnum, StatValues};
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ComputedAttributeEnum, ComputedAttributes, ComputedAttributeValues, ResourcePoolEnum, ResourcePools, ResourcePoolValues, StatBlock, StatsEnum, StatValues};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PlayerStats {
     pub level: u32,
     pub base_stats: StatBlock,
-    pub computed_attributes: ComputedAttributes
+    pub computed_attributes: ComputedAttributes,
+    pub resources: ResourcePools
 }
 
 impl StatValues for PlayerStats {
@@ -40,13 +42,29 @@ impl ComputedAttributeValues for PlayerStats {
     }
 }
 
+impl ResourcePoolValues for PlayerStats {
+    fn get_resource(&self, pool: ResourcePoolEnum) -> i32 {
+        self.resources.get_resource(pool)
+    }
+
+    fn get_resource_limit(&self, pool: ResourcePoolEnum) -> i32 {
+        self.computed_attributes.get_computed_attribute(pool.limiting_attribute())
+    }
+
+    fn set_resource(&mut self, pool: ResourcePoolEnum, value: i32) {
+        self.resources.set_resource(pool, value);
+    }
+}
+
 impl PlayerStats {
     pub fn new() -> Self {
         let base_stats = StatBlock::default();
+        let computed_attributes = ComputedAttributes::new(base_stats);
         PlayerStats {
             level: 1,
             base_stats,
-            computed_attributes: ComputedAttributes::new(base_stats)
+            computed_attributes,
+            resources: ResourcePools::new(computed_attributes)
         }
     }
 
@@ -54,6 +72,7 @@ impl PlayerStats {
         self.level += 1;
         self.set_passive_base_stat_increase();
         self.computed_attributes = ComputedAttributes::new(self.base_stats);
+        self.resources.clamp_to(&self.computed_attributes);
     }
 
     fn set_passive_base_stat_increase(&mut self) {
@@ -80,3 +99,9 @@ impl PlayerStats {
         &mut self.base_stats
     }
 }
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        PlayerStats::new()
+    }
+}
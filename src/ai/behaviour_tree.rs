@@ -0,0 +1,256 @@
+use std::fmt::Debug;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BehaviourTreeError {
+    #[error("Invalid behaviour tree data: {0}")]
+    InvalidData(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NodeStatus {
+    Success,
+    Failure,
+    // the action is still underway, e.g. an NPC walking somewhere over several ticks
+    Running,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Decorator {
+    Invert,
+    AlwaysSucceed,
+    AlwaysFail,
+    // ticks the child up to n times in a row, stopping as soon as it doesn't succeed
+    Repeat(u32),
+}
+
+/// A node of a behaviour tree, generic over the conditions it can check and the actions it can take
+/// so that the same tree machinery drives both combat and exploration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BehaviourNode<C, A> {
+    // runs children in order until one fails or is still running
+    Sequence(Vec<BehaviourNode<C, A>>),
+    // runs children in order until one succeeds or is still running
+    Selector(Vec<BehaviourNode<C, A>>),
+    Decorator { decorator: Decorator, child: Box<BehaviourNode<C, A>> },
+    Condition(C),
+    Action(A),
+}
+
+/// Whatever a tree is ticked against: the brain's view of the world and its way of acting on it.
+pub trait BehaviourContext {
+    type Condition;
+    type Action;
+
+    fn check(&self, condition: &Self::Condition) -> bool;
+    fn perform(&mut self, action: &Self::Action) -> NodeStatus;
+}
+
+/// One node visited during a tick, in the order they were visited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub depth: usize,
+    pub label: String,
+    pub status: NodeStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TickTrace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl TickTrace {
+    /// The actions that ran this tick, e.g. to show what an NPC decided in a debug overlay.
+    pub fn get_actions(&self) -> Vec<&TraceEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.label.starts_with("Action"))
+            .collect()
+    }
+
+    /// An indented line per visited node for debugging.
+    pub fn to_lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| format!("{}{} -> {:?}", "  ".repeat(entry.depth), entry.label, entry.status))
+            .collect()
+    }
+}
+
+/// A behaviour tree loaded from data. Ticking is stateless, the tree is walked from the root each tick,
+/// so the same context always produces the same result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BehaviourTree<C, A> {
+    pub id: String,
+    pub root: BehaviourNode<C, A>,
+}
+
+impl<C: Debug + DeserializeOwned, A: Debug + DeserializeOwned> BehaviourTree<C, A> {
+    pub fn new(id: &str, root: BehaviourNode<C, A>) -> Self {
+        BehaviourTree {
+            id: id.to_string(),
+            root,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, BehaviourTreeError> {
+        serde_json::from_str(json).map_err(|error| BehaviourTreeError::InvalidData(error.to_string()))
+    }
+
+    pub fn tick<X: BehaviourContext<Condition = C, Action = A>>(&self, context: &mut X) -> (NodeStatus, TickTrace) {
+        let mut trace = TickTrace::default();
+        let status = Self::tick_node(&self.root, context, 0, &mut trace);
+
+        (status, trace)
+    }
+
+    fn tick_node<X: BehaviourContext<Condition = C, Action = A>>(node: &BehaviourNode<C, A>, context: &mut X, depth: usize, trace: &mut TickTrace) -> NodeStatus {
+        // reserve the entry so parents are listed before their children
+        let index = trace.entries.len();
+        trace.entries.push(TraceEntry { depth, label: String::new(), status: NodeStatus::Failure });
+
+        let (label, status) = match node {
+            BehaviourNode::Sequence(children) => {
+                let status = children
+                    .iter()
+                    .map(|child| Self::tick_node(child, context, depth + 1, trace))
+                    .find(|status| *status != NodeStatus::Success)
+                    .unwrap_or(NodeStatus::Success);
+
+                ("Sequence".to_string(), status)
+            },
+            BehaviourNode::Selector(children) => {
+                let status = children
+                    .iter()
+                    .map(|child| Self::tick_node(child, context, depth + 1, trace))
+                    .find(|status| *status != NodeStatus::Failure)
+                    .unwrap_or(NodeStatus::Failure);
+
+                ("Selector".to_string(), status)
+            },
+            BehaviourNode::Decorator { decorator, child } => {
+                let status = match decorator {
+                    Decorator::Invert => match Self::tick_node(child, context, depth + 1, trace) {
+                        NodeStatus::Success => NodeStatus::Failure,
+                        NodeStatus::Failure => NodeStatus::Success,
+                        NodeStatus::Running => NodeStatus::Running,
+                    },
+                    Decorator::AlwaysSucceed => {
+                        Self::tick_node(child, context, depth + 1, trace);
+                        NodeStatus::Success
+                    },
+                    Decorator::AlwaysFail => {
+                        Self::tick_node(child, context, depth + 1, trace);
+                        NodeStatus::Failure
+                    },
+                    Decorator::Repeat(times) => (0..*times)
+                        .map(|_| Self::tick_node(child, context, depth + 1, trace))
+                        .find(|status| *status != NodeStatus::Success)
+                        .unwrap_or(NodeStatus::Success),
+                };
+
+                (format!("Decorator({decorator:?})"), status)
+            },
+            BehaviourNode::Condition(condition) => {
+                let status = match context.check(condition) {
+                    true => NodeStatus::Success,
+                    false => NodeStatus::Failure,
+                };
+
+                (format!("Condition({condition:?})"), status)
+            },
+            BehaviourNode::Action(action) => (format!("Action({action:?})"), context.perform(action)),
+        };
+
+        trace.entries[index] = TraceEntry { depth, label, status };
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::behaviour_tree::{BehaviourContext, BehaviourNode, BehaviourTree, Decorator, NodeStatus};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    enum TestCondition {
+        IsHungry,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    enum TestAction {
+        Eat,
+        Wander,
+    }
+
+    #[derive(Default)]
+    struct TestContext {
+        is_hungry: bool,
+        performed: Vec<TestAction>,
+    }
+
+    impl BehaviourContext for TestContext {
+        type Condition = TestCondition;
+        type Action = TestAction;
+
+        fn check(&self, condition: &TestCondition) -> bool {
+            match condition {
+                TestCondition::IsHungry => self.is_hungry,
+            }
+        }
+
+        fn perform(&mut self, action: &TestAction) -> NodeStatus {
+            self.performed.push(action.clone());
+            match action {
+                TestAction::Eat => NodeStatus::Success,
+                TestAction::Wander => NodeStatus::Running,
+            }
+        }
+    }
+
+    #[test]
+    fn test_tick_from_json() {
+        let tree: BehaviourTree<TestCondition, TestAction> = BehaviourTree::from_json(r#"{
+            "id": "villager",
+            "root": { "Selector": [
+                { "Sequence": [ { "Condition": "IsHungry" }, { "Action": "Eat" } ] },
+                { "Action": "Wander" }
+            ] }
+        }"#).unwrap();
+
+        let mut hungry = TestContext { is_hungry: true, ..TestContext::default() };
+        let (status, trace) = tree.tick(&mut hungry);
+        assert_eq!(status, NodeStatus::Success);
+        assert_eq!(hungry.performed, vec![TestAction::Eat]);
+        assert_eq!(trace.to_lines(), vec![
+            "Selector -> Success",
+            "  Sequence -> Success",
+            "    Condition(IsHungry) -> Success",
+            "    Action(Eat) -> Success",
+        ]);
+
+        let mut fed = TestContext::default();
+        let (status, trace) = tree.tick(&mut fed);
+        assert_eq!(status, NodeStatus::Running);
+        assert_eq!(fed.performed, vec![TestAction::Wander]);
+        assert_eq!(trace.get_actions().len(), 1);
+    }
+
+    #[test]
+    fn test_decorators() {
+        let tree = BehaviourTree::new("glutton", BehaviourNode::Sequence(vec![
+            BehaviourNode::Decorator { decorator: Decorator::Invert, child: Box::new(BehaviourNode::Condition(TestCondition::IsHungry)) },
+            BehaviourNode::Decorator { decorator: Decorator::Repeat(3), child: Box::new(BehaviourNode::Action(TestAction::Eat)) },
+        ]));
+
+        let mut context = TestContext::default();
+        assert_eq!(tree.tick(&mut context).0, NodeStatus::Success);
+        assert_eq!(context.performed, vec![TestAction::Eat, TestAction::Eat, TestAction::Eat]);
+
+        let mut context = TestContext { is_hungry: true, ..TestContext::default() };
+        assert_eq!(tree.tick(&mut context).0, NodeStatus::Failure);
+        assert!(context.performed.is_empty());
+    }
+}
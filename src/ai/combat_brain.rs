@@ -0,0 +1,345 @@
+use serde::{Deserialize, Serialize};
+use crate::abilities::ability::{TargetFilter, TargetingRule};
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+use crate::ai::behaviour_tree::{BehaviourContext, BehaviourNode, BehaviourTree, NodeStatus, TickTrace};
+use crate::combat::combat_engine::{AbilityTarget, Combat, CombatAction, CombatController};
+use crate::combat::combatant::{Combatant, CombatantId, GridPosition};
+use crate::combat::psychological::MentalConditionKind;
+
+/// The share of a pool that is filled, in percent of its limit.
+pub fn get_resource_percent(combatant: &Combatant, pool: ResourcePoolEnum) -> i32 {
+    let limit = combatant.get_resource_limit(pool);
+    if limit <= 0 {
+        return 0;
+    }
+
+    combatant.get_resource(pool) * 100 / limit
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CombatCondition {
+    HealthBelowPercent(i32),
+    MadnessAbovePercent(i32),
+    AllyHealthBelowPercent(i32),
+    EnemyInReach,
+    EnemiesAtLeast(usize),
+    AbilityReady(String),
+    HasMentalCondition(MentalConditionKind),
+    HasAction,
+    HasMove,
+}
+
+impl CombatCondition {
+    pub fn is_met(&self, combat: &Combat, actor: CombatantId) -> bool {
+        let Some(combatant) = combat.get_combatant(actor) else {
+            return false;
+        };
+        let combatants = combat.get_combatants();
+
+        match self {
+            CombatCondition::HealthBelowPercent(percent) => get_resource_percent(combatant, ResourcePoolEnum::Health) < *percent,
+            CombatCondition::MadnessAbovePercent(percent) => get_resource_percent(combatant, ResourcePoolEnum::Madness) > *percent,
+            CombatCondition::AllyHealthBelowPercent(percent) => combat
+                .get_allies_of(actor)
+                .iter()
+                .any(|ally| get_resource_percent(&combatants[*ally], ResourcePoolEnum::Health) < *percent),
+            CombatCondition::EnemyInReach => combat
+                .get_enemies_of(actor)
+                .iter()
+                .any(|enemy| combatant.can_reach(&combatants[*enemy])),
+            CombatCondition::EnemiesAtLeast(count) => combat.get_enemies_of(actor).len() >= *count,
+            CombatCondition::AbilityReady(ability_id) => combatant.abilities
+                .iter()
+                .any(|known| known.definition.id == *ability_id && known.is_ready() && known.definition.can_pay_costs(combatant)),
+            CombatCondition::HasMentalCondition(kind) => combatant.has_mental_condition(*kind),
+            CombatCondition::HasAction => combatant.budget.has_action,
+            CombatCondition::HasMove => combatant.budget.has_move,
+        }
+    }
+}
+
+/// What an NPC brain wants to do, before it is turned into a concrete `CombatAction`.
+/// Every brain (behaviour trees, utility scoring) picks from these so they can be swapped freely.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CombatIntent {
+    AttackWeakest,
+    AttackNearest,
+    MindAttack,
+    UseAbility(String),
+    Approach,
+    Flee,
+    Defend,
+    EndTurn,
+}
+
+impl CombatIntent {
+    /// The action that carries out the intent, or None if it can't be done right now.
+    pub fn resolve(&self, combat: &Combat, actor: CombatantId) -> Option<CombatAction> {
+        let combatant = combat.get_combatant(actor)?;
+        let combatants = combat.get_combatants();
+        let enemies = combat.get_enemies_of(actor);
+        let is_feared = combatant.has_mental_condition(MentalConditionKind::Feared);
+        let distance_to = |id: &CombatantId| combatant.position.distance_to(&combatants[*id].position);
+
+        match self {
+            CombatIntent::AttackWeakest | CombatIntent::AttackNearest => {
+                if !combatant.budget.has_action || is_feared {
+                    return None;
+                }
+
+                let in_reach = enemies
+                    .iter()
+                    .filter(|enemy| combatant.can_reach(&combatants[**enemy]));
+                let target = match self {
+                    CombatIntent::AttackWeakest => in_reach.min_by_key(|enemy| combatants[**enemy].get_resource(ResourcePoolEnum::Health)),
+                    _ => in_reach.min_by_key(|enemy| distance_to(enemy)),
+                };
+
+                target.map(|target| CombatAction::Attack { target: *target })
+            },
+            CombatIntent::MindAttack => {
+                let attack = combatant.psychological_attack.as_ref()?;
+                if !combatant.budget.has_action || is_feared {
+                    return None;
+                }
+
+                enemies
+                    .iter()
+                    .filter(|enemy| distance_to(enemy) <= attack.reach)
+                    .min_by_key(|enemy| combatants[**enemy].get_resource(ResourcePoolEnum::Madness))
+                    .map(|target| CombatAction::MindAttack { target: *target })
+            },
+            CombatIntent::UseAbility(ability_id) => {
+                let index = combatant.abilities
+                    .iter()
+                    .position(|known| known.definition.id == *ability_id)?;
+                let known = &combatant.abilities[index];
+                let ability = &known.definition;
+                if !combatant.budget.has_action || !known.is_ready() || !ability.can_pay_costs(combatant) || (is_feared && ability.is_offensive()) {
+                    return None;
+                }
+
+                let candidates: Vec<CombatantId> = match ability.affects {
+                    TargetFilter::Allies => std::iter::once(actor).chain(combat.get_allies_of(actor)).collect(),
+                    // abilities that hit everyone are still aimed at the enemy
+                    TargetFilter::Enemies | TargetFilter::Everyone => enemies.clone(),
+                };
+                let in_range = candidates
+                    .iter()
+                    .filter(|id| distance_to(id) <= ability.get_range());
+
+                // healing and buffs go to whoever is worst off, harm to whoever is closest to going down
+                let target = in_range.min_by_key(|id| (combatants[**id].get_resource(ResourcePoolEnum::Health), distance_to(id)));
+
+                let target = match ability.targeting {
+                    TargetingRule::SelfOnly => Some(AbilityTarget::Caster),
+                    TargetingRule::Single { .. } => target.map(|target| AbilityTarget::Combatant(*target)),
+                    TargetingRule::Area { .. } | TargetingRule::Line { .. } => target.map(|target| AbilityTarget::Position(combatants[*target].position)),
+                };
+
+                target.map(|target| CombatAction::UseAbility { ability: index, target })
+            },
+            CombatIntent::Approach => {
+                if !combatant.budget.has_move {
+                    return None;
+                }
+
+                let nearest = enemies.iter().min_by_key(|enemy| distance_to(enemy))?;
+                combat
+                    .find_approach_position(actor, *nearest)
+                    .map(|position| CombatAction::Move { to: position })
+            },
+            CombatIntent::Flee => {
+                if !combatant.budget.has_move || enemies.is_empty() {
+                    return None;
+                }
+
+                find_flee_position(combat, combatant, &enemies).map(|position| CombatAction::Move { to: position })
+            },
+            CombatIntent::Defend => combatant.budget.has_action.then_some(CombatAction::Defend),
+            CombatIntent::EndTurn => Some(CombatAction::EndTurn),
+        }
+    }
+}
+
+/// The free cell within the combatant's movement that is furthest from the closest enemy,
+/// preferring the shortest walk. None if there's nowhere safer to go.
+fn find_flee_position(combat: &Combat, combatant: &Combatant, enemies: &[CombatantId]) -> Option<GridPosition> {
+    let combatants = combat.get_combatants();
+    let movement = combatant.get_movement();
+    let safety = |position: &GridPosition| enemies
+        .iter()
+        .map(|enemy| position.distance_to(&combatants[*enemy].position))
+        .min()
+        .unwrap_or(0);
+    let current_safety = safety(&combatant.position);
+
+    let mut best: Option<(i32, i32, GridPosition)> = None;
+    for dx in -movement..=movement {
+        for dy in -movement..=movement {
+            let position = GridPosition::new(combatant.position.x + dx, combatant.position.y + dy);
+            if (dx == 0 && dy == 0) || !combat.is_position_free(&position) {
+                continue;
+            }
+
+            let position_safety = safety(&position);
+            let walked = combatant.position.distance_to(&position);
+            if best.is_none_or(|(best_safety, best_walked, _)| (position_safety, -walked) > (best_safety, -best_walked)) {
+                best = Some((position_safety, walked, position));
+            }
+        }
+    }
+
+    best
+        .filter(|(position_safety, _, _)| *position_safety > current_safety)
+        .map(|(_, _, position)| position)
+}
+
+/// A behaviour tree ticked against one combatant's view of a fight.
+/// The first intent that resolves is the one the combatant takes, later action nodes fail.
+pub struct CombatBrainContext<'a> {
+    pub combat: &'a Combat,
+    pub actor: CombatantId,
+    pub chosen_action: Option<CombatAction>,
+}
+
+impl<'a> CombatBrainContext<'a> {
+    pub fn new(combat: &'a Combat, actor: CombatantId) -> Self {
+        CombatBrainContext {
+            combat,
+            actor,
+            chosen_action: None,
+        }
+    }
+}
+
+impl BehaviourContext for CombatBrainContext<'_> {
+    type Condition = CombatCondition;
+    type Action = CombatIntent;
+
+    fn check(&self, condition: &CombatCondition) -> bool {
+        condition.is_met(self.combat, self.actor)
+    }
+
+    fn perform(&mut self, intent: &CombatIntent) -> NodeStatus {
+        if self.chosen_action.is_some() {
+            return NodeStatus::Failure;
+        }
+
+        match intent.resolve(self.combat, self.actor) {
+            Some(action) => {
+                self.chosen_action = Some(action);
+                NodeStatus::Success
+            },
+            None => NodeStatus::Failure,
+        }
+    }
+}
+
+pub type CombatBehaviourTree = BehaviourTree<CombatCondition, CombatIntent>;
+
+/// Flees when badly hurt, otherwise uses whatever abilities are ready, then fights and closes in.
+pub fn create_default_combat_tree(ability_ids: &[String]) -> CombatBehaviourTree {
+    let mut options = vec![
+        BehaviourNode::Sequence(vec![
+            BehaviourNode::Condition(CombatCondition::HealthBelowPercent(25)),
+            BehaviourNode::Action(CombatIntent::Flee),
+        ]),
+    ];
+    options.extend(ability_ids
+        .iter()
+        .map(|ability_id| BehaviourNode::Action(CombatIntent::UseAbility(ability_id.clone()))));
+    options.extend([
+        BehaviourNode::Action(CombatIntent::AttackWeakest),
+        BehaviourNode::Action(CombatIntent::MindAttack),
+        BehaviourNode::Action(CombatIntent::Approach),
+        BehaviourNode::Sequence(vec![
+            BehaviourNode::Condition(CombatCondition::HasMentalCondition(MentalConditionKind::Feared)),
+            BehaviourNode::Action(CombatIntent::Defend),
+        ]),
+        BehaviourNode::Action(CombatIntent::EndTurn),
+    ]);
+
+    BehaviourTree::new("default_combat", BehaviourNode::Selector(options))
+}
+
+/// Drives combatants with a behaviour tree, keeping the trace of the last decision for debugging.
+#[derive(Debug, Clone)]
+pub struct BehaviourTreeController {
+    tree: CombatBehaviourTree,
+    last_trace: TickTrace,
+}
+
+impl BehaviourTreeController {
+    pub fn new(tree: CombatBehaviourTree) -> Self {
+        BehaviourTreeController {
+            tree,
+            last_trace: TickTrace::default(),
+        }
+    }
+
+    pub fn get_last_trace(&self) -> &TickTrace {
+        &self.last_trace
+    }
+}
+
+impl Default for BehaviourTreeController {
+    fn default() -> Self {
+        Self::new(create_default_combat_tree(&[]))
+    }
+}
+
+impl CombatController for BehaviourTreeController {
+    fn choose_action(&mut self, combat: &Combat, actor: CombatantId) -> CombatAction {
+        let mut context = CombatBrainContext::new(combat, actor);
+        let (_, trace) = self.tree.tick(&mut context);
+        self.last_trace = trace;
+
+        context.chosen_action.unwrap_or(CombatAction::EndTurn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+    use crate::actors::player::PlayerStats;
+    use crate::ai::combat_brain::{BehaviourTreeController, CombatIntent};
+    use crate::combat::combat_engine::{Combat, CombatAction, CombatController, CombatStatus};
+    use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+    use crate::equipment::equipment_manager::{WeaponStats, WeaponType};
+
+    fn create_encounter() -> Vec<Combatant> {
+        vec![
+            Combatant::from_player_stats("Player", &PlayerStats::new())
+                .with_weapon(WeaponStats::new(&WeaponType::Sword, 10, 16))
+                .with_position(GridPosition::new(0, 0)),
+            Combatant::from_npc("Bandit", CombatTeam::Hostile, &BaseNpcStats::new(1, None))
+                .with_weapon(WeaponStats::new(&WeaponType::Dagger, 2, 4))
+                .with_position(GridPosition::new(2, 0)),
+        ]
+    }
+
+    #[test]
+    fn test_flee_at_low_health() {
+        let mut combat = Combat::new(create_encounter(), 5);
+        combat.get_combatant_mut(1).unwrap().set_resource(ResourcePoolEnum::Health, 1);
+
+        let Some(CombatAction::Move { to }) = CombatIntent::Flee.resolve(&combat, 1) else {
+            panic!("the bandit should run");
+        };
+        assert!(to.distance_to(&GridPosition::new(0, 0)) > 2);
+
+        let mut controller = BehaviourTreeController::default();
+        let action = controller.choose_action(&combat, 1);
+        assert_eq!(action, CombatAction::Move { to });
+        assert_eq!(controller.get_last_trace().get_actions().len(), 1);
+    }
+
+    #[test]
+    fn test_default_tree_fights() {
+        let mut combat = Combat::new(create_encounter(), 11);
+
+        assert_eq!(combat.run(&mut BehaviourTreeController::default(), 100), CombatStatus::Victory(CombatTeam::Party));
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use crate::ai::behaviour_tree::{BehaviourContext, BehaviourNode, BehaviourTree, NodeStatus};
+use crate::combat::combatant::GridPosition;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExplorationCondition {
+    HasPointOfInterest,
+    IsHome,
+    // further than the given number of cells from home
+    IsAwayFromHome(i32),
+    HasPatrolRoute,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExplorationIntent {
+    // walks the patrol route, looping back to the start after the last point
+    Patrol,
+    // walks to the point of interest and forgets it once there
+    Investigate,
+    ReturnHome,
+    Idle,
+}
+
+/// Where an NPC is out of combat and where it wants to be. Each tick moves it at most `speed` cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorationState {
+    pub position: GridPosition,
+    pub home: GridPosition,
+    pub patrol_route: Vec<GridPosition>,
+    pub patrol_index: usize,
+    // e.g. a noise the NPC heard or a body it spotted
+    pub point_of_interest: Option<GridPosition>,
+    pub speed: i32,
+}
+
+impl ExplorationState {
+    pub fn new(home: GridPosition, speed: i32) -> Self {
+        ExplorationState {
+            position: home,
+            home,
+            patrol_route: Vec::new(),
+            patrol_index: 0,
+            point_of_interest: None,
+            speed,
+        }
+    }
+
+    pub fn with_patrol_route(mut self, patrol_route: Vec<GridPosition>) -> Self {
+        self.patrol_route = patrol_route;
+        self
+    }
+
+    /// Steps towards the destination, returning Success once the NPC stands on it.
+    fn walk_to(&mut self, destination: GridPosition) -> NodeStatus {
+        self.position = self.position.step_towards(&destination, self.speed);
+
+        match self.position == destination {
+            true => NodeStatus::Success,
+            false => NodeStatus::Running,
+        }
+    }
+}
+
+impl BehaviourContext for ExplorationState {
+    type Condition = ExplorationCondition;
+    type Action = ExplorationIntent;
+
+    fn check(&self, condition: &ExplorationCondition) -> bool {
+        match condition {
+            ExplorationCondition::HasPointOfInterest => self.point_of_interest.is_some(),
+            ExplorationCondition::IsHome => self.position == self.home,
+            ExplorationCondition::IsAwayFromHome(distance) => self.position.distance_to(&self.home) > *distance,
+            ExplorationCondition::HasPatrolRoute => !self.patrol_route.is_empty(),
+        }
+    }
+
+    fn perform(&mut self, intent: &ExplorationIntent) -> NodeStatus {
+        match intent {
+            ExplorationIntent::Patrol => {
+                let Some(waypoint) = self.patrol_route.get(self.patrol_index).copied() else {
+                    return NodeStatus::Failure;
+                };
+
+                let status = self.walk_to(waypoint);
+                if status == NodeStatus::Success {
+                    self.patrol_index = (self.patrol_index + 1) % self.patrol_route.len();
+                }
+                status
+            },
+            ExplorationIntent::Investigate => {
+                let Some(point_of_interest) = self.point_of_interest else {
+                    return NodeStatus::Failure;
+                };
+
+                let status = self.walk_to(point_of_interest);
+                if status == NodeStatus::Success {
+                    self.point_of_interest = None;
+                }
+                status
+            },
+            ExplorationIntent::ReturnHome => self.walk_to(self.home),
+            ExplorationIntent::Idle => NodeStatus::Success,
+        }
+    }
+}
+
+pub type ExplorationBehaviourTree = BehaviourTree<ExplorationCondition, ExplorationIntent>;
+
+/// Investigates anything suspicious, otherwise patrols, otherwise heads home and idles there.
+pub fn create_default_exploration_tree() -> ExplorationBehaviourTree {
+    BehaviourTree::new("default_exploration", BehaviourNode::Selector(vec![
+        BehaviourNode::Action(ExplorationIntent::Investigate),
+        BehaviourNode::Action(ExplorationIntent::Patrol),
+        BehaviourNode::Sequence(vec![
+            BehaviourNode::Condition(ExplorationCondition::IsHome),
+            BehaviourNode::Action(ExplorationIntent::Idle),
+        ]),
+        BehaviourNode::Action(ExplorationIntent::ReturnHome),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::behaviour_tree::NodeStatus;
+    use crate::ai::exploration_brain::{create_default_exploration_tree, ExplorationState};
+    use crate::combat::combatant::GridPosition;
+
+    #[test]
+    fn test_investigate_then_patrol() {
+        let tree = create_default_exploration_tree();
+        let mut guard = ExplorationState::new(GridPosition::new(0, 0), 2)
+            .with_patrol_route(vec![GridPosition::new(0, 2), GridPosition::new(0, 0)]);
+        guard.point_of_interest = Some(GridPosition::new(4, 0));
+
+        assert_eq!(tree.tick(&mut guard).0, NodeStatus::Running);
+        assert_eq!(guard.position, GridPosition::new(2, 0));
+        assert_eq!(tree.tick(&mut guard).0, NodeStatus::Success);
+        assert_eq!(guard.point_of_interest, None);
+
+        let (status, trace) = tree.tick(&mut guard);
+        assert_eq!(status, NodeStatus::Running);
+        assert_eq!(guard.position, GridPosition::new(2, 2));
+        assert_eq!(trace.get_actions().last().unwrap().label, "Action(Patrol)");
+
+        tree.tick(&mut guard);
+        assert_eq!(guard.position, GridPosition::new(0, 2));
+        assert_eq!(guard.patrol_index, 1);
+    }
+}
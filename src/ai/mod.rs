@@ -0,0 +1,4 @@
+pub mod behaviour_tree;
+pub mod combat_brain;
+pub mod exploration_brain;
+pub mod utility_brain;
\ No newline at end of file
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::actors::models::ResourcePoolEnum;
+use crate::ai::combat_brain::{get_resource_percent, CombatIntent};
+use crate::combat::combat_engine::{AbilityTarget, Combat, CombatAction, CombatController};
+use crate::combat::combatant::{Combatant, CombatantId};
+use crate::utils::rng::SeededRng;
+
+// distances at or beyond this many cells count as fully far away
+const MAX_CONSIDERED_DISTANCE: f32 = 10.0;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UtilityBrainError {
+    #[error("Invalid utility brain data: {0}")]
+    InvalidData(String),
+}
+
+/// What a consideration looks at. Every input is normalised to `0.0..=1.0` before the curve is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConsiderationInput {
+    OwnHealthRatio,
+    OwnManaRatio,
+    OwnStaminaRatio,
+    OwnMadnessRatio,
+    TargetHealthRatio,
+    // how big a share of the actor's remaining health the target can take in one hit
+    TargetThreat,
+    TargetDistance,
+}
+
+/// Shapes a normalised input into a score, clamped to `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ResponseCurve {
+    Linear { slope: f32, offset: f32 },
+    // x^exponent, flipped to 1 - x^exponent when inverted
+    Polynomial { exponent: f32, inverted: bool },
+    Logistic { steepness: f32, midpoint: f32 },
+    // 1 at or above the threshold, 0 below it
+    Step { threshold: f32 },
+}
+
+impl ResponseCurve {
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        let y = match self {
+            ResponseCurve::Linear { slope, offset } => slope * x + offset,
+            ResponseCurve::Polynomial { exponent, inverted } => match inverted {
+                true => 1.0 - x.powf(*exponent),
+                false => x.powf(*exponent),
+            },
+            ResponseCurve::Logistic { steepness, midpoint } => 1.0 / (1.0 + (-steepness * (x - midpoint)).exp()),
+            ResponseCurve::Step { threshold } => match x >= *threshold {
+                true => 1.0,
+                false => 0.0,
+            },
+        };
+
+        y.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Consideration {
+    pub input: ConsiderationInput,
+    pub curve: ResponseCurve,
+    pub weight: f32,
+}
+
+/// An intent and what makes it worth doing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtilityOption {
+    pub intent: CombatIntent,
+    pub considerations: Vec<Consideration>,
+}
+
+/// The inputs for scoring one option: the actor and whoever the resolved action is aimed at.
+pub struct ScoringContext<'a> {
+    pub actor: &'a Combatant,
+    pub target: Option<&'a Combatant>,
+}
+
+impl ScoringContext<'_> {
+    pub fn get_input(&self, input: ConsiderationInput) -> f32 {
+        let ratio = |combatant: &Combatant, pool: ResourcePoolEnum| get_resource_percent(combatant, pool) as f32 / 100.0;
+
+        let value = match input {
+            ConsiderationInput::OwnHealthRatio => ratio(self.actor, ResourcePoolEnum::Health),
+            ConsiderationInput::OwnManaRatio => ratio(self.actor, ResourcePoolEnum::Mana),
+            ConsiderationInput::OwnStaminaRatio => ratio(self.actor, ResourcePoolEnum::Stamina),
+            ConsiderationInput::OwnMadnessRatio => ratio(self.actor, ResourcePoolEnum::Madness),
+            ConsiderationInput::TargetHealthRatio => self.target.map_or(0.0, |target| ratio(target, ResourcePoolEnum::Health)),
+            ConsiderationInput::TargetThreat => self.target.map_or(0.0, |target| {
+                let health = self.actor.resources.get_resource(ResourcePoolEnum::Health).max(1);
+                target.weapon.max_damage as f32 / health as f32
+            }),
+            ConsiderationInput::TargetDistance => self.target.map_or(1.0, |target| {
+                self.actor.position.distance_to(&target.position) as f32 / MAX_CONSIDERED_DISTANCE
+            }),
+        };
+
+        value.clamp(0.0, 1.0)
+    }
+}
+
+impl UtilityOption {
+    /// The weighted average of the considerations, 0 when there are none.
+    pub fn score(&self, context: &ScoringContext) -> f32 {
+        let total_weight: f32 = self.considerations.iter().map(|consideration| consideration.weight).sum();
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+
+        self.considerations
+            .iter()
+            .map(|consideration| consideration.weight * consideration.curve.evaluate(context.get_input(consideration.input)))
+            .sum::<f32>() / total_weight
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtilityBrain {
+    pub id: String,
+    pub options: Vec<UtilityOption>,
+    // each score is jittered by up to this share either way, 0 always picks the best option
+    pub randomness: f32,
+}
+
+impl UtilityBrain {
+    pub fn from_json(json: &str) -> Result<Self, UtilityBrainError> {
+        serde_json::from_str(json).map_err(|error| UtilityBrainError::InvalidData(error.to_string()))
+    }
+}
+
+/// The score an option got on the last decision, for debugging.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredIntent {
+    pub intent: CombatIntent,
+    pub score: f32,
+}
+
+/// Drives combatants by scoring every option that can be carried out right now and taking the best one.
+/// Options that can't resolve into an action are skipped rather than scored.
+#[derive(Debug, Clone)]
+pub struct UtilityController {
+    brain: UtilityBrain,
+    rng: SeededRng,
+    last_scores: Vec<ScoredIntent>,
+}
+
+impl UtilityController {
+    pub fn new(brain: UtilityBrain, seed: u64) -> Self {
+        UtilityController {
+            brain,
+            rng: SeededRng::new(seed),
+            last_scores: Vec::new(),
+        }
+    }
+
+    pub fn get_last_scores(&self) -> &[ScoredIntent] {
+        &self.last_scores
+    }
+
+    fn get_action_target(combat: &Combat, actor: CombatantId, action: &CombatAction) -> Option<CombatantId> {
+        match action {
+            CombatAction::Attack { target } | CombatAction::MindAttack { target } => Some(*target),
+            CombatAction::UseAbility { target: AbilityTarget::Combatant(target), .. } => Some(*target),
+            CombatAction::UseAbility { target: AbilityTarget::Caster, .. } => Some(actor),
+            // anything without a target of its own is weighed against the nearest enemy
+            _ => {
+                let position = match action {
+                    CombatAction::UseAbility { target: AbilityTarget::Position(position), .. } => *position,
+                    _ => combat.get_combatant(actor)?.position,
+                };
+
+                combat
+                    .get_enemies_of(actor)
+                    .into_iter()
+                    .min_by_key(|enemy| combat.get_combatants()[*enemy].position.distance_to(&position))
+            },
+        }
+    }
+}
+
+impl CombatController for UtilityController {
+    fn choose_action(&mut self, combat: &Combat, actor: CombatantId) -> CombatAction {
+        self.last_scores.clear();
+        let Some(combatant) = combat.get_combatant(actor) else {
+            return CombatAction::EndTurn;
+        };
+
+        let mut best: Option<(f32, CombatAction)> = None;
+        for option in &self.brain.options {
+            let Some(action) = option.intent.resolve(combat, actor) else {
+                continue;
+            };
+
+            let context = ScoringContext {
+                actor: combatant,
+                target: Self::get_action_target(combat, actor, &action).and_then(|target| combat.get_combatant(target)),
+            };
+            let jitter = 1.0 + self.brain.randomness * (self.rng.next_f32() * 2.0 - 1.0);
+            let score = option.score(&context) * jitter;
+
+            self.last_scores.push(ScoredIntent { intent: option.intent.clone(), score });
+            // ties keep the option listed first
+            if best.as_ref().is_none_or(|(best_score, _)| score > *best_score) {
+                best = Some((score, action));
+            }
+        }
+
+        best.map_or(CombatAction::EndTurn, |(_, action)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+    use crate::actors::player::PlayerStats;
+    use crate::ai::combat_brain::CombatIntent;
+    use crate::ai::utility_brain::{ResponseCurve, UtilityBrain, UtilityBrainError, UtilityController};
+    use crate::combat::combat_engine::{Combat, CombatAction, CombatController};
+    use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+    use crate::equipment::equipment_manager::{WeaponStats, WeaponType};
+
+    const INQUISITOR_JSON: &str = r#"{
+        "id": "inquisitor",
+        "randomness": 0.0,
+        "options": [
+            {
+                "intent": "AttackWeakest",
+                "considerations": [
+                    { "input": "OwnHealthRatio", "curve": { "Linear": { "slope": 1.0, "offset": 0.0 } }, "weight": 1.0 }
+                ]
+            },
+            {
+                "intent": "Flee",
+                "considerations": [
+                    { "input": "OwnHealthRatio", "curve": { "Polynomial": { "exponent": 2.0, "inverted": true } }, "weight": 2.0 },
+                    { "input": "TargetThreat", "curve": { "Logistic": { "steepness": 10.0, "midpoint": 0.3 } }, "weight": 1.0 }
+                ]
+            },
+            { "intent": "EndTurn", "considerations": [] }
+        ]
+    }"#;
+
+    #[test]
+    fn test_response_curves() {
+        assert_eq!(ResponseCurve::Linear { slope: -1.0, offset: 1.0 }.evaluate(0.25), 0.75);
+        assert_eq!(ResponseCurve::Polynomial { exponent: 2.0, inverted: false }.evaluate(0.5), 0.25);
+        assert_eq!(ResponseCurve::Logistic { steepness: 10.0, midpoint: 0.5 }.evaluate(0.5), 0.5);
+        assert_eq!(ResponseCurve::Step { threshold: 0.5 }.evaluate(0.4), 0.0);
+        assert_eq!(ResponseCurve::Linear { slope: 2.0, offset: 0.0 }.evaluate(3.0), 1.0);
+    }
+
+    #[test]
+    fn test_scores_change_with_health() {
+        let encounter = vec![
+            Combatant::from_player_stats("Player", &PlayerStats::new())
+                .with_weapon(WeaponStats::new(&WeaponType::Sword, 10, 16))
+                .with_position(GridPosition::new(0, 0)),
+            Combatant::from_npc("Inquisitor", CombatTeam::Hostile, &BaseNpcStats::new(1, None))
+                .with_weapon(WeaponStats::new(&WeaponType::Sword, 4, 8))
+                .with_position(GridPosition::new(1, 0)),
+        ];
+        let mut combat = Combat::new(encounter, 3);
+        let mut controller = UtilityController::new(UtilityBrain::from_json(INQUISITOR_JSON).unwrap(), 3);
+
+        assert_eq!(controller.choose_action(&combat, 1), CombatAction::Attack { target: 0 });
+        assert_eq!(controller.get_last_scores().len(), 3);
+
+        combat.get_combatant_mut(1).unwrap().set_resource(ResourcePoolEnum::Health, 10);
+        assert!(matches!(controller.choose_action(&combat, 1), CombatAction::Move { .. }));
+        assert_eq!(controller.get_last_scores()[1].intent, CombatIntent::Flee);
+        assert!(matches!(UtilityBrain::from_json("{\"id\": \"broken\"}"), Err(UtilityBrainError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use rpg_scratch::abilities::ability::{AbilityDefinition, AbilityRegistry};
+use rpg_scratch::actors::npc_template::{NpcGroupEntry, NpcTemplateRegistry, NpcWeapon};
+use rpg_scratch::actors::player::PlayerStats;
+use rpg_scratch::combat::balance::BalanceSimulator;
+use rpg_scratch::combat::combatant::{Combatant, CombatTeam};
+use rpg_scratch::combat::controller::AggressiveController;
+use rpg_scratch::equipment::equipment_manager::WeaponStats;
+
+const USAGE: &str = "usage: balance_sim --templates npcs.json [--party party.json] [--players players.json] --enemies enemies.json [--abilities abilities.json] [--runs N] [--seed N] [--max-rounds N] [--json]";
+
+/// A party member built from a player's (or companion's) stats rather than an NPC template.
+#[derive(Debug, Deserialize)]
+struct PartyMember {
+    name: String,
+    stats: PlayerStats,
+    weapon: Option<NpcWeapon>,
+    #[serde(default)]
+    ability_ids: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Options {
+    templates_path: String,
+    party_path: Option<String>,
+    players_path: Option<String>,
+    enemies_path: String,
+    abilities_path: Option<String>,
+    runs: u32,
+    seed: u64,
+    max_rounds: u32,
+    json: bool,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> anyhow::Result<Self> {
+        let mut templates_path = None;
+        let mut enemies_path = None;
+        let mut options = Options {
+            templates_path: String::new(),
+            party_path: None,
+            players_path: None,
+            enemies_path: String::new(),
+            abilities_path: None,
+            runs: 1000,
+            seed: 0,
+            max_rounds: 50,
+            json: false,
+        };
+
+        while let Some(arg) = args.next() {
+            if arg == "--json" {
+                options.json = true;
+                continue;
+            }
+
+            let value = args.next().ok_or_else(|| anyhow!("missing value for {arg}\n{USAGE}"))?;
+            let invalid = || format!("invalid value for {arg}: {value}");
+
+            match arg.as_str() {
+                "--templates" => templates_path = Some(value),
+                "--party" => options.party_path = Some(value),
+                "--players" => options.players_path = Some(value),
+                "--enemies" => enemies_path = Some(value),
+                "--abilities" => options.abilities_path = Some(value),
+                "--runs" => options.runs = value.parse().with_context(invalid)?,
+                "--seed" => options.seed = value.parse().with_context(invalid)?,
+                "--max-rounds" => options.max_rounds = value.parse().with_context(invalid)?,
+                _ => return Err(anyhow!("unknown argument {arg}\n{USAGE}")),
+            }
+        }
+
+        options.templates_path = templates_path.ok_or_else(|| anyhow!("no NPC templates given\n{USAGE}"))?;
+        if options.party_path.is_none() && options.players_path.is_none() {
+            return Err(anyhow!("no party given\n{USAGE}"));
+        }
+        options.enemies_path = enemies_path.ok_or_else(|| anyhow!("no enemies given\n{USAGE}"))?;
+        Ok(options)
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &str) -> anyhow::Result<T> {
+    let json = std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+    serde_json::from_str(&json).with_context(|| format!("failed to parse {path}"))
+}
+
+fn load_group(path: &str, team: CombatTeam, templates: &NpcTemplateRegistry, abilities: &AbilityRegistry) -> anyhow::Result<Vec<Combatant>> {
+    let entries: Vec<NpcGroupEntry> = read_json(path)?;
+    templates.spawn_group(&entries, team, abilities).with_context(|| format!("failed to build the group in {path}"))
+}
+
+fn load_players(path: &str, abilities: &AbilityRegistry) -> anyhow::Result<Vec<Combatant>> {
+    let members: Vec<PartyMember> = read_json(path)?;
+
+    members
+        .iter()
+        .map(|member| {
+            if let Some(ability_id) = member.ability_ids.iter().find(|ability_id| abilities.get_ability(ability_id).is_none()) {
+                return Err(anyhow!("unknown ability {ability_id} on {} in {path}", member.name));
+            }
+
+            let weapon = member.weapon
+                .as_ref()
+                .map_or_else(WeaponStats::unarmed, |weapon| WeaponStats::new(&weapon.weapon_type, weapon.min_damage, weapon.max_damage));
+            Ok(Combatant::from_player_stats(&member.name, &member.stats)
+                .with_weapon(weapon)
+                .with_abilities(abilities.get_known_abilities(&member.ability_ids)))
+        })
+        .collect()
+}
+
+/// Simulates a party against a group of enemies and prints how the fights went.
+/// The group files are JSON arrays of `{"template_id": "bandit", "level": 3, "rank": "Elite", "count": 2}`
+/// (rank and count are optional), so templates can be tweaked and rerun without rebuilding. Party members
+/// can also come from player stats, as `{"name": "Ada", "stats": {...}, "weapon": {...}, "ability_ids": [...]}`.
+fn main() -> anyhow::Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    let mut abilities = AbilityRegistry::new();
+    if let Some(path) = &options.abilities_path {
+        let definitions: Vec<AbilityDefinition> = read_json(path)?;
+        definitions.into_iter().for_each(|definition| abilities.register_ability(definition));
+    }
+
+    let mut templates = NpcTemplateRegistry::new();
+    let json = std::fs::read_to_string(&options.templates_path).with_context(|| format!("failed to read {}", options.templates_path))?;
+    templates.load_from_json(&json).with_context(|| format!("failed to parse {}", options.templates_path))?;
+
+    let mut party = Vec::new();
+    if let Some(path) = &options.players_path {
+        party.extend(load_players(path, &abilities)?);
+    }
+    if let Some(path) = &options.party_path {
+        party.extend(load_group(path, CombatTeam::Party, &templates, &abilities)?);
+    }
+    let enemies = load_group(&options.enemies_path, CombatTeam::Hostile, &templates, &abilities)?;
+
+    if party.is_empty() || enemies.is_empty() {
+        return Err(anyhow!("both sides need at least one combatant"));
+    }
+
+    let report = BalanceSimulator::new(party, enemies)
+        .with_runs(options.runs)
+        .with_seed(options.seed)
+        .with_max_rounds(options.max_rounds)
+        .simulate::<AggressiveController>();
+
+    match options.json {
+        true => println!("{}", serde_json::to_string_pretty(&report)?),
+        false => println!("{}", report.to_table()),
+    }
+
+    Ok(())
+}
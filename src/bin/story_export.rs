@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Context};
+use rpg_scratch::story_tree::story_export::StoryExport;
+use rpg_scratch::story_tree::story_graph::StoryGraph;
+use rpg_scratch::story_tree::story_history::RecordedChoice;
+use rpg_scratch::story_tree::story_script::load_story;
+
+const USAGE: &str = "usage: story_export [--format dot|mermaid] [--route choices.json] <story.story|story.json>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug)]
+struct Options {
+    path: String,
+    format: Format,
+    route_path: Option<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> anyhow::Result<Self> {
+        let mut path = None;
+        let mut format = Format::Dot;
+        let mut route_path = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    format = match args.next().as_deref() {
+                        Some("dot") => Format::Dot,
+                        Some("mermaid") => Format::Mermaid,
+                        _ => return Err(anyhow!("--format takes dot or mermaid\n{USAGE}")),
+                    }
+                }
+                "--route" => route_path = Some(args.next().ok_or_else(|| anyhow!("--route takes a file\n{USAGE}"))?),
+                flag if flag.starts_with("--") => return Err(anyhow!("unknown argument {arg}\n{USAGE}")),
+                _ => path = Some(arg),
+            }
+        }
+
+        Ok(Options {
+            path: path.ok_or_else(|| anyhow!("no story file given\n{USAGE}"))?,
+            format,
+            route_path,
+        })
+    }
+}
+
+fn load_graph(path: &str) -> anyhow::Result<StoryGraph> {
+    let source = std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+    // anything that isn't JSON is read as a story script
+    match path.ends_with(".json") {
+        true => StoryGraph::from_json(&source).with_context(|| format!("failed to parse {path}")),
+        false => load_story(&source).with_context(|| format!("failed to parse {path}")),
+    }
+}
+
+/// Prints a story as a Graphviz DOT or Mermaid diagram, optionally highlighting a route of recorded choices.
+fn main() -> anyhow::Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let graph = load_graph(&options.path)?;
+
+    let mut export = StoryExport::new(&graph);
+    if let Some(route_path) = &options.route_path {
+        let source = std::fs::read_to_string(route_path).with_context(|| format!("failed to read {route_path}"))?;
+        let route: Vec<RecordedChoice> = serde_json::from_str(&source).with_context(|| format!("failed to parse {route_path}"))?;
+        export = export.with_route(route);
+    }
+
+    match options.format {
+        Format::Dot => println!("{}", export.to_dot()),
+        Format::Mermaid => println!("{}", export.to_mermaid()),
+    }
+
+    Ok(())
+}
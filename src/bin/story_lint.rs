@@ -0,0 +1,90 @@
+use anyhow::{anyhow, Context};
+use serde::Serialize;
+use rpg_scratch::story_tree::story_management::StoryNode;
+use rpg_scratch::story_tree::story_script::parse_story;
+use rpg_scratch::story_tree::story_validation::{validate_nodes, StoryReport};
+
+const USAGE: &str = "usage: story_lint [--deny-warnings] [--json] <story.story|story.json>...";
+
+#[derive(Debug)]
+struct Options {
+    paths: Vec<String>,
+    deny_warnings: bool,
+    json: bool,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(args: I) -> anyhow::Result<Self> {
+        let mut options = Options {
+            paths: Vec::new(),
+            deny_warnings: false,
+            json: false,
+        };
+
+        for arg in args {
+            match arg.as_str() {
+                "--deny-warnings" => options.deny_warnings = true,
+                "--json" => options.json = true,
+                flag if flag.starts_with("--") => return Err(anyhow!("unknown argument {arg}\n{USAGE}")),
+                _ => options.paths.push(arg),
+            }
+        }
+
+        if options.paths.is_empty() {
+            return Err(anyhow!("no story files given\n{USAGE}"));
+        }
+
+        Ok(options)
+    }
+}
+
+// one entry in the `--json` output
+#[derive(Debug, Serialize)]
+struct FileReport<'a> {
+    path: &'a str,
+    report: StoryReport,
+}
+
+fn lint_file(path: &str) -> anyhow::Result<StoryReport> {
+    let source = std::fs::read_to_string(path).with_context(|| format!("failed to read {path}"))?;
+    // anything that isn't JSON is read as a story script
+    let nodes: Vec<StoryNode> = match path.ends_with(".json") {
+        true => serde_json::from_str(&source).with_context(|| format!("failed to parse {path}"))?,
+        false => parse_story(&source).with_context(|| format!("failed to parse {path}"))?,
+    };
+    Ok(validate_nodes(&nodes))
+}
+
+/// Checks story files for broken branches and exits with 1 if any has errors
+/// (or warnings, with `--deny-warnings`) so it can gate a build.
+fn main() -> anyhow::Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    let mut failed = false;
+    let mut file_reports = Vec::new();
+    for path in &options.paths {
+        let report = lint_file(path)?;
+        failed |= report.has_errors() || (options.deny_warnings && !report.is_clean());
+
+        match options.json {
+            true => file_reports.push(FileReport { path, report }),
+            false => {
+                println!("{path}");
+                for line in report.to_lines() {
+                    println!("  {line}");
+                }
+            }
+        }
+    }
+
+    // every file goes in one array so the output stays a single JSON document
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&file_reports)?);
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
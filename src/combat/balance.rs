@@ -0,0 +1,360 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use crate::abilities::ability::DamageKind;
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+use crate::combat::combat_engine::{Combat, CombatController, CombatStatus};
+use crate::combat::combat_log::CombatEvent;
+use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+
+// how many cells apart the party and the enemies line up at the start of each fight
+const FORMATION_DISTANCE: i32 = 6;
+
+const TRACKED_POOLS: [ResourcePoolEnum; 4] = [
+    ResourcePoolEnum::Health,
+    ResourcePoolEnum::Mana,
+    ResourcePoolEnum::Stamina,
+    ResourcePoolEnum::Madness,
+];
+
+/// Min, mean, median, 90th percentile and max of a set of samples.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub min: i32,
+    pub mean: f32,
+    pub median: i32,
+    pub p90: i32,
+    pub max: i32,
+}
+
+impl Distribution {
+    pub fn from_samples(mut samples: Vec<i32>) -> Self {
+        if samples.is_empty() {
+            return Distribution::default();
+        }
+
+        samples.sort();
+        let len = samples.len();
+
+        Distribution {
+            min: samples[0],
+            mean: samples.iter().map(|sample| *sample as f32).sum::<f32>() / len as f32,
+            median: samples[len / 2],
+            p90: samples[(len * 9 / 10).min(len - 1)],
+            max: samples[len - 1],
+        }
+    }
+}
+
+/// The average change of a resource pool over a fight, negative when it was spent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceUsage {
+    pub pool: ResourcePoolEnum,
+    pub average_change: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CombatantReport {
+    pub name: String,
+    pub team: CombatTeam,
+    pub survival_rate: f32,
+    pub damage_dealt: Distribution,
+    pub damage_taken: Distribution,
+    pub madness_inflicted: Distribution,
+    pub resource_usage: Vec<ResourceUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceReport {
+    pub runs: u32,
+    pub base_seed: u64,
+    pub party_win_rate: f32,
+    pub hostile_win_rate: f32,
+    // fights that were still going when the round limit ran out
+    pub stalemate_rate: f32,
+    pub rounds: Distribution,
+    // every combatant's turn counts, so a round of four combatants is four turns
+    pub turns: Distribution,
+    pub combatants: Vec<CombatantReport>,
+}
+
+impl BalanceReport {
+    /// A plain text table for reading in a terminal.
+    pub fn to_table(&self) -> String {
+        let mut lines = vec![
+            format!("runs: {}  base seed: {}", self.runs, self.base_seed),
+            format!(
+                "party wins: {:.1}%  hostile wins: {:.1}%  stalemates: {:.1}%",
+                self.party_win_rate * 100.0,
+                self.hostile_win_rate * 100.0,
+                self.stalemate_rate * 100.0
+            ),
+            format!(
+                "rounds: avg {:.1}  median {}  p90 {}  min {}  max {}",
+                self.rounds.mean,
+                self.rounds.median,
+                self.rounds.p90,
+                self.rounds.min,
+                self.rounds.max
+            ),
+            format!(
+                "turns: avg {:.1}  median {}  p90 {}  min {}  max {}",
+                self.turns.mean,
+                self.turns.median,
+                self.turns.p90,
+                self.turns.min,
+                self.turns.max
+            ),
+            String::new(),
+            format!(
+                "{:<16} {:<8} {:>8} {:>10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                "combatant", "team", "survives", "dealt avg", "p90", "max", "taken avg", "madness", "mana", "stamina", "health"
+            ),
+        ];
+
+        for combatant in &self.combatants {
+            let usage = |pool: ResourcePoolEnum| combatant.resource_usage
+                .iter()
+                .find(|usage| usage.pool == pool)
+                .map_or(0.0, |usage| usage.average_change);
+
+            lines.push(format!(
+                "{:<16} {:<8} {:>7.1}% {:>10.1} {:>8} {:>8} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+                combatant.name,
+                format!("{:?}", combatant.team),
+                combatant.survival_rate * 100.0,
+                combatant.damage_dealt.mean,
+                combatant.damage_dealt.p90,
+                combatant.damage_dealt.max,
+                combatant.damage_taken.mean,
+                combatant.madness_inflicted.mean,
+                usage(ResourcePoolEnum::Mana),
+                usage(ResourcePoolEnum::Stamina),
+                usage(ResourcePoolEnum::Health)
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// What happened to one combatant over a single fight.
+#[derive(Debug, Clone, Default)]
+struct CombatantOutcome {
+    survived: bool,
+    damage_dealt: i32,
+    damage_taken: i32,
+    madness_inflicted: i32,
+    resource_changes: Vec<i32>,
+}
+
+#[derive(Debug, Clone)]
+struct FightOutcome {
+    winner: Option<CombatTeam>,
+    rounds: u32,
+    turns: u32,
+    combatants: Vec<CombatantOutcome>,
+}
+
+/// Runs the same party against the same enemies over many seeds so designers can see how a fight
+/// tends to go, rather than how it went once.
+#[derive(Debug, Clone)]
+pub struct BalanceSimulator {
+    combatants: Vec<Combatant>,
+    runs: u32,
+    base_seed: u64,
+    max_rounds: u32,
+}
+
+impl BalanceSimulator {
+    /// Lines the party up facing the enemies; any positions already on the combatants are replaced.
+    pub fn new(party: Vec<Combatant>, enemies: Vec<Combatant>) -> Self {
+        let party = party
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut combatant)| {
+                combatant.team = CombatTeam::Party;
+                combatant.position = GridPosition::new(0, index as i32);
+                combatant
+            });
+        let enemies = enemies
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut combatant)| {
+                combatant.team = CombatTeam::Hostile;
+                combatant.position = GridPosition::new(FORMATION_DISTANCE, index as i32);
+                combatant
+            });
+
+        BalanceSimulator {
+            combatants: party.chain(enemies).collect(),
+            runs: 1000,
+            base_seed: 0,
+            max_rounds: 50,
+        }
+    }
+
+    pub fn with_runs(mut self, runs: u32) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    pub fn with_seed(mut self, base_seed: u64) -> Self {
+        self.base_seed = base_seed;
+        self
+    }
+
+    pub fn with_max_rounds(mut self, max_rounds: u32) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Fights every run with a fresh controller of type `C`, seeding run `n` with `base_seed + n`.
+    pub fn simulate<C: CombatController + Default>(&self) -> BalanceReport {
+        let outcomes: Vec<FightOutcome> = (0..self.runs)
+            .into_par_iter()
+            .map(|run| self.simulate_fight::<C>(self.base_seed.wrapping_add(run as u64)))
+            .collect();
+
+        self.build_report(&outcomes)
+    }
+
+    fn simulate_fight<C: CombatController + Default>(&self, seed: u64) -> FightOutcome {
+        let mut combat = Combat::new(self.combatants.clone(), seed);
+        let status = combat.run(&mut C::default(), self.max_rounds);
+
+        let mut combatants: Vec<CombatantOutcome> = self.combatants
+            .iter()
+            .zip(combat.get_combatants())
+            .map(|(before, after)| CombatantOutcome {
+                survived: !after.is_defeated(),
+                resource_changes: TRACKED_POOLS
+                    .iter()
+                    .map(|pool| after.get_resource(*pool) - before.get_resource(*pool))
+                    .collect(),
+                ..CombatantOutcome::default()
+            })
+            .collect();
+
+        let mut turns = 0;
+        for entry in combat.get_log().get_entries() {
+            let (source, target, damage) = match &entry.event {
+                CombatEvent::TurnStarted(_) => {
+                    turns += 1;
+                    continue;
+                },
+                CombatEvent::Attacked { attacker, target, is_hit: true, damage, .. } => (*attacker, *target, *damage),
+                CombatEvent::AbilityDamaged { caster, target, kind: DamageKind::Physical, amount } => (*caster, *target, *amount),
+                CombatEvent::AbilityDamaged { caster, kind: DamageKind::Psychological, amount, .. } => {
+                    combatants[*caster].madness_inflicted += amount;
+                    continue;
+                },
+                CombatEvent::MindAttacked { attacker, is_hit: true, madness, .. } => {
+                    combatants[*attacker].madness_inflicted += madness;
+                    continue;
+                },
+                _ => continue,
+            };
+
+            combatants[source].damage_dealt += damage;
+            combatants[target].damage_taken += damage;
+        }
+
+        FightOutcome {
+            winner: match status {
+                CombatStatus::Victory(team) => Some(team),
+                CombatStatus::Ongoing => None,
+            },
+            rounds: combat.get_round(),
+            turns,
+            combatants,
+        }
+    }
+
+    fn build_report(&self, outcomes: &[FightOutcome]) -> BalanceReport {
+        let runs = outcomes.len().max(1) as f32;
+        let rate = |winner: Option<CombatTeam>| outcomes
+            .iter()
+            .filter(|outcome| outcome.winner == winner)
+            .count() as f32 / runs;
+
+        let combatants = self.combatants
+            .iter()
+            .enumerate()
+            .map(|(id, combatant)| {
+                let samples = |get: fn(&CombatantOutcome) -> i32| Distribution::from_samples(
+                    outcomes.iter().map(|outcome| get(&outcome.combatants[id])).collect()
+                );
+
+                CombatantReport {
+                    name: combatant.name.clone(),
+                    team: combatant.team,
+                    survival_rate: outcomes.iter().filter(|outcome| outcome.combatants[id].survived).count() as f32 / runs,
+                    damage_dealt: samples(|outcome| outcome.damage_dealt),
+                    damage_taken: samples(|outcome| outcome.damage_taken),
+                    madness_inflicted: samples(|outcome| outcome.madness_inflicted),
+                    resource_usage: TRACKED_POOLS
+                        .iter()
+                        .enumerate()
+                        .map(|(index, pool)| ResourceUsage {
+                            pool: *pool,
+                            average_change: outcomes.iter().map(|outcome| outcome.combatants[id].resource_changes[index] as f32).sum::<f32>() / runs,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        BalanceReport {
+            runs: self.runs,
+            base_seed: self.base_seed,
+            party_win_rate: rate(Some(CombatTeam::Party)),
+            hostile_win_rate: rate(Some(CombatTeam::Hostile)),
+            stalemate_rate: rate(None),
+            rounds: Distribution::from_samples(outcomes.iter().map(|outcome| outcome.rounds as i32).collect()),
+            turns: Distribution::from_samples(outcomes.iter().map(|outcome| outcome.turns as i32).collect()),
+            combatants,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::player::PlayerStats;
+    use crate::combat::balance::{BalanceSimulator, Distribution};
+    use crate::combat::combatant::{Combatant, CombatTeam};
+    use crate::combat::controller::AggressiveController;
+    use crate::equipment::equipment_manager::{WeaponStats, WeaponType};
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::from_samples(vec![5, 1, 3, 9, 7, 2, 4, 8, 6, 10]);
+
+        assert_eq!(distribution.min, 1);
+        assert_eq!(distribution.max, 10);
+        assert_eq!(distribution.median, 6);
+        assert_eq!(distribution.p90, 10);
+        assert_eq!(distribution.mean, 5.5);
+        assert_eq!(Distribution::from_samples(Vec::new()), Distribution::default());
+    }
+
+    #[test]
+    fn test_simulation_is_reproducible() {
+        let simulator = BalanceSimulator::new(
+            vec![Combatant::from_player_stats("Player", &PlayerStats::new()).with_weapon(WeaponStats::new(&WeaponType::Sword, 10, 16))],
+            vec![Combatant::from_npc("Bandit", CombatTeam::Hostile, &BaseNpcStats::new(1, None)).with_weapon(WeaponStats::new(&WeaponType::Dagger, 2, 4))]
+        )
+            .with_runs(200)
+            .with_seed(9);
+
+        let report = simulator.simulate::<AggressiveController>();
+
+        assert_eq!(report, simulator.simulate::<AggressiveController>());
+        assert!((report.party_win_rate + report.hostile_win_rate + report.stalemate_rate - 1.0).abs() < 0.001);
+        assert!(report.party_win_rate > report.hostile_win_rate);
+        assert_eq!(report.combatants.len(), 2);
+        assert!(report.turns.mean >= report.rounds.mean);
+        assert!(report.to_table().contains("turns: avg"));
+        assert!(report.combatants[0].damage_dealt.mean > 0.0);
+    }
+}
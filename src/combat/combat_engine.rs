@@ -0,0 +1,1076 @@
+use thiserror::Error;
+use crate::abilities::ability::{AbilityDefinition, AbilityEffect, DamageKind, TargetFilter, TargetingRule};
+use crate::actors::models::{ComputedAttributeEnum, MadnessTier, ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+use crate::combat::combat_log::{CombatEvent, CombatLog};
+use crate::combat::combatant::{ActionBudget, Combatant, CombatantId, CombatTeam, GridPosition};
+use crate::combat::psychological::{MentalCondition, MentalConditionKind};
+use crate::factions::faction::FactionRegistry;
+use crate::factions::reputation::{PlayerReputation, ReputationChange};
+use crate::quests::quest::QuestEvent;
+use crate::status_effects::active_effects::StatusEvent;
+use crate::utils::rng::SeededRng;
+
+const BASE_HIT_PERCENT: i32 = 80;
+const MIN_HIT_PERCENT: i32 = 5;
+const MAX_HIT_PERCENT: i32 = 95;
+const BASE_CRITICAL_PERCENT: i32 = 5;
+// damage is scaled by RESISTANCE_SCALE / (RESISTANCE_SCALE + resistance)
+const RESISTANCE_SCALE: i32 = 200;
+// defending raises physical resistance by half until the defender's next turn
+const DEFEND_RESISTANCE_PERCENT: i32 = 150;
+// chance that a confused combatant's attack goes to a random combatant in reach instead
+const CONFUSION_REDIRECT_PERCENT: i32 = 50;
+// a controller that keeps making invalid choices has its turn ended for it
+const MAX_ACTIONS_PER_TURN: u32 = 8;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CombatError {
+    #[error("Combat is already over")]
+    CombatOver,
+    #[error("Unknown combatant: {0}")]
+    UnknownCombatant(CombatantId),
+    #[error("Combatant {0} is defeated")]
+    TargetDefeated(CombatantId),
+    #[error("Combatant {0} is not an enemy")]
+    NotAnEnemy(CombatantId),
+    #[error("No move left this turn")]
+    NoMoveLeft,
+    #[error("No action left this turn")]
+    NoActionLeft,
+    #[error("Target {0} is out of reach")]
+    OutOfReach(CombatantId),
+    #[error("Can't move {distance} cells, only {movement} allowed")]
+    TooFar { distance: i32, movement: i32 },
+    #[error("Position ({}, {}) is occupied", .0.x, .0.y)]
+    PositionOccupied(GridPosition),
+    #[error("Too afraid to attack")]
+    Feared,
+    #[error("Combatant {0} has no psychological attack")]
+    NoPsychologicalAttack(CombatantId),
+    #[error("Unknown ability: {0}")]
+    UnknownAbility(usize),
+    #[error("Ability is on cooldown for {0} more turns")]
+    AbilityOnCooldown(u32),
+    #[error("Can't pay the cost of the ability")]
+    CanNotPayCost,
+    #[error("Invalid target for the ability")]
+    InvalidAbilityTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbilityTarget {
+    Caster,
+    Combatant(CombatantId),
+    Position(GridPosition),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatAction {
+    Move { to: GridPosition },
+    Attack { target: CombatantId },
+    MindAttack { target: CombatantId },
+    // the ability is picked by its index in the combatant's known abilities
+    UseAbility { ability: usize, target: AbilityTarget },
+    Defend,
+    EndTurn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatStatus {
+    Ongoing,
+    Victory(CombatTeam),
+}
+
+/// Anything that picks actions for combatants, from the player's input to NPC brains.
+pub trait CombatController {
+    fn choose_action(&mut self, combat: &Combat, actor: CombatantId) -> CombatAction;
+}
+
+/// A headless turn based fight. Every roll comes from the seeded rng,
+/// so the same combatants, seed and choices always play out the same way.
+#[derive(Debug, Clone)]
+pub struct Combat {
+    combatants: Vec<Combatant>,
+    initiative_order: Vec<CombatantId>,
+    turn_index: usize,
+    round: u32,
+    actions_this_turn: u32,
+    rng: SeededRng,
+    log: CombatLog,
+    status: CombatStatus,
+}
+
+impl Combat {
+    pub fn new(combatants: Vec<Combatant>, seed: u64) -> Self {
+        let mut combat = Combat {
+            combatants,
+            initiative_order: Vec::new(),
+            turn_index: 0,
+            round: 1,
+            actions_this_turn: 0,
+            rng: SeededRng::new(seed),
+            log: CombatLog::new(),
+            status: CombatStatus::Ongoing,
+        };
+
+        combat.roll_initiative();
+        combat.log.push(combat.round, CombatEvent::RoundStarted);
+        combat.check_for_victory();
+
+        if combat.status == CombatStatus::Ongoing {
+            combat.start_turn();
+        }
+
+        combat
+    }
+
+    fn roll_initiative(&mut self) {
+        let mut rolls: Vec<(CombatantId, i32, i32)> = Vec::new();
+
+        for (id, combatant) in self.combatants.iter().enumerate() {
+            let dexterity = combatant.get_effective_stat(StatsEnum::Dexterity);
+            let initiative = dexterity
+                + combatant.get_effective_stat(StatsEnum::Luck) / 2
+                + self.rng.roll_die(20) as i32;
+
+            self.log.push(self.round, CombatEvent::InitiativeRolled { combatant: id, initiative });
+            rolls.push((id, initiative, dexterity));
+        }
+
+        // ties go to the more dexterous combatant, then to whoever was added first
+        rolls.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)));
+        self.initiative_order = rolls.iter().map(|roll| roll.0).collect();
+    }
+
+    pub fn get_combatants(&self) -> &[Combatant] {
+        &self.combatants
+    }
+
+    pub fn get_combatant(&self, id: CombatantId) -> Option<&Combatant> {
+        self.combatants.get(id)
+    }
+
+    pub fn get_combatant_mut(&mut self, id: CombatantId) -> Option<&mut Combatant> {
+        self.combatants.get_mut(id)
+    }
+
+    pub fn get_initiative_order(&self) -> &[CombatantId] {
+        &self.initiative_order
+    }
+
+    pub fn get_current_combatant_id(&self) -> CombatantId {
+        self.initiative_order[self.turn_index]
+    }
+
+    pub fn get_round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn get_status(&self) -> CombatStatus {
+        self.status
+    }
+
+    pub fn get_log(&self) -> &CombatLog {
+        &self.log
+    }
+
+    pub fn get_rng_mut(&mut self) -> &mut SeededRng {
+        &mut self.rng
+    }
+
+    pub fn log_event(&mut self, event: CombatEvent) {
+        self.log.push(self.round, event);
+    }
+
+    fn log_defeat(&mut self, id: CombatantId) {
+        let faction_id = self.combatants[id].faction_id.clone();
+        self.log_event(CombatEvent::Defeated { combatant: id, faction_id });
+    }
+
+    /// Living combatants on the other team from the given one.
+    pub fn get_enemies_of(&self, id: CombatantId) -> Vec<CombatantId> {
+        let Some(combatant) = self.combatants.get(id) else {
+            return Vec::new();
+        };
+
+        self.combatants
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| other.team != combatant.team && !other.is_defeated())
+            .map(|(other_id, _)| other_id)
+            .collect()
+    }
+
+    pub fn get_allies_of(&self, id: CombatantId) -> Vec<CombatantId> {
+        let Some(combatant) = self.combatants.get(id) else {
+            return Vec::new();
+        };
+
+        self.combatants
+            .iter()
+            .enumerate()
+            .filter(|(other_id, other)| *other_id != id && other.team == combatant.team && !other.is_defeated())
+            .map(|(other_id, _)| other_id)
+            .collect()
+    }
+
+    pub fn is_position_free(&self, position: &GridPosition) -> bool {
+        !self.combatants
+            .iter()
+            .any(|combatant| !combatant.is_defeated() && combatant.position == *position)
+    }
+
+    /// The free cell within the actor's movement that gets it closest to the target,
+    /// preferring the shortest walk. None if the actor can't get any closer.
+    pub fn find_approach_position(&self, actor_id: CombatantId, target_id: CombatantId) -> Option<GridPosition> {
+        let actor = self.combatants.get(actor_id)?;
+        let target = self.combatants.get(target_id)?;
+        let movement = actor.get_movement();
+        let current_distance = actor.position.distance_to(&target.position);
+
+        let mut best: Option<(i32, i32, GridPosition)> = None;
+        for dx in -movement..=movement {
+            for dy in -movement..=movement {
+                let position = GridPosition::new(actor.position.x + dx, actor.position.y + dy);
+                if (dx == 0 && dy == 0) || !self.is_position_free(&position) {
+                    continue;
+                }
+
+                let distance = position.distance_to(&target.position).max(actor.weapon.reach.min(current_distance));
+                let walked = actor.position.distance_to(&position);
+                if best.is_none_or(|(best_distance, best_walked, _)| (distance, walked) < (best_distance, best_walked)) {
+                    best = Some((distance, walked, position));
+                }
+            }
+        }
+
+        best
+            .filter(|(distance, _, _)| *distance < current_distance)
+            .map(|(_, _, position)| position)
+    }
+
+    pub fn get_hit_chance(&self, attacker: &Combatant, target: &Combatant) -> i32 {
+        let dexterity_difference = attacker.get_effective_stat(StatsEnum::Dexterity) - target.get_effective_stat(StatsEnum::Dexterity);
+        let luck_difference = attacker.get_effective_stat(StatsEnum::Luck) - target.get_effective_stat(StatsEnum::Luck);
+
+        (BASE_HIT_PERCENT + dexterity_difference * 2 + luck_difference).clamp(MIN_HIT_PERCENT, MAX_HIT_PERCENT)
+    }
+
+    /// Scales raw damage down by the given resistance, never below one point.
+    pub fn mitigate_damage(damage: i32, resistance: i32) -> i32 {
+        (damage * RESISTANCE_SCALE / (RESISTANCE_SCALE + resistance.max(0))).max(1)
+    }
+
+    pub fn perform_action(&mut self, action: CombatAction) -> Result<(), CombatError> {
+        if self.status != CombatStatus::Ongoing {
+            return Err(CombatError::CombatOver);
+        }
+
+        let actor_id = self.get_current_combatant_id();
+        self.actions_this_turn += 1;
+
+        match action {
+            CombatAction::Move { to } => self.move_combatant(actor_id, to)?,
+            CombatAction::Attack { target } => {
+                let reach = self.combatants[actor_id].weapon.reach;
+                self.validate_attack(actor_id, target, reach)?;
+
+                self.combatants[actor_id].budget.has_action = false;
+                let target = self.pick_confused_target(actor_id, target, reach);
+                self.resolve_attack(actor_id, target, false);
+            },
+            CombatAction::MindAttack { target } => {
+                let reach = self.combatants[actor_id].psychological_attack
+                    .as_ref()
+                    .map(|attack| attack.reach)
+                    .ok_or(CombatError::NoPsychologicalAttack(actor_id))?;
+                self.validate_attack(actor_id, target, reach)?;
+
+                self.combatants[actor_id].budget.has_action = false;
+                let target = self.pick_confused_target(actor_id, target, reach);
+                self.resolve_mind_attack(actor_id, target);
+            },
+            CombatAction::UseAbility { ability, target } => self.use_ability(actor_id, ability, target)?,
+            CombatAction::Defend => {
+                let actor = &mut self.combatants[actor_id];
+                if !actor.budget.has_action {
+                    return Err(CombatError::NoActionLeft);
+                }
+
+                actor.budget.has_action = false;
+                actor.is_defending = true;
+                self.log_event(CombatEvent::Defended(actor_id));
+            },
+            CombatAction::EndTurn => {
+                self.end_turn();
+                return Ok(());
+            },
+        }
+
+        self.check_for_victory();
+
+        // nothing left to do (or the actor went down to a reaction) so the turn passes on by itself
+        let actor = &self.combatants[actor_id];
+        if self.status == CombatStatus::Ongoing && (actor.is_defeated() || (!actor.budget.has_move && !actor.budget.has_action)) {
+            self.end_turn();
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_enemy_target(&self, actor_id: CombatantId, target: CombatantId) -> Result<(), CombatError> {
+        let target_combatant = self.combatants
+            .get(target)
+            .ok_or(CombatError::UnknownCombatant(target))?;
+
+        if target_combatant.is_defeated() {
+            return Err(CombatError::TargetDefeated(target));
+        }
+        if target_combatant.team == self.combatants[actor_id].team {
+            return Err(CombatError::NotAnEnemy(target));
+        }
+
+        Ok(())
+    }
+
+    fn validate_attack(&self, actor_id: CombatantId, target: CombatantId, reach: i32) -> Result<(), CombatError> {
+        self.validate_enemy_target(actor_id, target)?;
+
+        let actor = &self.combatants[actor_id];
+        if !actor.budget.has_action {
+            return Err(CombatError::NoActionLeft);
+        }
+        if actor.has_mental_condition(MentalConditionKind::Feared) {
+            return Err(CombatError::Feared);
+        }
+        if actor.position.distance_to(&self.combatants[target].position) > reach {
+            return Err(CombatError::OutOfReach(target));
+        }
+
+        Ok(())
+    }
+
+    /// A confused combatant's attack may land on anyone within reach, including allies.
+    fn pick_confused_target(&mut self, actor_id: CombatantId, target: CombatantId, reach: i32) -> CombatantId {
+        if !self.combatants[actor_id].has_mental_condition(MentalConditionKind::Confused) || !self.rng.chance(CONFUSION_REDIRECT_PERCENT) {
+            return target;
+        }
+
+        let position = self.combatants[actor_id].position;
+        let in_reach: Vec<CombatantId> = self.combatants
+            .iter()
+            .enumerate()
+            .filter(|(id, other)| *id != actor_id && !other.is_defeated() && other.position.distance_to(&position) <= reach)
+            .map(|(id, _)| id)
+            .collect();
+
+        let index = self.rng.range_u32(0, in_reach.len() as u32 - 1) as usize;
+        in_reach[index]
+    }
+
+    fn move_combatant(&mut self, actor_id: CombatantId, to: GridPosition) -> Result<(), CombatError> {
+        let actor = &self.combatants[actor_id];
+        if !actor.budget.has_move {
+            return Err(CombatError::NoMoveLeft);
+        }
+
+        let distance = actor.position.distance_to(&to);
+        let movement = actor.get_movement();
+        if distance > movement {
+            return Err(CombatError::TooFar { distance, movement });
+        }
+        if to != actor.position && !self.is_position_free(&to) {
+            return Err(CombatError::PositionOccupied(to));
+        }
+
+        let from = actor.position;
+
+        // leaving an enemy's melee reach provokes a reaction attack
+        let provoked: Vec<CombatantId> = self.get_enemies_of(actor_id)
+            .into_iter()
+            .filter(|enemy_id| {
+                let enemy = &self.combatants[*enemy_id];
+                enemy.budget.has_reaction
+                    && enemy.weapon.reach == 1
+                    && enemy.position.distance_to(&from) <= 1
+                    && enemy.position.distance_to(&to) > 1
+            })
+            .collect();
+
+        self.combatants[actor_id].budget.has_move = false;
+
+        for enemy_id in provoked {
+            self.combatants[enemy_id].budget.has_reaction = false;
+            self.resolve_attack(enemy_id, actor_id, true);
+
+            if self.combatants[actor_id].is_defeated() {
+                return Ok(());
+            }
+        }
+
+        self.combatants[actor_id].position = to;
+        self.log_event(CombatEvent::Moved { combatant: actor_id, from, to });
+
+        Ok(())
+    }
+
+    fn resolve_attack(&mut self, attacker_id: CombatantId, target_id: CombatantId, is_reaction: bool) {
+        let attacker = &self.combatants[attacker_id];
+        let target = &self.combatants[target_id];
+
+        let hit_chance = self.get_hit_chance(attacker, target);
+        let critical_chance = (BASE_CRITICAL_PERCENT + attacker.get_effective_stat(StatsEnum::Luck) - 10).clamp(1, 50);
+        let weapon = attacker.weapon;
+        let stat_bonus = (attacker.get_effective_stat(weapon.scaling_stat) - 10) / 2;
+
+        let mut resistance = target.get_effective_attribute(ComputedAttributeEnum::PhysicalResistance);
+        if target.is_defending {
+            resistance = resistance * DEFEND_RESISTANCE_PERCENT / 100;
+        }
+
+        let is_hit = self.rng.chance(hit_chance);
+        let mut damage = 0;
+        let mut is_critical = false;
+
+        if is_hit {
+            is_critical = self.rng.chance(critical_chance);
+
+            let mut raw_damage = (self.rng.range_i32(weapon.min_damage, weapon.max_damage) + stat_bonus).max(1);
+            if is_critical {
+                raw_damage *= 2;
+            }
+
+            damage = Self::mitigate_damage(raw_damage, resistance);
+            self.combatants[target_id].modify_resource(ResourcePoolEnum::Health, -damage);
+        }
+
+        self.log_event(CombatEvent::Attacked { attacker: attacker_id, target: target_id, hit_chance, is_hit, is_critical, is_reaction, damage });
+
+        if self.combatants[target_id].is_defeated() {
+            self.log_defeat(target_id);
+        }
+    }
+
+    pub fn get_mind_hit_chance(&self, attacker: &Combatant, target: &Combatant) -> i32 {
+        let Some(attack) = &attacker.psychological_attack else {
+            return 0;
+        };
+
+        let difference = attacker.get_effective_stat(attack.scaling_stat) - target.get_effective_stat(StatsEnum::Wisdom);
+        (BASE_HIT_PERCENT + difference * 2).clamp(MIN_HIT_PERCENT, MAX_HIT_PERCENT)
+    }
+
+    fn resolve_mind_attack(&mut self, attacker_id: CombatantId, target_id: CombatantId) {
+        let attacker = &self.combatants[attacker_id];
+        let target = &self.combatants[target_id];
+        let Some(attack) = attacker.psychological_attack.clone() else {
+            return;
+        };
+
+        let hit_chance = self.get_mind_hit_chance(attacker, target);
+        let stat_bonus = (attacker.get_effective_stat(attack.scaling_stat) - 10) / 2;
+        let resistance = target.get_effective_attribute(ComputedAttributeEnum::PsychologicalResistance);
+
+        let is_hit = self.rng.chance(hit_chance);
+        let mut madness = 0;
+        let mut tier_before = None;
+
+        if is_hit {
+            let raw_damage = (self.rng.range_i32(attack.min_damage, attack.max_damage) + stat_bonus).max(1);
+            tier_before = Some(self.combatants[target_id].get_madness_tier());
+            madness = self.combatants[target_id].modify_resource(ResourcePoolEnum::Madness, Self::mitigate_damage(raw_damage, resistance));
+        }
+
+        self.log_event(CombatEvent::MindAttacked { attacker: attacker_id, target: target_id, hit_chance, is_hit, madness });
+
+        if let Some(tier_before) = tier_before {
+            self.check_for_mental_break(target_id, tier_before);
+        }
+    }
+
+    /// Breaks the combatant's mind if its madness has climbed into a worse tier than `tier_before`.
+    fn check_for_mental_break(&mut self, combatant_id: CombatantId, tier_before: MadnessTier) {
+        let tier_after = self.combatants[combatant_id].get_madness_tier();
+        if tier_after > tier_before {
+            if let Some(kind) = MentalConditionKind::from_madness_tier(tier_after) {
+                self.apply_mental_condition(combatant_id, kind);
+            }
+        }
+    }
+
+    fn use_ability(&mut self, caster_id: CombatantId, ability_index: usize, target: AbilityTarget) -> Result<(), CombatError> {
+        let caster = &self.combatants[caster_id];
+        let known = caster.abilities
+            .get(ability_index)
+            .ok_or(CombatError::UnknownAbility(ability_index))?;
+        let ability = known.definition.clone();
+
+        if !caster.budget.has_action {
+            return Err(CombatError::NoActionLeft);
+        }
+        if !known.is_ready() {
+            return Err(CombatError::AbilityOnCooldown(known.cooldown_remaining));
+        }
+        if ability.is_offensive() && caster.has_mental_condition(MentalConditionKind::Feared) {
+            return Err(CombatError::Feared);
+        }
+        if !ability.can_pay_costs(caster) {
+            return Err(CombatError::CanNotPayCost);
+        }
+
+        let targets = self.get_ability_targets(caster_id, ability.targeting, ability.affects, target)?;
+
+        let caster = &mut self.combatants[caster_id];
+        let tier_before = caster.get_madness_tier();
+        ability.pay_costs(caster);
+        caster.abilities[ability_index].cooldown_remaining = ability.cooldown_turns;
+        caster.budget.has_action = false;
+
+        self.log_event(CombatEvent::AbilityUsed { caster: caster_id, ability_id: ability.id.clone() });
+        self.check_for_mental_break(caster_id, tier_before);
+
+        for target_id in targets {
+            for effect in &ability.effects {
+                if self.combatants[target_id].is_defeated() {
+                    break;
+                }
+                self.apply_ability_effect(caster_id, target_id, effect, &ability);
+            }
+        }
+
+        if let Some(risk) = &ability.forbidden {
+            if self.rng.chance(risk.madness_chance_percent) {
+                let tier_before = self.combatants[caster_id].get_madness_tier();
+                let madness = self.combatants[caster_id].modify_resource(ResourcePoolEnum::Madness, risk.madness_amount);
+
+                self.log_event(CombatEvent::ForbiddenBacklash { caster: caster_id, madness });
+                self.check_for_mental_break(caster_id, tier_before);
+            }
+
+            *self.combatants[caster_id].notoriety.entry(risk.notoriety_faction_id.clone()).or_insert(0) += risk.notoriety;
+            self.log_event(CombatEvent::NotorietyGained { caster: caster_id, faction_id: risk.notoriety_faction_id.clone(), amount: risk.notoriety });
+        }
+
+        Ok(())
+    }
+
+    fn get_ability_targets(&self, caster_id: CombatantId, targeting: TargetingRule, affects: TargetFilter, target: AbilityTarget) -> Result<Vec<CombatantId>, CombatError> {
+        let caster = &self.combatants[caster_id];
+        let target_position = match target {
+            AbilityTarget::Caster => caster.position,
+            AbilityTarget::Combatant(id) => self.combatants
+                .get(id)
+                .filter(|combatant| !combatant.is_defeated())
+                .ok_or(CombatError::InvalidAbilityTarget)?
+                .position,
+            AbilityTarget::Position(position) => position,
+        };
+
+        let is_affected = |combatant: &Combatant| !combatant.is_defeated() && match affects {
+            TargetFilter::Enemies => combatant.team != caster.team,
+            TargetFilter::Allies => combatant.team == caster.team,
+            TargetFilter::Everyone => true,
+        };
+
+        let in_cells = |cells: &[GridPosition]| -> Vec<CombatantId> {
+            self.combatants
+                .iter()
+                .enumerate()
+                .filter(|(_, combatant)| is_affected(combatant) && cells.contains(&combatant.position))
+                .map(|(id, _)| id)
+                .collect()
+        };
+
+        match targeting {
+            TargetingRule::SelfOnly => Ok(vec![caster_id]),
+            TargetingRule::Single { range } => {
+                let AbilityTarget::Combatant(target_id) = target else {
+                    return Err(CombatError::InvalidAbilityTarget);
+                };
+                if caster.position.distance_to(&target_position) > range {
+                    return Err(CombatError::OutOfReach(target_id));
+                }
+                if !is_affected(&self.combatants[target_id]) {
+                    return Err(CombatError::InvalidAbilityTarget);
+                }
+
+                Ok(vec![target_id])
+            },
+            TargetingRule::Area { range, radius } => {
+                if caster.position.distance_to(&target_position) > range {
+                    return Err(CombatError::InvalidAbilityTarget);
+                }
+
+                Ok(self.combatants
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, combatant)| is_affected(combatant) && combatant.position.distance_to(&target_position) <= radius)
+                    .map(|(id, _)| id)
+                    .collect())
+            },
+            TargetingRule::Line { length } => {
+                let direction = GridPosition::new(
+                    (target_position.x - caster.position.x).signum(),
+                    (target_position.y - caster.position.y).signum()
+                );
+                if direction == GridPosition::default() {
+                    return Err(CombatError::InvalidAbilityTarget);
+                }
+
+                let cells: Vec<GridPosition> = (1..=length)
+                    .map(|step| GridPosition::new(caster.position.x + direction.x * step, caster.position.y + direction.y * step))
+                    .collect();
+
+                Ok(in_cells(&cells))
+            },
+        }
+    }
+
+    fn apply_ability_effect(&mut self, caster_id: CombatantId, target_id: CombatantId, effect: &AbilityEffect, ability: &AbilityDefinition) {
+        match effect {
+            AbilityEffect::Damage { kind, min, max } => {
+                let rolled = self.rng.range_i32(*min, *max);
+                let raw_damage = ability.get_scaled_amount(rolled, |stat| self.combatants[caster_id].get_effective_stat(stat)).max(1);
+                let target = &self.combatants[target_id];
+
+                let amount = match kind {
+                    DamageKind::Physical => {
+                        let mut resistance = target.get_effective_attribute(ComputedAttributeEnum::PhysicalResistance);
+                        if target.is_defending {
+                            resistance = resistance * DEFEND_RESISTANCE_PERCENT / 100;
+                        }
+
+                        -self.combatants[target_id].modify_resource(ResourcePoolEnum::Health, -Self::mitigate_damage(raw_damage, resistance))
+                    },
+                    DamageKind::Psychological => {
+                        let resistance = target.get_effective_attribute(ComputedAttributeEnum::PsychologicalResistance);
+                        let tier_before = target.get_madness_tier();
+                        let amount = self.combatants[target_id].modify_resource(ResourcePoolEnum::Madness, Self::mitigate_damage(raw_damage, resistance));
+
+                        self.log_event(CombatEvent::AbilityDamaged { caster: caster_id, target: target_id, kind: *kind, amount });
+                        self.check_for_mental_break(target_id, tier_before);
+                        return;
+                    },
+                };
+
+                self.log_event(CombatEvent::AbilityDamaged { caster: caster_id, target: target_id, kind: *kind, amount });
+
+                if self.combatants[target_id].is_defeated() {
+                    self.log_defeat(target_id);
+                }
+            },
+            AbilityEffect::Restore { pool, min, max } => {
+                let rolled = self.rng.range_i32(*min, *max);
+                let amount = ability.get_scaled_amount(rolled, |stat| self.combatants[caster_id].get_effective_stat(stat));
+                let amount = match pool {
+                    // restoring sanity means taking madness away
+                    ResourcePoolEnum::Madness => -self.combatants[target_id].modify_resource(*pool, -amount),
+                    _ => self.combatants[target_id].modify_resource(*pool, amount),
+                };
+
+                self.log_event(CombatEvent::Restored { caster: caster_id, target: target_id, pool: *pool, amount });
+            },
+            AbilityEffect::ApplyStatus(definition) => {
+                let result = self.combatants[target_id].status_effects.apply_effect(definition);
+                self.log_event(CombatEvent::StatusApplied { combatant: target_id, effect_id: definition.id.clone(), result });
+            },
+            AbilityEffect::RemoveStatus(effect_id) => {
+                if self.combatants[target_id].status_effects.remove_effect(effect_id) {
+                    self.log_event(CombatEvent::StatusRemoved { combatant: target_id, effect_id: effect_id.clone() });
+                }
+            },
+        }
+    }
+
+    /// Breaks the combatant's mind, replacing any condition it already had.
+    pub fn apply_mental_condition(&mut self, combatant_id: CombatantId, kind: MentalConditionKind) {
+        let combatant = &mut self.combatants[combatant_id];
+        let original_team = combatant.mental_condition
+            .map(|condition| condition.original_team)
+            .unwrap_or(combatant.team);
+
+        combatant.team = match kind {
+            MentalConditionKind::Possessed => original_team.get_opposing_team(),
+            _ => original_team,
+        };
+        combatant.mental_condition = Some(MentalCondition { kind, remaining_turns: kind.get_duration(), original_team });
+
+        self.log_event(CombatEvent::MentalBreak { combatant: combatant_id, condition: kind });
+        self.check_for_victory();
+    }
+
+    fn start_turn(&mut self) {
+        let actor_id = self.get_current_combatant_id();
+        self.actions_this_turn = 0;
+        self.log_event(CombatEvent::TurnStarted(actor_id));
+
+        let combatant = &mut self.combatants[actor_id];
+        combatant.budget = ActionBudget::full();
+        combatant.is_defending = false;
+        combatant.abilities
+            .iter_mut()
+            .for_each(|ability| ability.cooldown_remaining = ability.cooldown_remaining.saturating_sub(1));
+
+        if let Some(mut condition) = combatant.mental_condition {
+            if condition.remaining_turns == 0 {
+                combatant.team = condition.original_team;
+                combatant.mental_condition = None;
+                self.log_event(CombatEvent::MentalConditionEnded { combatant: actor_id, condition: condition.kind });
+            } else {
+                condition.remaining_turns -= 1;
+                combatant.mental_condition = Some(condition);
+            }
+        }
+
+        let combatant = &mut self.combatants[actor_id];
+
+        let mut status_effects = std::mem::take(&mut combatant.status_effects);
+        let events = status_effects.tick_turn(combatant);
+        combatant.status_effects = status_effects;
+
+        for event in events {
+            let event = match event {
+                StatusEvent::Ticked { effect_id, pool, amount } => CombatEvent::StatusTicked { combatant: actor_id, effect_id, pool, amount },
+                StatusEvent::Expired(effect_id) => CombatEvent::StatusExpired { combatant: actor_id, effect_id },
+            };
+            self.log_event(event);
+        }
+
+        if self.combatants[actor_id].is_defeated() {
+            self.log_defeat(actor_id);
+            self.check_for_victory();
+
+            if self.status == CombatStatus::Ongoing {
+                self.end_turn();
+            }
+        }
+    }
+
+    /// Passes the turn to the next combatant still standing, starting a new round after the last one.
+    pub fn end_turn(&mut self) {
+        if self.status != CombatStatus::Ongoing {
+            return;
+        }
+
+        let actor_id = self.get_current_combatant_id();
+        self.log_event(CombatEvent::TurnEnded(actor_id));
+
+        loop {
+            self.turn_index += 1;
+
+            if self.turn_index >= self.initiative_order.len() {
+                self.turn_index = 0;
+                self.round += 1;
+                self.log_event(CombatEvent::RoundStarted);
+            }
+
+            if !self.combatants[self.get_current_combatant_id()].is_defeated() {
+                break;
+            }
+        }
+
+        self.start_turn();
+    }
+
+    pub fn check_for_victory(&mut self) -> CombatStatus {
+        if self.status != CombatStatus::Ongoing {
+            return self.status;
+        }
+
+        let is_standing = |team: CombatTeam| self.combatants
+            .iter()
+            .any(|combatant| combatant.team == team && !combatant.is_defeated());
+
+        let winner = match (is_standing(CombatTeam::Party), is_standing(CombatTeam::Hostile)) {
+            (true, false) => Some(CombatTeam::Party),
+            (false, true) => Some(CombatTeam::Hostile),
+            // nobody left standing counts as a loss for the party
+            (false, false) => Some(CombatTeam::Hostile),
+            (true, true) => None,
+        };
+
+        if let Some(winner) = winner {
+            self.status = CombatStatus::Victory(winner);
+            self.log_event(CombatEvent::CombatEnded { winner });
+        }
+
+        self.status
+    }
+
+    /// Lets the controller play the fight out, stopping early after `max_rounds`.
+    pub fn run<C: CombatController>(&mut self, controller: &mut C, max_rounds: u32) -> CombatStatus {
+        while self.status == CombatStatus::Ongoing && self.round <= max_rounds {
+            let actor_id = self.get_current_combatant_id();
+
+            if self.actions_this_turn >= MAX_ACTIONS_PER_TURN {
+                self.end_turn();
+                continue;
+            }
+
+            let action = controller.choose_action(self, actor_id);
+            if self.perform_action(action).is_err() {
+                // an invalid choice can't stall the fight
+                self.end_turn();
+            }
+        }
+
+        self.status
+    }
+
+    /// Applies the notoriety the party gained during the fight to the player's reputation, including what
+    /// companions gained (the player answers for their party). Each gain is only applied once.
+    pub fn apply_party_notoriety(&mut self, reputation: &mut PlayerReputation, factions: &FactionRegistry) -> Vec<ReputationChange> {
+        let mut notoriety: Vec<(String, i32)> = self.combatants
+            .iter_mut()
+            .filter(|combatant| combatant.mental_condition.map_or(combatant.team, |condition| condition.original_team) == CombatTeam::Party)
+            .flat_map(|combatant| std::mem::take(&mut combatant.notoriety))
+            .collect();
+        notoriety.sort();
+
+        notoriety
+            .into_iter()
+            .flat_map(|(faction_id, amount)| reputation.record_notoriety(&faction_id, amount, factions))
+            .collect()
+    }
+
+    /// A `QuestEvent::Killed` for every NPC the party defeated, to pass to `handle_quest_event` once the fight is over.
+    /// Allies that fall don't count.
+    pub fn get_quest_events(&self) -> Vec<QuestEvent> {
+        self.log
+            .get_entries()
+            .iter()
+            .filter_map(|entry| match &entry.event {
+                CombatEvent::Defeated { combatant, .. } => self.combatants.get(*combatant),
+                _ => None,
+            })
+            .filter(|combatant| combatant.mental_condition.map_or(combatant.team, |condition| condition.original_team) != CombatTeam::Party)
+            .filter_map(|combatant| combatant.npc_id.clone())
+            .map(QuestEvent::Killed)
+            .collect()
+    }
+
+    pub fn get_defeated(&self) -> Vec<CombatantId> {
+        self.combatants
+            .iter()
+            .enumerate()
+            .filter(|(_, combatant)| combatant.is_defeated())
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::abilities::ability::{AbilityCost, AbilityDefinition, AbilityEffect, DamageKind, ForbiddenRisk, KnownAbility, StatScaling, TargetFilter, TargetingRule};
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatBlock, StatsEnum, StatValues};
+    use crate::actors::player::PlayerStats;
+    use crate::combat::combat_engine::{AbilityTarget, Combat, CombatAction, CombatError, CombatStatus};
+    use crate::combat::combat_log::CombatEvent;
+    use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+    use crate::combat::controller::AggressiveController;
+    use crate::equipment::equipment_manager::{WeaponStats, WeaponType};
+    use crate::factions::faction::FactionRegistry;
+    use crate::factions::reputation::PlayerReputation;
+    use crate::state_management::player_state::PlayerState;
+    use crate::status_effects::status_effect::{ResourceTick, StackingRule, StatusDuration, StatusEffectDefinition, StatusStatModifier};
+
+    fn create_encounter() -> Vec<Combatant> {
+        let mut bandit_stats = StatBlock::default();
+        bandit_stats.set_stat(StatsEnum::Strength, 6);
+
+        vec![
+            Combatant::from_player_stats("Player", &PlayerStats::new())
+                .with_weapon(WeaponStats::new(&WeaponType::Sword, 10, 16))
+                .with_position(GridPosition::new(0, 0)),
+            Combatant::from_npc("Bandit", CombatTeam::Hostile, &BaseNpcStats::new(1, Some(bandit_stats)))
+                .with_weapon(WeaponStats::new(&WeaponType::Dagger, 2, 4))
+                .with_faction("roadside_bandits")
+                .with_position(GridPosition::new(4, 0)),
+        ]
+    }
+
+    #[test]
+    fn test_player_carries_effects_into_and_out_of_combat() {
+        let mut player = PlayerState::new();
+        player.name = String::from("Ada");
+        let poison = StatusEffectDefinition {
+            id: String::from("poisoned"),
+            name: String::from("Poisoned"),
+            is_harmful: true,
+            duration: StatusDuration::Turns(5),
+            stacking: StackingRule::Refresh,
+            tick_changes: vec![ResourceTick { pool: ResourcePoolEnum::Health, amount: -3 }],
+            stat_modifiers: vec![StatusStatModifier { stat: StatsEnum::Dexterity, amount: -2 }],
+            attribute_modifiers: Vec::new(),
+            grants_immunity_to: Vec::new(),
+        };
+        player.status_effects.apply_effect(&poison);
+        let health = player.get_resource(ResourcePoolEnum::Health);
+
+        let mut combatant = Combatant::from_player(&player);
+        assert_eq!(combatant.name, "Ada");
+        assert_eq!(combatant.get_effective_stat(StatsEnum::Dexterity), player.get_effective_stat(StatsEnum::Dexterity));
+        assert_eq!(combatant.get_effective_stat(StatsEnum::Dexterity), player.player_stats.get_stat(StatsEnum::Dexterity) - 2);
+
+        combatant.modify_resource(ResourcePoolEnum::Health, -10);
+        let mut status_effects = std::mem::take(&mut combatant.status_effects);
+        status_effects.tick_turn(&mut combatant);
+        combatant.status_effects = status_effects;
+        combatant.write_back_to_player(&mut player);
+        assert_eq!(player.get_resource(ResourcePoolEnum::Health), health - 13);
+        assert!(player.status_effects.has_effect("poisoned"));
+    }
+
+    #[test]
+    fn test_same_seed_same_fight() {
+        let mut first = Combat::new(create_encounter(), 42);
+        let mut second = Combat::new(create_encounter(), 42);
+
+        let first_status = first.run(&mut AggressiveController, 100);
+        let second_status = second.run(&mut AggressiveController, 100);
+
+        assert_eq!(first_status, CombatStatus::Victory(CombatTeam::Party));
+        assert_eq!(first_status, second_status);
+        assert_eq!(first.get_log(), second.get_log());
+        assert_eq!(first.get_defeated(), vec![1]);
+
+        // the bandit's faction comes with its defeat, so the kill can cost the player reputation
+        let factions = FactionRegistry::new();
+        let mut reputation = PlayerReputation::new();
+        for entry in first.get_log().get_entries() {
+            if let CombatEvent::Defeated { faction_id: Some(faction_id), .. } = &entry.event {
+                reputation.record_kill(faction_id, &factions);
+            }
+        }
+        assert!(first.get_log().get_entries().iter().any(|entry| entry.event == CombatEvent::Defeated { combatant: 1, faction_id: Some("roadside_bandits".to_string()) }));
+        assert_eq!(reputation.get_reputation("roadside_bandits", &factions), -5);
+    }
+
+    #[test]
+    fn test_action_economy() {
+        let mut combat = Combat::new(create_encounter(), 3);
+        let actor = combat.get_current_combatant_id();
+        let target = 1 - actor;
+
+        assert_eq!(combat.perform_action(CombatAction::Attack { target }), Err(CombatError::OutOfReach(target)));
+        assert_eq!(
+            combat.perform_action(CombatAction::Move { to: GridPosition::new(40, 0) }),
+            Err(CombatError::TooFar { distance: 40 - combat.get_combatant(actor).unwrap().position.x.abs(), movement: 5 })
+        );
+
+        let target_position = combat.get_combatant(target).unwrap().position;
+        assert_eq!(combat.perform_action(CombatAction::Move { to: target_position }), Err(CombatError::PositionOccupied(target_position)));
+
+        combat.perform_action(CombatAction::Defend).unwrap();
+        assert_eq!(combat.perform_action(CombatAction::Defend), Err(CombatError::NoActionLeft));
+        assert!(combat.get_combatant(actor).unwrap().is_defending);
+
+        combat.perform_action(CombatAction::EndTurn).unwrap();
+        assert_eq!(combat.get_current_combatant_id(), target);
+        assert_eq!(
+            combat.get_combatant(target).unwrap().get_resource(ResourcePoolEnum::Health),
+            combat.get_combatant(target).unwrap().get_resource_limit(ResourcePoolEnum::Health)
+        );
+    }
+
+    #[test]
+    fn test_forbidden_ability() {
+        let bolt = AbilityDefinition {
+            id: "blood_bolt".to_string(),
+            name: "Blood Bolt".to_string(),
+            description: "Hurls a lance of boiling blood.".to_string(),
+            costs: vec![AbilityCost { pool: ResourcePoolEnum::Mana, amount: 5 }],
+            cooldown_turns: 2,
+            targeting: TargetingRule::Single { range: 6 },
+            affects: TargetFilter::Enemies,
+            effects: vec![AbilityEffect::Damage { kind: DamageKind::Physical, min: 4, max: 6 }],
+            scaling: None,
+            forbidden: Some(ForbiddenRisk {
+                madness_chance_percent: 100,
+                madness_amount: 3,
+                notoriety_faction_id: "church".to_string(),
+                notoriety: 10,
+            }),
+        };
+
+        let mut encounter = create_encounter();
+        encounter[0] = encounter[0].clone().with_abilities(vec![KnownAbility::new(bolt)]);
+        let mut combat = Combat::new(encounter, 7);
+        if combat.get_current_combatant_id() != 0 {
+            combat.perform_action(CombatAction::EndTurn).unwrap();
+        }
+
+        let mana_before = combat.get_combatant(0).unwrap().get_resource(ResourcePoolEnum::Mana);
+        let madness_before = combat.get_combatant(0).unwrap().get_resource(ResourcePoolEnum::Madness);
+        assert_eq!(
+            combat.perform_action(CombatAction::UseAbility { ability: 0, target: AbilityTarget::Caster }),
+            Err(CombatError::InvalidAbilityTarget)
+        );
+        combat.perform_action(CombatAction::UseAbility { ability: 0, target: AbilityTarget::Combatant(1) }).unwrap();
+
+        let caster = combat.get_combatant(0).unwrap();
+        assert_eq!(caster.get_resource(ResourcePoolEnum::Mana), mana_before - 5);
+        assert_eq!(caster.get_resource(ResourcePoolEnum::Madness), madness_before + 3);
+        assert_eq!(caster.abilities[0].cooldown_remaining, 2);
+        assert!(combat.get_combatant(1).unwrap().get_resource(ResourcePoolEnum::Health) < combat.get_combatant(1).unwrap().get_resource_limit(ResourcePoolEnum::Health));
+        assert!(combat.get_log().get_entries().iter().any(|entry| entry.event == CombatEvent::NotorietyGained { caster: 0, faction_id: "church".to_string(), amount: 10 }));
+
+        let factions = FactionRegistry::new();
+        let mut reputation = PlayerReputation::new();
+        assert_eq!(combat.apply_party_notoriety(&mut reputation, &factions).len(), 1);
+        assert_eq!(reputation.get_reputation("church", &factions), -10);
+        assert!(combat.apply_party_notoriety(&mut reputation, &factions).is_empty());
+        assert_eq!(
+            combat.perform_action(CombatAction::UseAbility { ability: 0, target: AbilityTarget::Combatant(1) }),
+            Err(CombatError::NoActionLeft)
+        );
+    }
+
+    #[test]
+    fn test_buffs_scale_ability_damage() {
+        let spark = AbilityDefinition {
+            id: "spark".to_string(),
+            name: "Spark".to_string(),
+            description: "A crackle of stolen lightning.".to_string(),
+            costs: Vec::new(),
+            cooldown_turns: 0,
+            targeting: TargetingRule::Single { range: 6 },
+            affects: TargetFilter::Enemies,
+            effects: vec![AbilityEffect::Damage { kind: DamageKind::Physical, min: 20, max: 20 }],
+            scaling: Some(StatScaling { stat: StatsEnum::Intelligence, percent_per_point: 10 }),
+            forbidden: None,
+        };
+        let insight = StatusEffectDefinition {
+            id: String::from("insight"),
+            name: String::from("Insight"),
+            is_harmful: false,
+            duration: StatusDuration::Turns(3),
+            stacking: StackingRule::Refresh,
+            tick_changes: Vec::new(),
+            stat_modifiers: vec![StatusStatModifier { stat: StatsEnum::Intelligence, amount: 5 }],
+            attribute_modifiers: Vec::new(),
+            grants_immunity_to: Vec::new(),
+        };
+
+        let cast_spark = |is_buffed: bool| {
+            let mut encounter = create_encounter();
+            encounter[0] = encounter[0].clone().with_abilities(vec![KnownAbility::new(spark.clone())]);
+            if is_buffed {
+                encounter[0].status_effects.apply_effect(&insight);
+            }
+
+            let mut combat = Combat::new(encounter, 7);
+            if combat.get_current_combatant_id() != 0 {
+                combat.perform_action(CombatAction::EndTurn).unwrap();
+            }
+            combat.perform_action(CombatAction::UseAbility { ability: 0, target: AbilityTarget::Combatant(1) }).unwrap();
+            combat.get_log().get_entries().iter().find_map(|entry| match entry.event {
+                CombatEvent::AbilityDamaged { amount, .. } => Some(amount),
+                _ => None,
+            }).unwrap()
+        };
+
+        assert!(cast_spark(true) > cast_spark(false));
+    }
+}
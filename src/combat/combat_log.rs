@@ -0,0 +1,107 @@
+use crate::abilities::ability::DamageKind;
+use crate::actors::models::ResourcePoolEnum;
+use crate::combat::combatant::{Combatant, CombatantId, CombatTeam, GridPosition};
+use crate::combat::psychological::MentalConditionKind;
+use crate::status_effects::active_effects::ApplyStatusResult;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CombatEvent {
+    InitiativeRolled { combatant: CombatantId, initiative: i32 },
+    RoundStarted,
+    TurnStarted(CombatantId),
+    Moved { combatant: CombatantId, from: GridPosition, to: GridPosition },
+    Attacked { attacker: CombatantId, target: CombatantId, hit_chance: i32, is_hit: bool, is_critical: bool, is_reaction: bool, damage: i32 },
+    MindAttacked { attacker: CombatantId, target: CombatantId, hit_chance: i32, is_hit: bool, madness: i32 },
+    MentalBreak { combatant: CombatantId, condition: MentalConditionKind },
+    MentalConditionEnded { combatant: CombatantId, condition: MentalConditionKind },
+    AbilityUsed { caster: CombatantId, ability_id: String },
+    AbilityDamaged { caster: CombatantId, target: CombatantId, kind: DamageKind, amount: i32 },
+    Restored { caster: CombatantId, target: CombatantId, pool: ResourcePoolEnum, amount: i32 },
+    StatusApplied { combatant: CombatantId, effect_id: String, result: ApplyStatusResult },
+    StatusRemoved { combatant: CombatantId, effect_id: String },
+    ForbiddenBacklash { caster: CombatantId, madness: i32 },
+    NotorietyGained { caster: CombatantId, faction_id: String, amount: i32 },
+    Defended(CombatantId),
+    StatusTicked { combatant: CombatantId, effect_id: String, pool: ResourcePoolEnum, amount: i32 },
+    StatusExpired { combatant: CombatantId, effect_id: String },
+    // the faction is carried along so the caller can turn kills into reputation changes
+    Defeated { combatant: CombatantId, faction_id: Option<String> },
+    TurnEnded(CombatantId),
+    CombatEnded { winner: CombatTeam },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombatLogEntry {
+    pub round: u32,
+    pub event: CombatEvent,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CombatLog {
+    entries: Vec<CombatLogEntry>,
+}
+
+impl CombatLog {
+    pub fn new() -> Self {
+        CombatLog {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, round: u32, event: CombatEvent) {
+        self.entries.push(CombatLogEntry { round, event });
+    }
+
+    pub fn get_entries(&self) -> &[CombatLogEntry] {
+        &self.entries
+    }
+
+    /// Human readable lines for debugging and the balance tools.
+    pub fn to_lines(&self, combatants: &[Combatant]) -> Vec<String> {
+        let name = |id: &CombatantId| combatants
+            .get(*id)
+            .map(|combatant| combatant.name.clone())
+            .unwrap_or_else(|| format!("#{id}"));
+
+        self.entries
+            .iter()
+            .map(|entry| {
+                let text = match &entry.event {
+                    CombatEvent::InitiativeRolled { combatant, initiative } => format!("{} rolls {initiative} initiative", name(combatant)),
+                    CombatEvent::RoundStarted => String::from("round starts"),
+                    CombatEvent::TurnStarted(combatant) => format!("{}'s turn", name(combatant)),
+                    CombatEvent::Moved { combatant, from, to } => format!("{} moves from ({}, {}) to ({}, {})", name(combatant), from.x, from.y, to.x, to.y),
+                    CombatEvent::Attacked { attacker, target, hit_chance, is_hit, is_critical, is_reaction, damage } => {
+                        let kind = if *is_reaction { "reacts and attacks" } else { "attacks" };
+                        match (is_hit, is_critical) {
+                            (false, _) => format!("{} {kind} {} ({hit_chance}%) and misses", name(attacker), name(target)),
+                            (true, true) => format!("{} {kind} {} ({hit_chance}%) and critically hits for {damage}", name(attacker), name(target)),
+                            (true, false) => format!("{} {kind} {} ({hit_chance}%) and hits for {damage}", name(attacker), name(target)),
+                        }
+                    },
+                    CombatEvent::MindAttacked { attacker, target, hit_chance, is_hit, madness } => match is_hit {
+                        true => format!("{} assails the mind of {} ({hit_chance}%) for {madness} madness", name(attacker), name(target)),
+                        false => format!("{} assails the mind of {} ({hit_chance}%) but they resist", name(attacker), name(target)),
+                    },
+                    CombatEvent::MentalBreak { combatant, condition } => format!("{} breaks and is {condition:?}", name(combatant)),
+                    CombatEvent::MentalConditionEnded { combatant, condition } => format!("{} is no longer {condition:?}", name(combatant)),
+                    CombatEvent::AbilityUsed { caster, ability_id } => format!("{} uses {ability_id}", name(caster)),
+                    CombatEvent::AbilityDamaged { caster, target, kind, amount } => format!("{} deals {amount} {kind:?} damage to {}", name(caster), name(target)),
+                    CombatEvent::Restored { caster, target, pool, amount } => format!("{} restores {amount} {pool:?} to {}", name(caster), name(target)),
+                    CombatEvent::StatusApplied { combatant, effect_id, result } => format!("{effect_id} on {}: {result:?}", name(combatant)),
+                    CombatEvent::StatusRemoved { combatant, effect_id } => format!("{effect_id} is removed from {}", name(combatant)),
+                    CombatEvent::ForbiddenBacklash { caster, madness } => format!("forbidden magic claws at {}'s mind for {madness} madness", name(caster)),
+                    CombatEvent::NotorietyGained { caster, faction_id, amount } => format!("{} gains {amount} notoriety with {faction_id}", name(caster)),
+                    CombatEvent::Defended(combatant) => format!("{} defends", name(combatant)),
+                    CombatEvent::StatusTicked { combatant, effect_id, pool, amount } => format!("{} {amount} {pool:?} from {effect_id}", name(combatant)),
+                    CombatEvent::StatusExpired { combatant, effect_id } => format!("{effect_id} wears off {}", name(combatant)),
+                    CombatEvent::Defeated { combatant, .. } => format!("{} is defeated", name(combatant)),
+                    CombatEvent::TurnEnded(combatant) => format!("{} ends their turn", name(combatant)),
+                    CombatEvent::CombatEnded { winner } => format!("combat ends, {winner:?} wins"),
+                };
+
+                format!("[round {}] {text}", entry.round)
+            })
+            .collect()
+    }
+}
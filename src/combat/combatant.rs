@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::abilities::ability::KnownAbility;
+use crate::actors::base_npc::BaseNpcStats;
+use crate::actors::models::{ComputedAttributeEnum, ComputedAttributes, ComputedAttributeValues, ResourcePoolEnum, ResourcePools, ResourcePoolValues, StatBlock, StatsEnum, StatValues};
+use crate::actors::player::PlayerStats;
+use crate::combat::psychological::{MentalCondition, MentalConditionKind, PsychologicalAttack};
+use crate::equipment::equipment_manager::WeaponStats;
+use crate::party::companion::Companion;
+use crate::state_management::player_state::PlayerState;
+use crate::status_effects::active_effects::StatusEffects;
+
+pub type CombatantId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CombatTeam {
+    Party,
+    Hostile,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct GridPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl GridPosition {
+    pub fn new(x: i32, y: i32) -> Self {
+        GridPosition { x, y }
+    }
+
+    /// Distance in grid cells, counting diagonal steps as one.
+    pub fn distance_to(&self, other: &GridPosition) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    /// The position `steps` cells from here towards `target`, stopping on the target.
+    pub fn step_towards(&self, target: &GridPosition, steps: i32) -> GridPosition {
+        let dx = (target.x - self.x).clamp(-steps, steps);
+        let dy = (target.y - self.y).clamp(-steps, steps);
+        GridPosition::new(self.x + dx, self.y + dy)
+    }
+}
+
+/// The move, action and reaction a combatant may spend each round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionBudget {
+    pub has_move: bool,
+    pub has_action: bool,
+    pub has_reaction: bool,
+}
+
+impl ActionBudget {
+    pub fn full() -> Self {
+        ActionBudget {
+            has_move: true,
+            has_action: true,
+            has_reaction: true,
+        }
+    }
+}
+
+/// A copy of an actor's stats that takes part in a fight. Anything that should outlast the fight
+/// (e.g. remaining health) is written back by the caller once combat is over.
+#[derive(Debug, Clone)]
+pub struct Combatant {
+    pub name: String,
+    pub team: CombatTeam,
+    // the faction an NPC belongs to, None for the party and anyone without one
+    pub faction_id: Option<String>,
+    // the template an NPC was spawned from, which is what quest kill objectives count
+    pub npc_id: Option<String>,
+    pub level: u32,
+    pub base_stats: StatBlock,
+    pub computed_attributes: ComputedAttributes,
+    pub resources: ResourcePools,
+    pub status_effects: StatusEffects,
+    pub weapon: WeaponStats,
+    pub psychological_attack: Option<PsychologicalAttack>,
+    pub abilities: Vec<KnownAbility>,
+    pub mental_condition: Option<MentalCondition>,
+    // notoriety gained per faction during the fight, applied to the player's reputation by the caller
+    pub notoriety: HashMap<String, i32>,
+    pub position: GridPosition,
+    pub budget: ActionBudget,
+    pub is_defending: bool,
+}
+
+impl Combatant {
+    pub fn new(name: &str, team: CombatTeam, level: u32, base_stats: StatBlock, computed_attributes: ComputedAttributes, resources: ResourcePools) -> Self {
+        Combatant {
+            name: name.to_string(),
+            team,
+            faction_id: None,
+            npc_id: None,
+            level,
+            base_stats,
+            computed_attributes,
+            resources,
+            status_effects: StatusEffects::new(),
+            weapon: WeaponStats::unarmed(),
+            psychological_attack: None,
+            abilities: Vec::new(),
+            mental_condition: None,
+            notoriety: HashMap::new(),
+            position: GridPosition::default(),
+            budget: ActionBudget::full(),
+            is_defending: false,
+        }
+    }
+
+    /// The player as they are right now, buffs, poisons and all. Call `write_back_to_player` once the fight is over.
+    pub fn from_player(player: &PlayerState) -> Self {
+        let name = match player.name.is_empty() {
+            true => "Player",
+            false => player.name.as_str(),
+        };
+
+        let mut combatant = Self::from_player_stats(name, &player.player_stats);
+        combatant.status_effects = player.status_effects.clone();
+        combatant
+    }
+
+    /// A party member built from bare stats, e.g. a companion, without any status effects.
+    pub fn from_player_stats(name: &str, player_stats: &PlayerStats) -> Self {
+        Self::new(name, CombatTeam::Party, player_stats.level, player_stats.base_stats, player_stats.computed_attributes, player_stats.resources)
+    }
+
+    pub fn from_npc(name: &str, team: CombatTeam, npc_stats: &BaseNpcStats) -> Self {
+        Self::new(name, team, npc_stats.level, npc_stats.base_stats, npc_stats.computed_attributes, npc_stats.resources)
+    }
+
+    pub fn with_faction(mut self, faction_id: &str) -> Self {
+        self.faction_id = Some(faction_id.to_string());
+        self
+    }
+
+    pub fn with_npc_id(mut self, npc_id: &str) -> Self {
+        self.npc_id = Some(npc_id.to_string());
+        self
+    }
+
+    pub fn with_weapon(mut self, weapon: WeaponStats) -> Self {
+        self.weapon = weapon;
+        self
+    }
+
+    pub fn with_psychological_attack(mut self, attack: PsychologicalAttack) -> Self {
+        self.psychological_attack = Some(attack);
+        self
+    }
+
+    pub fn with_abilities(mut self, abilities: Vec<KnownAbility>) -> Self {
+        self.abilities = abilities;
+        self
+    }
+
+    pub fn with_position(mut self, position: GridPosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        self.resources.get_resource(ResourcePoolEnum::Health) <= 0
+    }
+
+    /// Grid cells the combatant can cover with its move.
+    pub fn get_movement(&self) -> i32 {
+        3 + self.get_effective_stat(StatsEnum::Dexterity) / 5
+    }
+
+    pub fn get_effective_stat(&self, stat: StatsEnum) -> i32 {
+        self.status_effects.get_modified_stat(self, stat)
+    }
+
+    pub fn get_effective_attribute(&self, attribute: ComputedAttributeEnum) -> i32 {
+        self.status_effects.get_modified_attribute(self, attribute)
+    }
+
+    /// Carries what the fight did to the player over to them: their resources and the status effects still active.
+    /// Mental conditions only last for the fight and are left behind.
+    pub fn write_back_to_player(&self, player: &mut PlayerState) {
+        player.player_stats.resources = self.resources;
+        player.status_effects = self.status_effects.clone();
+    }
+
+    /// The same as `write_back_to_player`, for a companion that fought alongside the player.
+    pub fn write_back_to_companion(&self, companion: &mut Companion) {
+        companion.stats.resources = self.resources;
+        companion.status_effects = self.status_effects.clone();
+    }
+
+    pub fn has_mental_condition(&self, kind: MentalConditionKind) -> bool {
+        self.mental_condition.is_some_and(|condition| condition.kind == kind)
+    }
+
+    pub fn can_reach(&self, other: &Combatant) -> bool {
+        self.position.distance_to(&other.position) <= self.weapon.reach
+    }
+}
+
+impl StatValues for Combatant {
+    fn get_stat(&self, stat: StatsEnum) -> i32 {
+        self.base_stats.get_stat(stat)
+    }
+
+    fn set_stat(&mut self, stat: StatsEnum, value: i32) {
+        self.base_stats.set_stat(stat, value);
+    }
+
+    fn temp_modify_stat(&self, stat: StatsEnum, multiplier: f32) -> i32 {
+        let base_stat = self.base_stats.get_stat(stat);
+
+        (base_stat as f32 * multiplier).floor() as i32
+    }
+}
+
+impl ComputedAttributeValues for Combatant {
+    fn get_computed_attribute(&self, attribute: ComputedAttributeEnum) -> i32 {
+        self.computed_attributes.get_computed_attribute(attribute)
+    }
+
+    fn set_computed_attribute(&mut self, attribute: ComputedAttributeEnum, value: i32) {
+        self.computed_attributes.set_computed_attribute(attribute, value);
+    }
+
+    fn temp_modify_computed_attribute(&self, attribute: ComputedAttributeEnum, multiplier: f32) -> i32 {
+        let base_attribute = self.computed_attributes.get_computed_attribute(attribute);
+
+        (base_attribute as f32 * multiplier).floor() as i32
+    }
+}
+
+impl ResourcePoolValues for Combatant {
+    fn get_resource(&self, pool: ResourcePoolEnum) -> i32 {
+        self.resources.get_resource(pool)
+    }
+
+    fn get_resource_limit(&self, pool: ResourcePoolEnum) -> i32 {
+        self.computed_attributes.get_computed_attribute(pool.limiting_attribute())
+    }
+
+    fn set_resource(&mut self, pool: ResourcePoolEnum, value: i32) {
+        self.resources.set_resource(pool, value);
+    }
+}
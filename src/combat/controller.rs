@@ -0,0 +1,61 @@
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+use crate::combat::combat_engine::{Combat, CombatAction, CombatController};
+use crate::combat::combatant::CombatantId;
+use crate::combat::psychological::MentalConditionKind;
+
+/// Walks up to the nearest enemy and hits whichever enemy in reach is closest to going down,
+/// falling back on a psychological attack when no weapon target is in reach.
+/// Used for headless fights and anything without a smarter brain.
+#[derive(Debug, Default)]
+pub struct AggressiveController;
+
+impl CombatController for AggressiveController {
+    fn choose_action(&mut self, combat: &Combat, actor: CombatantId) -> CombatAction {
+        let Some(combatant) = combat.get_combatant(actor) else {
+            return CombatAction::EndTurn;
+        };
+
+        let enemies = combat.get_enemies_of(actor);
+
+        if combatant.has_mental_condition(MentalConditionKind::Feared) {
+            return match combatant.budget.has_action {
+                true => CombatAction::Defend,
+                false => CombatAction::EndTurn,
+            };
+        }
+
+        if combatant.budget.has_action {
+            let weakest_in_reach = enemies
+                .iter()
+                .filter(|enemy| combatant.can_reach(&combat.get_combatants()[**enemy]))
+                .min_by_key(|enemy| combat.get_combatants()[**enemy].get_resource(ResourcePoolEnum::Health));
+
+            if let Some(target) = weakest_in_reach {
+                return CombatAction::Attack { target: *target };
+            }
+
+            if let Some(attack) = &combatant.psychological_attack {
+                let sanest_in_reach = enemies
+                    .iter()
+                    .filter(|enemy| combatant.position.distance_to(&combat.get_combatants()[**enemy].position) <= attack.reach)
+                    .min_by_key(|enemy| combat.get_combatants()[**enemy].get_resource(ResourcePoolEnum::Madness));
+
+                if let Some(target) = sanest_in_reach {
+                    return CombatAction::MindAttack { target: *target };
+                }
+            }
+        }
+
+        if combatant.budget.has_move {
+            let nearest = enemies
+                .iter()
+                .min_by_key(|enemy| combatant.position.distance_to(&combat.get_combatants()[**enemy].position));
+
+            if let Some(position) = nearest.and_then(|enemy| combat.find_approach_position(actor, *enemy)) {
+                return CombatAction::Move { to: position };
+            }
+        }
+
+        CombatAction::EndTurn
+    }
+}
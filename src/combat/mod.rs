@@ -0,0 +1,6 @@
+pub mod combatant;
+pub mod combat_log;
+pub mod combat_engine;
+pub mod controller;
+pub mod psychological;
+pub mod balance;
\ No newline at end of file
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{MadnessTier, StatsEnum};
+use crate::combat::combatant::CombatTeam;
+
+const FEAR_TURNS: u32 = 2;
+const CONFUSION_TURNS: u32 = 2;
+const POSSESSION_TURNS: u32 = 3;
+
+/// An attack on the mind rather than the body. It fills the target's madness pool
+/// and is resisted by psychological resistance instead of physical resistance.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PsychologicalAttack {
+    pub name: String,
+    pub min_damage: i32,
+    pub max_damage: i32,
+    pub scaling_stat: StatsEnum,
+    pub reach: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MentalConditionKind {
+    // can't attack
+    Feared,
+    // attacks may land on anyone in reach, friend or foe
+    Confused,
+    // fights for the other side
+    Possessed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MentalCondition {
+    pub kind: MentalConditionKind,
+    pub remaining_turns: u32,
+    pub original_team: CombatTeam,
+}
+
+impl MentalConditionKind {
+    /// The condition a mind breaks into when it reaches the given madness tier, if any.
+    pub fn from_madness_tier(tier: MadnessTier) -> Option<Self> {
+        match tier {
+            MadnessTier::Broken => Some(MentalConditionKind::Possessed),
+            MadnessTier::Unhinged => Some(MentalConditionKind::Confused),
+            MadnessTier::Frightened => Some(MentalConditionKind::Feared),
+            MadnessTier::Uneasy | MadnessTier::Lucid => None,
+        }
+    }
+
+    pub fn get_duration(&self) -> u32 {
+        match self {
+            MentalConditionKind::Feared => FEAR_TURNS,
+            MentalConditionKind::Confused => CONFUSION_TURNS,
+            MentalConditionKind::Possessed => POSSESSION_TURNS,
+        }
+    }
+}
+
+impl CombatTeam {
+    pub fn get_opposing_team(&self) -> CombatTeam {
+        match self {
+            CombatTeam::Party => CombatTeam::Hostile,
+            CombatTeam::Hostile => CombatTeam::Party,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::models::{MadnessTier, ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+    use crate::actors::player::PlayerStats;
+    use crate::combat::combat_engine::{Combat, CombatAction, CombatError, CombatStatus};
+    use crate::combat::combat_log::CombatEvent;
+    use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+    use crate::combat::psychological::{MentalConditionKind, PsychologicalAttack};
+
+    fn create_horror() -> Combatant {
+        Combatant::from_npc("Whispering Horror", CombatTeam::Hostile, &BaseNpcStats::default())
+            .with_psychological_attack(PsychologicalAttack {
+                name: String::from("Whispers"),
+                min_damage: 30,
+                max_damage: 40,
+                scaling_stat: StatsEnum::Charisma,
+                reach: 4,
+            })
+            .with_position(GridPosition::new(3, 0))
+    }
+
+    #[test]
+    fn test_madness_tiers() {
+        assert_eq!(MadnessTier::from_values(0, 100), MadnessTier::Lucid);
+        assert_eq!(MadnessTier::from_values(50, 100), MadnessTier::Frightened);
+        assert_eq!(MadnessTier::from_values(80, 100), MadnessTier::Unhinged);
+        assert_eq!(MadnessTier::from_values(100, 100), MadnessTier::Broken);
+    }
+
+    #[test]
+    fn test_feared_combatant_can_not_attack() {
+        let player = Combatant::from_player_stats("Player", &PlayerStats::new());
+        let mut combat = Combat::new(vec![player, create_horror()], 11);
+
+        combat.apply_mental_condition(0, MentalConditionKind::Feared);
+        while combat.get_current_combatant_id() != 0 {
+            combat.perform_action(CombatAction::EndTurn).unwrap();
+        }
+        combat.get_combatant_mut(0).unwrap().position = GridPosition::new(2, 0);
+
+        assert_eq!(combat.perform_action(CombatAction::Attack { target: 1 }), Err(CombatError::Feared));
+    }
+
+    #[test]
+    fn test_possession_turns_a_lone_player() {
+        let mut player = Combatant::from_player_stats("Player", &PlayerStats::new());
+        let limit = player.get_resource_limit(ResourcePoolEnum::Madness);
+        player.set_resource(ResourcePoolEnum::Madness, limit - 1);
+
+        let mut combat = Combat::new(vec![player, create_horror()], 5);
+        while combat.get_current_combatant_id() != 1 {
+            combat.perform_action(CombatAction::EndTurn).unwrap();
+        }
+
+        while combat.get_status() == CombatStatus::Ongoing && combat.get_round() < 20 {
+            if combat.get_current_combatant_id() == 1 && combat.perform_action(CombatAction::MindAttack { target: 0 }).is_ok() {
+                continue;
+            }
+            combat.perform_action(CombatAction::EndTurn).unwrap();
+        }
+
+        assert_eq!(combat.get_status(), CombatStatus::Victory(CombatTeam::Hostile));
+        assert!(combat.get_log().get_entries().iter().any(|entry| entry.event == CombatEvent::MentalBreak { combatant: 0, condition: MentalConditionKind::Possessed }));
+    }
+}
@@ -0,0 +1 @@
+pub mod recipe;
\ No newline at end of file
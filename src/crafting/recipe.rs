@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum, StatValues};
+use crate::inventory::inventory_container::{InventoryContainer, InventoryError};
+use crate::inventory::item::ItemRegistry;
+use crate::utils::rng::SeededRng;
+
+// every point of luck above (or below) 10 shifts the success chance by 2%
+const SUCCESS_PERCENT_PER_LUCK: i32 = 2;
+const MIN_SUCCESS_PERCENT: i32 = 5;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CraftingError {
+    #[error("Unknown recipe: {0}")]
+    UnknownRecipe(String),
+    #[error("Unknown item: {0}")]
+    UnknownItem(String),
+    #[error("Missing ingredient {item_id}: requires {required}, available {available}")]
+    MissingIngredient { item_id: String, required: u32, available: u32 },
+    #[error("Missing tool: {0}")]
+    MissingTool(String),
+    #[error("Requires a {0:?}")]
+    WrongStation(CraftingStation),
+    #[error("Requires {required} {stat:?}, has {current}")]
+    StatTooLow { stat: StatsEnum, required: i32, current: i32 },
+    #[error("Not enough {pool:?} to pay the ritual cost of {required}")]
+    CostTooHigh { pool: ResourcePoolEnum, required: i32 },
+    #[error("Inventory error: {0}")]
+    Inventory(#[from] InventoryError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CraftingStation {
+    Workbench,
+    Forge,
+    AlchemyTable,
+    Altar,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeItem {
+    pub item_id: String,
+    pub quantity: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatRequirement {
+    pub stat: StatsEnum,
+    pub minimum: i32,
+}
+
+/// A price paid from the crafter's own resources. Madness costs are gained, every other pool is spent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RitualCost {
+    pub pool: ResourcePoolEnum,
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recipe {
+    pub id: String,
+    pub name: String,
+    pub inputs: Vec<RecipeItem>,
+    // tools have to be in the inventory but are not used up
+    pub tools: Vec<String>,
+    pub station: Option<CraftingStation>,
+    pub stat_requirements: Vec<StatRequirement>,
+    pub ritual_costs: Vec<RitualCost>,
+    pub outputs: Vec<RecipeItem>,
+    pub base_success_percent: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CraftingOutcome {
+    Success(Vec<RecipeItem>),
+    // the inputs and ritual costs are still spent on a failure
+    Failure,
+}
+
+impl Recipe {
+    pub fn is_ritual(&self) -> bool {
+        !self.ritual_costs.is_empty()
+    }
+
+    pub fn get_success_percent<T: StatValues>(&self, crafter: &T) -> i32 {
+        let luck_bonus = (crafter.get_stat(StatsEnum::Luck) - 10) * SUCCESS_PERCENT_PER_LUCK;
+        (self.base_success_percent + luck_bonus).clamp(MIN_SUCCESS_PERCENT, 100)
+    }
+
+    /// Checks everything needed to attempt the recipe without changing anything.
+    pub fn check_requirements<T: StatValues + ResourcePoolValues>(
+        &self,
+        crafter: &T,
+        inventory: &InventoryContainer,
+        station: Option<CraftingStation>
+    ) -> Result<(), CraftingError> {
+        if let Some(required_station) = self.station {
+            if station != Some(required_station) {
+                return Err(CraftingError::WrongStation(required_station));
+            }
+        }
+
+        for input in &self.inputs {
+            let available = inventory.get_item_count(&input.item_id);
+            if available < input.quantity {
+                return Err(CraftingError::MissingIngredient {
+                    item_id: input.item_id.clone(),
+                    required: input.quantity,
+                    available,
+                });
+            }
+        }
+
+        if let Some(tool) = self.tools.iter().find(|tool| !inventory.has_item(tool, 1)) {
+            return Err(CraftingError::MissingTool(tool.clone()));
+        }
+
+        for requirement in &self.stat_requirements {
+            let current = crafter.get_stat(requirement.stat);
+            if current < requirement.minimum {
+                return Err(CraftingError::StatTooLow { stat: requirement.stat, required: requirement.minimum, current });
+            }
+        }
+
+        for cost in &self.ritual_costs {
+            let current = crafter.get_resource(cost.pool);
+            let affordable = match cost.pool {
+                ResourcePoolEnum::Madness => current + cost.amount <= crafter.get_resource_limit(cost.pool),
+                // a ritual may bleed the crafter but never kill them
+                ResourcePoolEnum::Health => current > cost.amount,
+                _ => current >= cost.amount,
+            };
+
+            if !affordable {
+                return Err(CraftingError::CostTooHigh { pool: cost.pool, required: cost.amount });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RecipeBook {
+    recipes: HashMap<String, Recipe>,
+}
+
+impl RecipeBook {
+    pub fn new() -> Self {
+        RecipeBook {
+            recipes: HashMap::new(),
+        }
+    }
+
+    pub fn register_recipe(&mut self, recipe: Recipe) {
+        self.recipes.insert(recipe.id.clone(), recipe);
+    }
+
+    pub fn get_recipe(&self, recipe_id: &str) -> Option<&Recipe> {
+        self.recipes.get(recipe_id)
+    }
+
+    /// Recipes whose requirements are all met right now.
+    pub fn get_craftable_recipes<T: StatValues + ResourcePoolValues>(
+        &self,
+        crafter: &T,
+        inventory: &InventoryContainer,
+        station: Option<CraftingStation>
+    ) -> Vec<&Recipe> {
+        self.recipes
+            .values()
+            .filter(|recipe| recipe.check_requirements(crafter, inventory, station).is_ok())
+            .collect()
+    }
+
+    /// Crafts straight out of an inventory container. The work is done on a copy of the container
+    /// which only replaces the original once everything succeeded, and the crafter only pays the ritual
+    /// costs after that point, so an error leaves both the inventory and the crafter untouched.
+    pub fn craft<T: StatValues + ResourcePoolValues>(
+        &self,
+        recipe_id: &str,
+        crafter: &mut T,
+        inventory: &mut InventoryContainer,
+        items: &ItemRegistry,
+        station: Option<CraftingStation>,
+        rng: &mut SeededRng
+    ) -> Result<CraftingOutcome, CraftingError> {
+        let recipe = self
+            .get_recipe(recipe_id)
+            .ok_or_else(|| CraftingError::UnknownRecipe(recipe_id.to_string()))?;
+
+        recipe.check_requirements(crafter, inventory, station)?;
+
+        let mut updated_inventory = inventory.clone();
+        for input in &recipe.inputs {
+            updated_inventory.remove_item(&input.item_id, input.quantity)?;
+        }
+
+        let is_success = rng.chance(recipe.get_success_percent(crafter));
+        if is_success {
+            for output in &recipe.outputs {
+                let item = items
+                    .get_item(&output.item_id)
+                    .ok_or_else(|| CraftingError::UnknownItem(output.item_id.clone()))?;
+
+                updated_inventory.add_item(item, output.quantity)?;
+            }
+        }
+
+        // nothing from here on can fail, keep every fallible step above this point
+        for cost in &recipe.ritual_costs {
+            let change = match cost.pool {
+                ResourcePoolEnum::Madness => cost.amount,
+                _ => -cost.amount,
+            };
+            crafter.modify_resource(cost.pool, change);
+        }
+
+        *inventory = updated_inventory;
+
+        match is_success {
+            true => Ok(CraftingOutcome::Success(recipe.outputs.clone())),
+            false => Ok(CraftingOutcome::Failure),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+    use crate::actors::player::PlayerStats;
+    use crate::crafting::recipe::{CraftingError, CraftingOutcome, CraftingStation, Recipe, RecipeBook, RecipeItem, RitualCost, StatRequirement};
+    use crate::inventory::consumables::{ConsumableDefinition, ConsumableEffect, UseRestriction};
+    use crate::inventory::inventory_container::InventoryContainer;
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::state_management::player_state::PlayerState;
+    use crate::utils::rng::SeededRng;
+
+    fn recipe_item(item_id: &str, quantity: u32) -> RecipeItem {
+        RecipeItem { item_id: item_id.to_string(), quantity }
+    }
+
+    fn create_ritual(base_success_percent: i32, output_id: &str) -> Recipe {
+        Recipe {
+            id: String::from("bone_ward"),
+            name: String::from("Bone Ward"),
+            inputs: vec![recipe_item("bone", 2)],
+            tools: vec![String::from("ritual_knife")],
+            station: Some(CraftingStation::Altar),
+            stat_requirements: vec![StatRequirement { stat: StatsEnum::Wisdom, minimum: 10 }],
+            ritual_costs: vec![RitualCost { pool: ResourcePoolEnum::Health, amount: 5 }, RitualCost { pool: ResourcePoolEnum::Madness, amount: 3 }],
+            outputs: vec![recipe_item(output_id, 1)],
+            base_success_percent,
+        }
+    }
+
+    fn create_items() -> ItemRegistry {
+        let mut items = ItemRegistry::new();
+        items.register_item(ItemDefinition::new("bone", "Bone", "Picked clean.", ItemCategory::Material, 1, 20));
+        items.register_item(ItemDefinition::new("ritual_knife", "Ritual Knife", "Notched and stained.", ItemCategory::Tool, 10, 1));
+        items.register_item(ItemDefinition::new("bone_ward", "Bone Ward", "Rattles when something is near.", ItemCategory::Miscellaneous, 15, 5));
+        items
+    }
+
+    fn create_inventory(items: &ItemRegistry) -> InventoryContainer {
+        let mut inventory = InventoryContainer::new(Some(10));
+        inventory.add_item(items.get_item("bone").unwrap(), 3).unwrap();
+        inventory.add_item(items.get_item("ritual_knife").unwrap(), 1).unwrap();
+        inventory
+    }
+
+    #[test]
+    fn test_requirements_report_what_is_missing() {
+        let items = create_items();
+        let recipe = create_ritual(100, "bone_ward");
+        let crafter = PlayerStats::new();
+        let inventory = create_inventory(&items);
+        let altar = Some(CraftingStation::Altar);
+
+        assert_eq!(recipe.check_requirements(&crafter, &inventory, altar), Ok(()));
+        assert_eq!(recipe.check_requirements(&crafter, &inventory, Some(CraftingStation::Forge)), Err(CraftingError::WrongStation(CraftingStation::Altar)));
+
+        let mut missing_bones = inventory.clone();
+        missing_bones.remove_item("bone", 2).unwrap();
+        assert_eq!(
+            recipe.check_requirements(&crafter, &missing_bones, altar),
+            Err(CraftingError::MissingIngredient { item_id: String::from("bone"), required: 2, available: 1 })
+        );
+
+        let mut missing_knife = inventory.clone();
+        missing_knife.remove_item("ritual_knife", 1).unwrap();
+        assert_eq!(recipe.check_requirements(&crafter, &missing_knife, altar), Err(CraftingError::MissingTool(String::from("ritual_knife"))));
+
+        let mut demanding = recipe.clone();
+        demanding.stat_requirements[0].minimum = 14;
+        assert_eq!(
+            demanding.check_requirements(&crafter, &inventory, altar),
+            Err(CraftingError::StatTooLow { stat: StatsEnum::Wisdom, required: 14, current: 10 })
+        );
+
+        let mut bled = crafter;
+        bled.set_resource(ResourcePoolEnum::Health, 5);
+        assert_eq!(recipe.check_requirements(&bled, &inventory, altar), Err(CraftingError::CostTooHigh { pool: ResourcePoolEnum::Health, required: 5 }));
+    }
+
+    #[test]
+    fn test_craft_spends_inputs_and_costs_even_on_a_failed_roll() {
+        let items = create_items();
+        let mut book = RecipeBook::new();
+        book.register_recipe(create_ritual(0, "bone_ward"));
+        let mut crafter = PlayerStats::new();
+        let health = crafter.get_resource(ResourcePoolEnum::Health);
+        let mut inventory = create_inventory(&items);
+
+        let outcome = book.craft("bone_ward", &mut crafter, &mut inventory, &items, Some(CraftingStation::Altar), &mut SeededRng::new(3));
+        assert_eq!(outcome, Ok(CraftingOutcome::Failure));
+        assert_eq!(inventory.get_item_count("bone"), 1);
+        assert_eq!(inventory.get_item_count("bone_ward"), 0);
+        assert!(inventory.has_item("ritual_knife", 1));
+        assert_eq!(crafter.get_resource(ResourcePoolEnum::Health), health - 5);
+        assert_eq!(crafter.get_resource(ResourcePoolEnum::Madness), 3);
+    }
+
+    #[test]
+    fn test_player_buffs_count_towards_crafting_luck() {
+        let mut items = create_items();
+        items.register_item(ItemDefinition::new(
+            "lucky_coin",
+            "Lucky Coin",
+            "Always lands face up.",
+            ItemCategory::Consumable(ConsumableDefinition {
+                effects: vec![ConsumableEffect::StatBuff { stat: StatsEnum::Luck, amount: 5, duration_turns: 2 }],
+                cooldown_turns: 0,
+                restriction: UseRestriction::Anytime,
+            }),
+            5,
+            5,
+        ));
+        let mut book = RecipeBook::new();
+        book.register_recipe(create_ritual(50, "bone_ward"));
+        let mut player = PlayerState::new();
+        player.inventory = create_inventory(&items);
+        player.inventory.add_item(items.get_item("lucky_coin").unwrap(), 1).unwrap();
+
+        let recipe = book.get_recipe("bone_ward").unwrap();
+        assert_eq!(recipe.get_success_percent(&player), 50);
+        player.use_item(&items, "lucky_coin", false).unwrap();
+        assert_eq!(recipe.get_success_percent(&player), 60);
+
+        assert!(player.craft(&book, "bone_ward", &items, Some(CraftingStation::Altar), &mut SeededRng::new(3)).is_ok());
+        assert_eq!(player.inventory.get_item_count("bone"), 1);
+    }
+
+    #[test]
+    fn test_unknown_output_changes_nothing() {
+        let items = create_items();
+        let mut book = RecipeBook::new();
+        book.register_recipe(create_ritual(100, "cursed_ward"));
+        let mut crafter = PlayerStats::new();
+        let mut inventory = create_inventory(&items);
+        let (original_crafter, original_inventory) = (crafter, inventory.clone());
+
+        let outcome = book.craft("bone_ward", &mut crafter, &mut inventory, &items, Some(CraftingStation::Altar), &mut SeededRng::new(3));
+        assert_eq!(outcome, Err(CraftingError::UnknownItem(String::from("cursed_ward"))));
+        assert_eq!(inventory, original_inventory);
+        assert_eq!(crafter, original_crafter);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::dialogue::dialogue_line::{DialogueError, DialogueLine};
+use crate::story_tree::story_conditions::{StoryCondition, StoryContext};
+use crate::story_tree::story_management::PlayerStoryTree;
+use crate::story_tree::story_text::{render_text, StoryTemplate};
+use crate::utils::rng::SeededRng;
+use crate::world::schedule::Activity;
+
+/// What an NPC is reacting to when it barks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BarkTrigger {
+    PlayerNearby,
+    Idle,
+    CombatStarted,
+    Hurt,
+    Fleeing,
+    Activity(Activity),
+}
+
+/// An ambient one-liner, e.g. a guard muttering about the curfew as the player walks past.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bark {
+    pub id: String,
+    // barks without a speaker can be said by anyone
+    #[serde(default)]
+    pub speaker_id: Option<String>,
+    pub trigger: BarkTrigger,
+    pub text: String,
+    #[serde(default)]
+    pub mood: Option<String>,
+    #[serde(default)]
+    pub conditions: Vec<StoryCondition>,
+    #[serde(default = "default_bark_weight")]
+    pub weight: u32,
+    // game minutes before the same speaker can say it again
+    #[serde(default)]
+    pub cooldown_minutes: u64,
+}
+
+fn default_bark_weight() -> u32 {
+    1
+}
+
+impl Bark {
+    fn is_available(&self, speaker_id: &str, trigger: BarkTrigger, context: &StoryContext, story_tree: &PlayerStoryTree) -> bool {
+        self.trigger == trigger
+            && self.weight > 0
+            && self.speaker_id.as_deref().is_none_or(|bark_speaker| bark_speaker == speaker_id)
+            && self.conditions.iter().all(|condition| condition.is_met(context, story_tree))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BarkLibrary {
+    barks: Vec<Bark>,
+    // when each speaker last said each bark, in game minutes
+    last_said: HashMap<(String, String), u64>,
+    last_bark_by_speaker: HashMap<String, String>,
+}
+
+impl BarkLibrary {
+    pub fn new() -> Self {
+        BarkLibrary {
+            barks: Vec::new(),
+            last_said: HashMap::new(),
+            last_bark_by_speaker: HashMap::new(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, DialogueError> {
+        let barks: Vec<Bark> = serde_json::from_str(json).map_err(|error| DialogueError::InvalidData(error.to_string()))?;
+        let mut library = BarkLibrary::new();
+        for bark in barks {
+            StoryTemplate::parse(&bark.text).map_err(|error| DialogueError::InvalidData(format!("bark {}: {}", bark.id, error)))?;
+            library.add_bark(bark);
+        }
+        Ok(library)
+    }
+
+    pub fn add_bark(&mut self, bark: Bark) {
+        self.barks.push(bark);
+    }
+
+    fn is_cooling_down(&self, speaker_id: &str, bark: &Bark, now_minutes: u64) -> bool {
+        self.last_said
+            .get(&(speaker_id.to_string(), bark.id.clone()))
+            .is_some_and(|said_at| now_minutes < said_at + bark.cooldown_minutes)
+    }
+
+    /// Picks a weighted bark for the speaker that fits the trigger and the player's situation. Barks still
+    /// on cooldown are skipped, and the speaker won't repeat their last bark unless it's the only one left.
+    pub fn choose_bark(&mut self, speaker_id: &str, trigger: BarkTrigger, context: &StoryContext, story_tree: &PlayerStoryTree, now_minutes: u64, rng: &mut SeededRng) -> Option<DialogueLine> {
+        let mut available: Vec<&Bark> = self.barks
+            .iter()
+            .filter(|bark| bark.is_available(speaker_id, trigger, context, story_tree))
+            .filter(|bark| !self.is_cooling_down(speaker_id, bark, now_minutes))
+            .collect();
+
+        let last_bark_id = self.last_bark_by_speaker.get(speaker_id);
+        if available.len() > 1 {
+            available.retain(|bark| Some(&bark.id) != last_bark_id);
+        }
+
+        let total_weight: u32 = available.iter().map(|bark| bark.weight).sum();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut roll = rng.range_u32(1, total_weight);
+        let mut chosen = available[0];
+        for bark in &available {
+            if roll <= bark.weight {
+                chosen = bark;
+                break;
+            }
+            roll -= bark.weight;
+        }
+
+        // barks loaded from data have had their text checked, others are shown as they are if it's broken
+        let line = DialogueLine {
+            speaker_id: Some(speaker_id.to_string()),
+            mood: chosen.mood.clone(),
+            text: render_text(&chosen.text, context, story_tree).unwrap_or_else(|_| chosen.text.clone()),
+        };
+        let bark_id = chosen.id.clone();
+
+        self.last_said.insert((speaker_id.to_string(), bark_id.clone()), now_minutes);
+        self.last_bark_by_speaker.insert(speaker_id.to_string(), bark_id);
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialogue::barks::{BarkLibrary, BarkTrigger};
+    use crate::factions::faction::FactionRegistry;
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_conditions::StoryContext;
+    use crate::story_tree::story_management::PlayerStoryTree;
+    use crate::utils::rng::SeededRng;
+    use crate::world::schedule::Activity;
+
+    const BARKS: &str = r#"[
+        {"id": "curfew", "speaker_id": "guard", "trigger": "PlayerNearby", "text": "Curfew's at dusk.", "cooldown_minutes": 60},
+        {"id": "eyes", "trigger": "PlayerNearby", "text": "Keep your eyes down.", "mood": "wary"},
+        {"id": "chapel", "trigger": "PlayerNearby", "text": "You went into the chapel{if player_name != \"\"}, {player_name}{/if}?", "conditions": [{"FlagSet": "entered_chapel"}]},
+        {"id": "prayer", "trigger": {"Activity": "Pray"}, "text": "Forgive us."}
+    ]"#;
+
+    #[test]
+    fn test_barks_respect_conditions_and_cooldowns() {
+        let mut library = BarkLibrary::from_json(BARKS).unwrap();
+        let player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let context = StoryContext { player: &player, factions: &factions };
+        let mut story_tree = PlayerStoryTree::new();
+        let mut rng = SeededRng::new(3);
+
+        let first = library.choose_bark("guard", BarkTrigger::PlayerNearby, &context, &story_tree, 0, &mut rng).unwrap();
+        let second = library.choose_bark("guard", BarkTrigger::PlayerNearby, &context, &story_tree, 1, &mut rng).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.speaker_id.as_deref(), Some("guard"));
+
+        // the curfew bark is on cooldown and the eyes bark was said last, but it's the only one left
+        let third = library.choose_bark("guard", BarkTrigger::PlayerNearby, &context, &story_tree, 2, &mut rng).unwrap();
+        assert_eq!(third.text, "Keep your eyes down.");
+        assert_eq!(third.mood.as_deref(), Some("wary"));
+
+        // only the guard says the curfew bark, and the chapel bark waits for the flag
+        for minute in 0..10 {
+            let line = library.choose_bark("priest", BarkTrigger::PlayerNearby, &context, &story_tree, minute, &mut rng).unwrap();
+            assert_eq!(line.text, "Keep your eyes down.");
+        }
+        story_tree.story_flags.insert("entered_chapel".to_string());
+        let line = library.choose_bark("priest", BarkTrigger::PlayerNearby, &context, &story_tree, 10, &mut rng).unwrap();
+        assert_eq!(line.text, "You went into the chapel?");
+
+        assert_eq!(library.choose_bark("priest", BarkTrigger::Activity(Activity::Pray), &context, &story_tree, 11, &mut rng).unwrap().text, "Forgive us.");
+        assert_eq!(library.choose_bark("priest", BarkTrigger::Hurt, &context, &story_tree, 12, &mut rng), None);
+        assert!(BarkLibrary::from_json(r#"[{"id": "broken", "trigger": "Idle", "text": "{if}"}]"#).is_err());
+    }
+}
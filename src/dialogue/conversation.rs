@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::dialogue::dialogue_line::{parse_dialogue_lines, DialogueError, DialogueLine};
+use crate::story_tree::story_conditions::StoryContext;
+use crate::story_tree::story_effects::{StoryOutcome, StoryWorld};
+use crate::story_tree::story_graph::{OptionState, StoryGraph, StoryGraphError};
+use crate::story_tree::story_management::{PlayerStoryTree, StoryNode};
+use crate::utils::rng::SeededRng;
+
+/// A conversation with an NPC, written as story nodes. Who the NPC greets depends on whether they've met the player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dialogue {
+    pub id: String,
+    pub npc_id: String,
+    pub first_meeting_node_id: u32,
+    pub returning_node_id: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NpcConversation {
+    pub times_met: u32,
+    // the dialogue nodes the NPC has already told the player
+    pub heard_node_ids: HashSet<u32>,
+}
+
+/// What the player has talked about with every NPC.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversationMemory {
+    npcs: HashMap<String, NpcConversation>,
+}
+
+impl ConversationMemory {
+    pub fn new() -> Self {
+        ConversationMemory {
+            npcs: HashMap::new(),
+        }
+    }
+
+    pub fn get_conversation(&self, npc_id: &str) -> Option<&NpcConversation> {
+        self.npcs.get(npc_id)
+    }
+
+    pub fn is_first_meeting(&self, npc_id: &str) -> bool {
+        self.get_conversation(npc_id).is_none_or(|conversation| conversation.times_met == 0)
+    }
+
+    pub fn has_heard(&self, npc_id: &str, node_id: u32) -> bool {
+        self.get_conversation(npc_id).is_some_and(|conversation| conversation.heard_node_ids.contains(&node_id))
+    }
+
+    fn record_meeting(&mut self, npc_id: &str) {
+        self.npcs.entry(npc_id.to_string()).or_default().times_met += 1;
+    }
+
+    fn record_heard(&mut self, npc_id: &str, node_id: u32) {
+        self.npcs.entry(npc_id.to_string()).or_default().heard_node_ids.insert(node_id);
+    }
+}
+
+/// A response the player can give, marked when it leads to something the NPC has already told them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueResponse<'a> {
+    pub state: OptionState<'a>,
+    pub already_heard: bool,
+}
+
+/// Runs a conversation for the UI, which shows the current line, calls `advance` as the player clicks through
+/// and offers the responses once the lines run out. Dialogue choices go through the player's story tree
+/// like any other story choice, and the story picks up where it was when the conversation ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueRunner {
+    npc_id: String,
+    lines: Vec<DialogueLine>,
+    line_index: usize,
+    has_responses: bool,
+    // where the story was before the conversation started
+    return_node_id: Option<u32>,
+}
+
+impl DialogueRunner {
+    pub fn start(dialogue: &Dialogue, graph: &StoryGraph, story_tree: &mut PlayerStoryTree, world: &mut StoryWorld) -> Result<Self, DialogueError> {
+        let entry_node_id = match world.player.conversations.is_first_meeting(&dialogue.npc_id) {
+            true => dialogue.first_meeting_node_id,
+            false => dialogue.returning_node_id,
+        };
+
+        // render the greeting before anything changes so a broken template leaves the story and memory as they were
+        let node = graph.get_node(entry_node_id).ok_or(StoryGraphError::NodeNotFound(entry_node_id))?;
+        let lines = render_lines(node, story_tree, world)?;
+
+        let return_node_id = story_tree.current_node_id;
+        graph.start_at(entry_node_id, story_tree)?;
+        world.player.conversations.record_meeting(&dialogue.npc_id);
+
+        let mut runner = DialogueRunner {
+            npc_id: dialogue.npc_id.clone(),
+            lines: Vec::new(),
+            line_index: 0,
+            has_responses: false,
+            return_node_id,
+        };
+        runner.enter_node(node, lines, world);
+        Ok(runner)
+    }
+
+    fn enter_node(&mut self, node: &StoryNode, lines: Vec<DialogueLine>, world: &mut StoryWorld) {
+        self.lines = lines;
+        self.line_index = 0;
+        self.has_responses = !node.options.is_empty();
+        world.player.conversations.record_heard(&self.npc_id, node.id);
+    }
+
+    pub fn get_npc_id(&self) -> &str {
+        &self.npc_id
+    }
+
+    pub fn get_current_line(&self) -> Option<&DialogueLine> {
+        self.lines.get(self.line_index)
+    }
+
+    /// Moves on to the next line, returning false once every line of the node has been shown.
+    pub fn advance(&mut self) -> bool {
+        self.line_index = (self.line_index + 1).min(self.lines.len());
+        self.get_current_line().is_some()
+    }
+
+    pub fn is_waiting_for_response(&self) -> bool {
+        self.get_current_line().is_none() && self.has_responses
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.get_current_line().is_none() && !self.has_responses
+    }
+
+    pub fn get_responses<'a>(&self, graph: &'a StoryGraph, story_tree: &PlayerStoryTree, context: &StoryContext) -> Vec<DialogueResponse<'a>> {
+        if !self.is_waiting_for_response() {
+            return Vec::new();
+        }
+
+        graph.get_option_states(story_tree, context)
+            .into_iter()
+            .map(|state| DialogueResponse {
+                already_heard: context.player.conversations.has_heard(&self.npc_id, state.option.next_node_id),
+                state,
+            })
+            .collect()
+    }
+
+    pub fn respond(&mut self, graph: &StoryGraph, story_tree: &mut PlayerStoryTree, option_index: usize, world: &mut StoryWorld, rng: &mut SeededRng) -> Result<Vec<StoryOutcome>, DialogueError> {
+        if !self.is_waiting_for_response() {
+            return Err(DialogueError::NotWaitingForResponse);
+        }
+
+        let choice = graph.choose_option(story_tree, option_index, world, rng)?;
+        let lines = match render_lines(choice.next_node, story_tree, world) {
+            Ok(lines) => lines,
+            Err(error) => {
+                // the response is taken back so the story, the player and the runner all stay on the current node
+                story_tree.undo(world.player);
+                return Err(error);
+            },
+        };
+        self.enter_node(choice.next_node, lines, world);
+        Ok(choice.outcomes)
+    }
+
+    /// Ends the conversation and puts the story back where it was.
+    pub fn end(self, story_tree: &mut PlayerStoryTree) {
+        story_tree.jump_to(self.return_node_id);
+    }
+}
+
+/// Reads the node's lines as they stand now, so variables in them keep the values they had when they were said.
+fn render_lines(node: &StoryNode, story_tree: &PlayerStoryTree, world: &StoryWorld) -> Result<Vec<DialogueLine>, DialogueError> {
+    Ok(parse_dialogue_lines(&node.render_text(&world.get_context(), story_tree)?))
+}
+
+#[derive(Debug, Default)]
+pub struct DialogueRegistry {
+    dialogues: HashMap<String, Dialogue>,
+}
+
+impl DialogueRegistry {
+    pub fn new() -> Self {
+        DialogueRegistry {
+            dialogues: HashMap::new(),
+        }
+    }
+
+    pub fn load_from_json(&mut self, json: &str) -> Result<(), DialogueError> {
+        let dialogues: Vec<Dialogue> = serde_json::from_str(json).map_err(|error| DialogueError::InvalidData(error.to_string()))?;
+        for dialogue in dialogues {
+            self.register_dialogue(dialogue);
+        }
+        Ok(())
+    }
+
+    pub fn register_dialogue(&mut self, dialogue: Dialogue) {
+        self.dialogues.insert(dialogue.id.clone(), dialogue);
+    }
+
+    pub fn get_dialogue(&self, dialogue_id: &str) -> Result<&Dialogue, DialogueError> {
+        self.dialogues
+            .get(dialogue_id)
+            .ok_or_else(|| DialogueError::UnknownDialogue(dialogue_id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dialogue::conversation::{Dialogue, DialogueRunner};
+    use crate::dialogue::dialogue_line::{DialogueError, DialogueLine, Speaker, SpeakerRegistry};
+    use crate::factions::faction::FactionRegistry;
+    use crate::inventory::item::ItemRegistry;
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_effects::{StoryEffect, StoryWorld};
+    use crate::story_tree::story_graph::StoryGraph;
+    use crate::story_tree::story_history::replay_choices;
+    use crate::story_tree::story_management::{PlayerStoryTree, StoryNode, StoryOption};
+    use crate::story_tree::story_script::load_story;
+    use crate::utils::rng::SeededRng;
+
+    const BLACKSMITH: &str = "\
+== 100 Blacksmith Greeting
+@blacksmith [wary] Haven't seen you before{if player_name != \"\"}, {player_name}{/if}.
+Keep your hands off the blades.
+
+A hammer rings somewhere behind him.
+
++ Ask about the church -> 102
++ Leave -> 103
+
+== 101 Blacksmith Returning
+@blacksmith Back again?
+
++ Ask about the church -> 102
++ Leave -> 103
+
+== 102 The Church
+@blacksmith [angry] They took my son for the choir. He never came back.
+
++ Leave -> 103
+    ! set asked_about_the_church
+
+== 103 Farewell @end
+";
+
+    #[test]
+    fn test_dialogue_lines_and_portraits() {
+        let line = DialogueLine::parse("@blacksmith [wary] Haven't seen\nyou before.");
+        assert_eq!(line.speaker_id.as_deref(), Some("blacksmith"));
+        assert_eq!(line.mood.as_deref(), Some("wary"));
+        assert_eq!(line.text, "Haven't seen you before.");
+        assert_eq!(DialogueLine::parse("Rain on the roof."), DialogueLine::narration("Rain on the roof."));
+
+        let mut speakers = SpeakerRegistry::new();
+        speakers.load_from_json(r#"[{"id": "blacksmith", "name": "Oswin", "portraits": {"angry": "oswin_angry"}, "default_portrait": "oswin"}]"#).unwrap();
+        assert_eq!(speakers.get_presentation(&line), Some(("Oswin", Some("oswin"))));
+        assert_eq!(speakers.get_speaker("blacksmith").map(|speaker: &Speaker| speaker.get_portrait(Some("angry"))), Some(Some("oswin_angry")));
+        assert_eq!(speakers.get_presentation(&DialogueLine::narration("...")), None);
+    }
+
+    #[test]
+    fn test_conversation_remembers_the_player() {
+        let graph = load_story(BLACKSMITH).unwrap();
+        let dialogue = Dialogue { id: "blacksmith_talk".to_string(), npc_id: "blacksmith".to_string(), first_meeting_node_id: 100, returning_node_id: 101 };
+        let mut player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+        let mut rng = SeededRng::new(1);
+        let mut story_tree = PlayerStoryTree::new();
+        story_tree.current_node_id = Some(7);
+
+        player.name = "Agnes".to_string();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+
+        let mut runner = DialogueRunner::start(&dialogue, &graph, &mut story_tree, &mut world).unwrap();
+        assert_eq!(runner.get_current_line().unwrap().text, "Haven't seen you before, Agnes. Keep your hands off the blades.");
+        assert_eq!(runner.get_current_line().unwrap().mood.as_deref(), Some("wary"));
+        assert!(runner.advance());
+        assert_eq!(runner.get_current_line().unwrap().speaker_id, None);
+        assert!(!runner.advance());
+        assert!(runner.is_waiting_for_response());
+
+        assert_eq!(runner.get_responses(&graph, &story_tree, &world.get_context()).len(), 2);
+        runner.respond(&graph, &mut story_tree, 0, &mut world, &mut rng).unwrap();
+        assert_eq!(runner.respond(&graph, &mut story_tree, 0, &mut world, &mut rng), Err(DialogueError::NotWaitingForResponse));
+
+        runner.advance();
+        runner.respond(&graph, &mut story_tree, 0, &mut world, &mut rng).unwrap();
+        assert!(runner.is_finished());
+        assert!(story_tree.story_flags.contains("asked_about_the_church"));
+        runner.end(&mut story_tree);
+        assert_eq!(story_tree.current_node_id, Some(7));
+
+        // the second time round the blacksmith recognises the player and the church has already been discussed
+        let mut runner = DialogueRunner::start(&dialogue, &graph, &mut story_tree, &mut world).unwrap();
+        assert_eq!(runner.get_current_line().unwrap().text, "Back again?");
+        runner.advance();
+        let responses = runner.get_responses(&graph, &story_tree, &world.get_context());
+        assert!(responses[0].already_heard);
+        assert_eq!(world.player.conversations.get_conversation("blacksmith").unwrap().times_met, 2);
+    }
+
+    #[test]
+    fn test_broken_greeting_changes_nothing() {
+        let greeting = StoryNode::new(100, String::new(), "@blacksmith {if}Hello.".to_string(), vec![], true, false);
+        let graph = StoryGraph::from_nodes(vec![greeting]).unwrap();
+        let dialogue = Dialogue { id: "blacksmith_talk".to_string(), npc_id: "blacksmith".to_string(), first_meeting_node_id: 100, returning_node_id: 100 };
+        let mut player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+        let mut story_tree = PlayerStoryTree::new();
+        story_tree.current_node_id = Some(7);
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+
+        assert!(matches!(DialogueRunner::start(&dialogue, &graph, &mut story_tree, &mut world), Err(DialogueError::Text(_))));
+        assert_eq!(story_tree.current_node_id, Some(7));
+        assert!(world.player.conversations.is_first_meeting("blacksmith"));
+    }
+
+    #[test]
+    fn test_broken_response_changes_nothing() {
+        let pay_up = StoryOption::new(101, "Pay up").with_effects(vec![StoryEffect::SetFlag("paid".to_string())]);
+        let greeting = StoryNode::new(100, String::new(), "@blacksmith Well?".to_string(), vec![pay_up], false, false);
+        let thanks = StoryNode::new(101, String::new(), "@blacksmith {if}Thanks.".to_string(), vec![], true, false);
+        let graph = StoryGraph::from_nodes(vec![greeting, thanks]).unwrap();
+        let dialogue = Dialogue { id: "blacksmith_talk".to_string(), npc_id: "blacksmith".to_string(), first_meeting_node_id: 100, returning_node_id: 100 };
+        let mut player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+        let mut rng = SeededRng::new(1);
+        let mut story_tree = PlayerStoryTree::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+
+        let mut runner = DialogueRunner::start(&dialogue, &graph, &mut story_tree, &mut world).unwrap();
+        runner.advance();
+        assert!(matches!(runner.respond(&graph, &mut story_tree, 0, &mut world, &mut rng), Err(DialogueError::Text(_))));
+        assert_eq!(story_tree.current_node_id, Some(100));
+        assert!(!story_tree.story_flags.contains("paid"));
+        assert!(story_tree.get_history().is_empty());
+        assert!(runner.is_waiting_for_response());
+        assert!(!world.player.conversations.has_heard("blacksmith", 101));
+    }
+
+    #[test]
+    fn test_conversations_replay_and_rewind() {
+        let graph = load_story(&format!("== 1 Gate @start\n+ Enter -> 2\n\n== 2 Village\n+ Leave -> 3\n\n== 3 Road @end\n\n{BLACKSMITH}")).unwrap();
+        let dialogue = Dialogue { id: "blacksmith_talk".to_string(), npc_id: "blacksmith".to_string(), first_meeting_node_id: 100, returning_node_id: 101 };
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+        let mut rng = SeededRng::new(1);
+
+        let mut player = PlayerState::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut story_tree = PlayerStoryTree::new();
+        graph.start(&mut story_tree).unwrap();
+        graph.choose_option(&mut story_tree, 0, &mut world, &mut rng).unwrap();
+        let mut runner = DialogueRunner::start(&dialogue, &graph, &mut story_tree, &mut world).unwrap();
+        while runner.advance() {}
+        runner.respond(&graph, &mut story_tree, 1, &mut world, &mut rng).unwrap();
+        runner.end(&mut story_tree);
+        graph.choose_option(&mut story_tree, 0, &mut world, &mut rng).unwrap();
+
+        let choices = story_tree.get_recorded_choices();
+        assert_eq!(choices.iter().map(|choice| (choice.node_id, choice.after_jump)).collect::<Vec<_>>(), vec![(1, true), (100, true), (2, true)]);
+        // the village was reached from the gate, the conversation from nowhere on the path
+        assert_eq!(story_tree.get_story_node(2).unwrap().previous_node_id, Some(1));
+        assert_eq!(story_tree.get_story_node(100).unwrap().previous_node_id, None);
+
+        let mut replayed_player = PlayerState::new();
+        let mut replayed_world = StoryWorld { player: &mut replayed_player, factions: &factions, items: &items };
+        let mut replayed_tree = PlayerStoryTree::new();
+        replay_choices(&graph, &choices, &mut replayed_tree, &mut replayed_world, &mut SeededRng::new(1)).unwrap();
+        assert_eq!(replayed_tree.get_recorded_choices(), choices);
+        assert_eq!(replayed_tree.current_node_id, Some(3));
+
+        // rewinding to the choice after the conversation still remembers it followed a jump
+        story_tree.rewind_to(2, world.player).unwrap();
+        assert_eq!(story_tree.current_node_id, Some(2));
+        graph.choose_option(&mut story_tree, 0, &mut world, &mut rng).unwrap();
+        assert_eq!(story_tree.get_recorded_choices(), choices);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::story_tree::story_graph::StoryGraphError;
+use crate::story_tree::story_text::StoryTemplateError;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DialogueError {
+    #[error("Unknown dialogue: {0}")]
+    UnknownDialogue(String),
+    #[error("The conversation is not waiting for a response")]
+    NotWaitingForResponse,
+    #[error("Story error: {0}")]
+    Story(#[from] StoryGraphError),
+    #[error("Text error: {0}")]
+    Text(#[from] StoryTemplateError),
+    #[error("Invalid dialogue data: {0}")]
+    InvalidData(String),
+}
+
+/// One line of a conversation. Lines without a speaker are narration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DialogueLine {
+    pub speaker_id: Option<String>,
+    // picks the speaker's portrait, e.g. "angry" or "afraid"
+    pub mood: Option<String>,
+    pub text: String,
+}
+
+impl DialogueLine {
+    pub fn narration(text: &str) -> Self {
+        DialogueLine {
+            speaker_id: None,
+            mood: None,
+            text: text.to_string(),
+        }
+    }
+
+    /// Reads a paragraph of node text. `@blacksmith [wary] Who are you?` is the blacksmith speaking warily,
+    /// anything that doesn't start with `@` is narration.
+    pub fn parse(paragraph: &str) -> Self {
+        let text = paragraph.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+        let Some(attributed) = text.strip_prefix('@') else {
+            return DialogueLine::narration(&text);
+        };
+
+        let (speaker_id, rest) = attributed.split_once(' ').unwrap_or((attributed, ""));
+        let (mood, rest) = match rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some((mood, rest)) => (Some(mood.trim().to_string()), rest.trim_start()),
+            None => (None, rest),
+        };
+
+        DialogueLine {
+            speaker_id: Some(speaker_id.to_string()),
+            mood,
+            text: rest.to_string(),
+        }
+    }
+}
+
+/// Splits a story node's text into lines, one per paragraph.
+pub fn parse_dialogue_lines(text: &str) -> Vec<DialogueLine> {
+    text.split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .map(DialogueLine::parse)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Speaker {
+    pub id: String,
+    pub name: String,
+    // portrait image ids keyed by mood
+    #[serde(default)]
+    pub portraits: HashMap<String, String>,
+    pub default_portrait: Option<String>,
+}
+
+impl Speaker {
+    pub fn get_portrait(&self, mood: Option<&str>) -> Option<&str> {
+        mood.and_then(|mood| self.portraits.get(mood))
+            .or(self.default_portrait.as_ref())
+            .map(|portrait| portrait.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SpeakerRegistry {
+    speakers: HashMap<String, Speaker>,
+}
+
+impl SpeakerRegistry {
+    pub fn new() -> Self {
+        SpeakerRegistry {
+            speakers: HashMap::new(),
+        }
+    }
+
+    pub fn load_from_json(&mut self, json: &str) -> Result<(), DialogueError> {
+        let speakers: Vec<Speaker> = serde_json::from_str(json).map_err(|error| DialogueError::InvalidData(error.to_string()))?;
+        for speaker in speakers {
+            self.register_speaker(speaker);
+        }
+        Ok(())
+    }
+
+    pub fn register_speaker(&mut self, speaker: Speaker) {
+        self.speakers.insert(speaker.id.clone(), speaker);
+    }
+
+    pub fn get_speaker(&self, speaker_id: &str) -> Option<&Speaker> {
+        self.speakers.get(speaker_id)
+    }
+
+    /// The name and portrait to show with a line, None for narration or unknown speakers.
+    pub fn get_presentation(&self, line: &DialogueLine) -> Option<(&str, Option<&str>)> {
+        let speaker = self.get_speaker(line.speaker_id.as_deref()?)?;
+        Some((speaker.name.as_str(), speaker.get_portrait(line.mood.as_deref())))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod dialogue_line;
+pub mod conversation;
+pub mod barks;
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// A regional coin. Every coin is valued against a shared base unit so that
+/// item values can be expressed once and priced in whatever coin a vendor takes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Currency {
+    pub id: String,
+    pub name: String,
+    pub base_units_per_coin: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct CurrencyRegistry {
+    currencies: HashMap<String, Currency>,
+}
+
+impl CurrencyRegistry {
+    pub fn new() -> Self {
+        CurrencyRegistry {
+            currencies: HashMap::new(),
+        }
+    }
+
+    pub fn register_currency(&mut self, currency: Currency) {
+        self.currencies.insert(currency.id.clone(), currency);
+    }
+
+    pub fn get_currency(&self, currency_id: &str) -> Option<&Currency> {
+        self.currencies.get(currency_id)
+    }
+
+    /// Converts an amount of coins from one currency to another, rounding down.
+    pub fn convert(&self, amount: u32, from_currency_id: &str, to_currency_id: &str) -> Option<u32> {
+        let from = self.get_currency(from_currency_id)?;
+        let to = self.get_currency(to_currency_id)?;
+
+        let base_units = amount as u64 * from.base_units_per_coin.max(1) as u64;
+        Some((base_units / to.base_units_per_coin.max(1) as u64) as u32)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Purse {
+    coins: HashMap<String, u32>,
+}
+
+impl Purse {
+    pub fn new() -> Self {
+        Purse {
+            coins: HashMap::new(),
+        }
+    }
+
+    pub fn get_balance(&self, currency_id: &str) -> u32 {
+        self.coins
+            .get(currency_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn can_afford(&self, currency_id: &str, amount: u32) -> bool {
+        self.get_balance(currency_id) >= amount
+    }
+
+    pub fn deposit(&mut self, currency_id: &str, amount: u32) {
+        *self.coins.entry(currency_id.to_string()).or_insert(0) += amount;
+    }
+
+    /// Returns false and leaves the purse untouched if there are not enough coins.
+    pub fn withdraw(&mut self, currency_id: &str, amount: u32) -> bool {
+        if !self.can_afford(currency_id, amount) {
+            return false;
+        }
+
+        if let Some(balance) = self.coins.get_mut(currency_id) {
+            *balance -= amount;
+        }
+
+        true
+    }
+}
@@ -0,0 +1,2 @@
+pub mod currency;
+pub mod vendor;
\ No newline at end of file
@@ -0,0 +1,351 @@
+use thiserror::Error;
+use crate::economy::currency::{CurrencyRegistry, Purse};
+use crate::inventory::inventory_container::{InventoryContainer, InventoryError};
+use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+use crate::quests::quest::QuestEvent;
+use crate::world::game_clock::{GameClock, MINUTES_PER_HOUR};
+
+// every point of charisma above (or below) 10 moves prices by 2%, up to 20% either way
+const HAGGLE_PERCENT_PER_CHARISMA: i32 = 2;
+const MAX_HAGGLE_PERCENT: i32 = 20;
+// reputation ranges from -100 to 100 and moves prices by up to 15% either way
+const MAX_REPUTATION_PERCENT: i32 = 15;
+const REPUTATION_RANGE: i32 = 100;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TradeError {
+    #[error("Unknown item: {0}")]
+    UnknownItem(String),
+    #[error("Unknown currency: {0}")]
+    UnknownCurrency(String),
+    #[error("{0} can not be traded")]
+    NotTradeable(String),
+    #[error("Not enough {currency_id}: price is {price}, available {available}")]
+    InsufficientFunds { currency_id: String, price: u32, available: u32 },
+    #[error("Inventory error: {0}")]
+    Inventory(#[from] InventoryError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionRecord {
+    pub kind: TransactionKind,
+    pub item_id: String,
+    pub quantity: u32,
+    pub unit_price: u32,
+    pub total_price: u32,
+    pub currency_id: String,
+    // total game minutes on the clock when the trade happened
+    pub game_minute: u64,
+}
+
+impl TransactionRecord {
+    /// Bought items count towards quest collect objectives, pass this to `handle_quest_event`.
+    pub fn to_quest_event(&self) -> Option<QuestEvent> {
+        match self.kind {
+            TransactionKind::Buy => Some(QuestEvent::ItemCollected { item_id: self.item_id.clone(), quantity: self.quantity }),
+            TransactionKind::Sell => None,
+        }
+    }
+}
+
+/// What the vendor tops its stock back up to every restock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestockEntry {
+    pub item_id: String,
+    pub quantity: u32,
+}
+
+/// The side of a trade that is not the vendor, usually the player.
+pub struct Trader<'a> {
+    pub inventory: &'a mut InventoryContainer,
+    pub purse: &'a mut Purse,
+    pub charisma: i32,
+    // reputation with the vendor's faction, from -100 (hated) to 100 (exalted)
+    pub reputation: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Vendor {
+    pub name: String,
+    pub faction_id: Option<String>,
+    pub currency_id: String,
+    pub stock: InventoryContainer,
+    pub purse: Purse,
+    // percentages of an item's value the vendor sells at and buys back at
+    pub sell_markup_percent: u32,
+    pub buyback_percent: u32,
+    pub restock_entries: Vec<RestockEntry>,
+    pub restock_interval_hours: u64,
+    // total game minutes on the clock at the last restock, None until the vendor has been stocked
+    pub last_restock_minute: Option<u64>,
+    pub transaction_log: Vec<TransactionRecord>,
+}
+
+fn haggle_percent(charisma: i32) -> i32 {
+    ((charisma - 10) * HAGGLE_PERCENT_PER_CHARISMA).clamp(-MAX_HAGGLE_PERCENT, MAX_HAGGLE_PERCENT)
+}
+
+fn reputation_percent(reputation: i32) -> i32 {
+    reputation.clamp(-REPUTATION_RANGE, REPUTATION_RANGE) * MAX_REPUTATION_PERCENT / REPUTATION_RANGE
+}
+
+impl Vendor {
+    pub fn new(name: &str, faction_id: Option<String>, currency_id: &str, restock_entries: Vec<RestockEntry>, restock_interval_hours: u64) -> Self {
+        Vendor {
+            name: name.to_string(),
+            faction_id,
+            currency_id: currency_id.to_string(),
+            stock: InventoryContainer::new(None),
+            purse: Purse::new(),
+            sell_markup_percent: 150,
+            buyback_percent: 50,
+            restock_entries,
+            restock_interval_hours,
+            last_restock_minute: None,
+            transaction_log: Vec::new(),
+        }
+    }
+
+    /// Price per item in the vendor's currency when the trader buys from the vendor.
+    pub fn get_buy_price(&self, item: &ItemDefinition, currencies: &CurrencyRegistry, charisma: i32, reputation: i32) -> Result<u32, TradeError> {
+        let discount = haggle_percent(charisma) + reputation_percent(reputation);
+        let base_units = item.value as u64 * self.sell_markup_percent as u64 * (100 - discount) as u64 / 10_000;
+
+        // rounded up so a vendor never sells for less than it is worth in their coin
+        Ok(self.base_units_to_coins(base_units, currencies, true)?.max(1))
+    }
+
+    /// Price per item in the vendor's currency when the trader sells to the vendor.
+    /// Never more than the vendor would sell the same item for, so buying and selling back can't make money.
+    pub fn get_sell_price(&self, item: &ItemDefinition, currencies: &CurrencyRegistry, charisma: i32, reputation: i32) -> Result<u32, TradeError> {
+        let bonus = haggle_percent(charisma) + reputation_percent(reputation);
+        let base_units = item.value as u64 * self.buyback_percent as u64 * (100 + bonus) as u64 / 10_000;
+        let sell_price = self.base_units_to_coins(base_units, currencies, false)?;
+
+        Ok(sell_price.min(self.get_buy_price(item, currencies, charisma, reputation)?))
+    }
+
+    fn base_units_to_coins(&self, base_units: u64, currencies: &CurrencyRegistry, round_up: bool) -> Result<u32, TradeError> {
+        let currency = currencies
+            .get_currency(&self.currency_id)
+            .ok_or_else(|| TradeError::UnknownCurrency(self.currency_id.clone()))?;
+
+        let per_coin = currency.base_units_per_coin.max(1) as u64;
+        let coins = match round_up {
+            true => base_units.div_ceil(per_coin),
+            false => base_units / per_coin,
+        };
+
+        Ok(coins as u32)
+    }
+
+    fn get_tradeable_item<'a>(items: &'a ItemRegistry, item_id: &str) -> Result<&'a ItemDefinition, TradeError> {
+        let item = items
+            .get_item(item_id)
+            .ok_or_else(|| TradeError::UnknownItem(item_id.to_string()))?;
+
+        match item.category {
+            ItemCategory::Quest => Err(TradeError::NotTradeable(item_id.to_string())),
+            _ => Ok(item),
+        }
+    }
+
+    /// Every check happens before anything moves, so a failed purchase leaves both sides untouched.
+    pub fn buy(
+        &mut self,
+        trader: &mut Trader,
+        items: &ItemRegistry,
+        currencies: &CurrencyRegistry,
+        item_id: &str,
+        quantity: u32,
+        clock: &GameClock
+    ) -> Result<TransactionRecord, TradeError> {
+        let item = Self::get_tradeable_item(items, item_id)?;
+        let unit_price = self.get_buy_price(item, currencies, trader.charisma, trader.reputation)?;
+        let total_price = unit_price.saturating_mul(quantity);
+
+        let available = self.stock.get_item_count(item_id);
+        if available < quantity {
+            return Err(InventoryError::NotEnoughItems { item_id: item_id.to_string(), requested: quantity, available }.into());
+        }
+
+        if !trader.purse.can_afford(&self.currency_id, total_price) {
+            return Err(TradeError::InsufficientFunds {
+                currency_id: self.currency_id.clone(),
+                price: total_price,
+                available: trader.purse.get_balance(&self.currency_id),
+            });
+        }
+
+        if !trader.inventory.can_add_item(item, quantity) {
+            return Err(InventoryError::ContainerFull(item_id.to_string()).into());
+        }
+
+        self.stock.remove_item(item_id, quantity)?;
+        trader.inventory.add_item(item, quantity)?;
+        trader.purse.withdraw(&self.currency_id, total_price);
+        self.purse.deposit(&self.currency_id, total_price);
+
+        Ok(self.record_transaction(TransactionKind::Buy, item_id, quantity, unit_price, clock))
+    }
+
+    pub fn sell(
+        &mut self,
+        trader: &mut Trader,
+        items: &ItemRegistry,
+        currencies: &CurrencyRegistry,
+        item_id: &str,
+        quantity: u32,
+        clock: &GameClock
+    ) -> Result<TransactionRecord, TradeError> {
+        let item = Self::get_tradeable_item(items, item_id)?;
+        let unit_price = self.get_sell_price(item, currencies, trader.charisma, trader.reputation)?;
+        let total_price = unit_price.saturating_mul(quantity);
+
+        let available = trader.inventory.get_item_count(item_id);
+        if available < quantity {
+            return Err(InventoryError::NotEnoughItems { item_id: item_id.to_string(), requested: quantity, available }.into());
+        }
+
+        if !self.purse.can_afford(&self.currency_id, total_price) {
+            return Err(TradeError::InsufficientFunds {
+                currency_id: self.currency_id.clone(),
+                price: total_price,
+                available: self.purse.get_balance(&self.currency_id),
+            });
+        }
+
+        trader.inventory.remove_item(item_id, quantity)?;
+        self.stock.add_item(item, quantity)?;
+        self.purse.withdraw(&self.currency_id, total_price);
+        trader.purse.deposit(&self.currency_id, total_price);
+
+        Ok(self.record_transaction(TransactionKind::Sell, item_id, quantity, unit_price, clock))
+    }
+
+    fn record_transaction(&mut self, kind: TransactionKind, item_id: &str, quantity: u32, unit_price: u32, clock: &GameClock) -> TransactionRecord {
+        let record = TransactionRecord {
+            kind,
+            item_id: item_id.to_string(),
+            quantity,
+            unit_price,
+            total_price: unit_price.saturating_mul(quantity),
+            currency_id: self.currency_id.clone(),
+            game_minute: clock.get_total_minutes(),
+        };
+
+        self.transaction_log.push(record.clone());
+        record
+    }
+
+    /// Tops the stock back up to its restock entries the first time it's called and again
+    /// once enough game time has passed. Returns true if a restock happened.
+    pub fn update_stock(&mut self, items: &ItemRegistry, clock: &GameClock) -> bool {
+        let now = clock.get_total_minutes();
+        let interval = self.restock_interval_hours * MINUTES_PER_HOUR as u64;
+        if self.last_restock_minute.is_some_and(|last_restock_minute| now < last_restock_minute + interval) {
+            return false;
+        }
+
+        for entry in &self.restock_entries {
+            let Some(item) = items.get_item(&entry.item_id) else {
+                continue;
+            };
+
+            let missing = entry.quantity.saturating_sub(self.stock.get_item_count(&entry.item_id));
+            if missing > 0 {
+                // the vendor's stock has no slot limit, so this can't fail
+                let _ = self.stock.add_item(item, missing);
+            }
+        }
+
+        self.last_restock_minute = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::economy::currency::{Currency, CurrencyRegistry, Purse};
+    use crate::economy::vendor::{RestockEntry, Trader, TradeError, Vendor};
+    use crate::inventory::inventory_container::InventoryContainer;
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::world::game_clock::{GameClock, GameTime};
+
+    fn create_registries() -> (ItemRegistry, CurrencyRegistry) {
+        let mut items = ItemRegistry::new();
+        items.register_item(ItemDefinition::new("iron_ingot", "Iron Ingot", "A bar of iron.", ItemCategory::Material, 40, 20));
+
+        let mut currencies = CurrencyRegistry::new();
+        currencies.register_currency(Currency { id: String::from("crown"), name: String::from("Crown"), base_units_per_coin: 10 });
+        currencies.register_currency(Currency { id: String::from("mark"), name: String::from("Mark"), base_units_per_coin: 4 });
+
+        (items, currencies)
+    }
+
+    fn create_vendor(items: &ItemRegistry) -> Vendor {
+        let mut vendor = Vendor::new(
+            "Smith",
+            None,
+            "crown",
+            vec![RestockEntry { item_id: String::from("iron_ingot"), quantity: 5 }],
+            24
+        );
+        // a new vendor is stocked straight away
+        assert!(vendor.update_stock(items, &GameClock::new(1, GameTime::new(0, 0))));
+        vendor
+    }
+
+    #[test]
+    fn test_prices_follow_charisma_and_reputation() {
+        let (items, currencies) = create_registries();
+        let vendor = create_vendor(&items);
+        let ingot = items.get_item("iron_ingot").unwrap();
+
+        assert_eq!(vendor.get_buy_price(ingot, &currencies, 10, 0), Ok(6));
+        assert_eq!(vendor.get_buy_price(ingot, &currencies, 20, 100), Ok(4));
+        assert_eq!(vendor.get_buy_price(ingot, &currencies, 5, -100), Ok(8));
+        assert_eq!(vendor.get_sell_price(ingot, &currencies, 10, 0), Ok(2));
+        assert!(vendor.get_sell_price(ingot, &currencies, 30, 100).unwrap() <= vendor.get_buy_price(ingot, &currencies, 30, 100).unwrap());
+        assert_eq!(currencies.convert(10, "crown", "mark"), Some(25));
+    }
+
+    #[test]
+    fn test_failed_buy_moves_nothing() {
+        let (items, currencies) = create_registries();
+        let mut vendor = create_vendor(&items);
+        let mut inventory = InventoryContainer::new(Some(10));
+        let mut purse = Purse::new();
+        purse.deposit("crown", 10);
+        let mut trader = Trader { inventory: &mut inventory, purse: &mut purse, charisma: 10, reputation: 0 };
+        let mut clock = GameClock::new(1, GameTime::new(6, 0));
+
+        assert_eq!(
+            vendor.buy(&mut trader, &items, &currencies, "iron_ingot", 2, &clock),
+            Err(TradeError::InsufficientFunds { currency_id: String::from("crown"), price: 12, available: 10 })
+        );
+        assert_eq!(vendor.stock.get_item_count("iron_ingot"), 5);
+        assert_eq!(trader.inventory.get_item_count("iron_ingot"), 0);
+        assert_eq!(trader.purse.get_balance("crown"), 10);
+
+        let record = vendor.buy(&mut trader, &items, &currencies, "iron_ingot", 1, &clock).unwrap();
+        assert_eq!(record.game_minute, clock.get_total_minutes());
+        assert_eq!(vendor.stock.get_item_count("iron_ingot"), 4);
+        assert_eq!(trader.inventory.get_item_count("iron_ingot"), 1);
+        assert_eq!(trader.purse.get_balance("crown"), 4);
+        assert_eq!(vendor.purse.get_balance("crown"), 6);
+        assert_eq!(vendor.transaction_log.len(), 1);
+
+        clock.advance_minutes(16 * 60);
+        assert!(!vendor.update_stock(&items, &clock));
+        clock.advance_minutes(2 * 60);
+        assert!(vendor.update_stock(&items, &clock));
+        assert_eq!(vendor.stock.get_item_count("iron_ingot"), 5);
+    }
+}
@@ -39,4 +39,10 @@ impl SacrificialRobes {
             attribute_modifiers,
         }
     }
+}
+
+impl Default for SacrificialRobes {
+    fn default() -> Self {
+        SacrificialRobes::new()
+    }
 }
\ No newline at end of file
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
-use crate::actors::models::{ComputedAttributes, StatBlock};
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ComputedAttributes, StatBlock, StatsEnum};
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WeaponType {
     Axe,
     Sword,
@@ -14,7 +15,7 @@ pub enum WeaponType {
     Shield,
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EquipmentSlot {
     Head,
     Torso,
@@ -30,6 +31,47 @@ pub enum EquipmentSlot {
     OffHand(WeaponType),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WeaponStats {
+    pub min_damage: i32,
+    pub max_damage: i32,
+    pub scaling_stat: StatsEnum,
+    // how far away (in grid cells) a target can be hit from, 1 for melee weapons
+    pub reach: i32,
+}
+
+impl WeaponType {
+    pub fn get_scaling_stat(&self) -> StatsEnum {
+        match self {
+            WeaponType::Dagger | WeaponType::Bow => StatsEnum::Dexterity,
+            WeaponType::Staff => StatsEnum::Intelligence,
+            _ => StatsEnum::Strength,
+        }
+    }
+}
+
+impl WeaponStats {
+    pub fn new(weapon_type: &WeaponType, min_damage: i32, max_damage: i32) -> Self {
+        let reach = match weapon_type {
+            WeaponType::Bow => 6,
+            WeaponType::Polearm => 2,
+            _ => 1,
+        };
+
+        WeaponStats {
+            min_damage,
+            max_damage,
+            scaling_stat: weapon_type.get_scaling_stat(),
+            reach,
+        }
+    }
+
+    pub fn unarmed() -> Self {
+        Self::new(&WeaponType::Fist, 1, 3)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Equipment {
     pub slot: EquipmentSlot,
     pub name: String,
@@ -37,8 +79,11 @@ pub struct Equipment {
     pub required_stats: StatBlock,
     pub stat_modifiers: StatBlock,
     pub attribute_modifiers: ComputedAttributes,
+    // what the item hits with when held in the main hand, None for anything that isn't a weapon
+    pub weapon_stats: Option<WeaponStats>,
 }
 
+#[derive(Debug, Clone)]
 pub struct Weapon {
     pub slot: EquipmentSlot,
     pub name: String,
@@ -47,8 +92,12 @@ pub struct Weapon {
     pub stat_modifiers: StatBlock,
     pub attribute_modifiers: ComputedAttributes,
     pub weapon_type: WeaponType,
+    pub weapon_stats: WeaponStats,
 }
 
+// saved as a plain list since slots can't be used as JSON keys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Equipment>", into = "Vec<Equipment>")]
 pub struct EquipmentManager {
     pub equipment: HashMap<EquipmentSlot, Equipment>
 }
@@ -72,4 +121,35 @@ impl EquipmentManager {
     pub fn get_equipment(&self, slot: EquipmentSlot) -> Option<&Equipment> {
         self.equipment.get(&slot)
     }
-}
\ No newline at end of file
+
+    /// The stats of whatever is held in the main hand, or bare fists if nothing there is a weapon.
+    pub fn get_weapon_stats(&self) -> WeaponStats {
+        self.equipment
+            .iter()
+            .find(|(slot, _)| matches!(slot, EquipmentSlot::MainHand(_)))
+            .and_then(|(_, equipment)| equipment.weapon_stats)
+            .unwrap_or_else(WeaponStats::unarmed)
+    }
+}
+
+impl Default for EquipmentManager {
+    fn default() -> Self {
+        EquipmentManager::new()
+    }
+}
+
+impl From<Vec<Equipment>> for EquipmentManager {
+    fn from(equipment: Vec<Equipment>) -> Self {
+        let mut manager = EquipmentManager::new();
+        for item in equipment {
+            manager.equip(item);
+        }
+        manager
+    }
+}
+
+impl From<EquipmentManager> for Vec<Equipment> {
+    fn from(manager: EquipmentManager) -> Self {
+        manager.equipment.into_values().collect()
+    }
+}
@@ -1,5 +1,5 @@
 use crate::actors::models::{ComputedAttributes, StatBlock};
-use crate::equipment::equipment_manager::{EquipmentSlot, WeaponType};
+use crate::equipment::equipment_manager::{EquipmentSlot, WeaponStats, WeaponType};
 
 pub struct SacrificialDagger {
     pub slot: EquipmentSlot,
@@ -9,6 +9,7 @@ pub struct SacrificialDagger {
     pub stat_modifiers: StatBlock,
     pub attribute_modifiers: ComputedAttributes,
     pub weapon_type: WeaponType,
+    pub weapon_stats: WeaponStats,
 }
 
 impl SacrificialDagger {
@@ -28,6 +29,7 @@ impl SacrificialDagger {
                 madness_limit: 0,
             },
             weapon_type: WeaponType::Dagger,
+            weapon_stats: WeaponStats::new(&WeaponType::Dagger, 3, 7),
         }
     }
 
@@ -40,6 +42,13 @@ impl SacrificialDagger {
             stat_modifiers,
             attribute_modifiers,
             weapon_type: WeaponType::Dagger,
+            weapon_stats: WeaponStats::new(&WeaponType::Dagger, 3, 7),
         }
     }
+}
+
+impl Default for SacrificialDagger {
+    fn default() -> Self {
+        SacrificialDagger::new()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+// relationships and reputation both range from -100 (hated) to 100 (exalted)
+pub const MIN_STANDING: i32 = -100;
+pub const MAX_STANDING: i32 = 100;
+// at or below this two factions attack each other's members on sight
+const HOSTILE_RELATIONSHIP: i32 = -50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FactionKind {
+    Kingdom,
+    CityState,
+    Church,
+    Cult,
+    Bandits,
+    Guild,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Faction {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub kind: FactionKind,
+    // the player's reputation before they have done anything for or against the faction
+    pub starting_reputation: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FactionRelationship {
+    pub faction_a: String,
+    pub faction_b: String,
+    pub standing: i32,
+}
+
+/// Every faction and how they feel about each other. Relationships go both ways,
+/// factions that were never related are neutral (0) and a faction is always at 100 with itself.
+#[derive(Debug, Default)]
+pub struct FactionRegistry {
+    factions: HashMap<String, Faction>,
+    relationships: HashMap<(String, String), i32>,
+}
+
+impl FactionRegistry {
+    pub fn new() -> Self {
+        FactionRegistry {
+            factions: HashMap::new(),
+            relationships: HashMap::new(),
+        }
+    }
+
+    pub fn register_faction(&mut self, faction: Faction) {
+        self.factions.insert(faction.id.clone(), faction);
+    }
+
+    pub fn get_faction(&self, faction_id: &str) -> Option<&Faction> {
+        self.factions.get(faction_id)
+    }
+
+    fn get_key(faction_a: &str, faction_b: &str) -> (String, String) {
+        match faction_a <= faction_b {
+            true => (faction_a.to_string(), faction_b.to_string()),
+            false => (faction_b.to_string(), faction_a.to_string()),
+        }
+    }
+
+    pub fn set_relationship(&mut self, faction_a: &str, faction_b: &str, standing: i32) {
+        self.relationships.insert(Self::get_key(faction_a, faction_b), standing.clamp(MIN_STANDING, MAX_STANDING));
+    }
+
+    pub fn add_relationship(&mut self, relationship: FactionRelationship) {
+        self.set_relationship(&relationship.faction_a, &relationship.faction_b, relationship.standing);
+    }
+
+    pub fn get_relationship(&self, faction_a: &str, faction_b: &str) -> i32 {
+        if faction_a == faction_b {
+            return MAX_STANDING;
+        }
+
+        self.relationships
+            .get(&Self::get_key(faction_a, faction_b))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn are_hostile(&self, faction_a: &str, faction_b: &str) -> bool {
+        self.get_relationship(faction_a, faction_b) <= HOSTILE_RELATIONSHIP
+    }
+
+    /// Every other faction with a relationship to the given one, sorted by id so results stay stable.
+    pub fn get_related_factions(&self, faction_id: &str) -> Vec<(String, i32)> {
+        let mut related: Vec<(String, i32)> = self.relationships
+            .iter()
+            .filter_map(|((faction_a, faction_b), standing)| match faction_id {
+                id if id == faction_a => Some((faction_b.clone(), *standing)),
+                id if id == faction_b => Some((faction_a.clone(), *standing)),
+                _ => None,
+            })
+            .collect();
+
+        related.sort();
+        related
+    }
+}
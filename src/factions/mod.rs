@@ -0,0 +1,2 @@
+pub mod faction;
+pub mod reputation;
\ No newline at end of file
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::factions::faction::{FactionRegistry, MAX_STANDING, MIN_STANDING};
+
+// killing a faction's member costs this much reputation with it
+const KILL_REPUTATION_PENALTY: i32 = 5;
+// the share of a change that ripples out to related factions, scaled by how they feel about the faction
+const SPILLOVER_PERCENT: i32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ReputationTier {
+    Hated,
+    Hostile,
+    Unfriendly,
+    Neutral,
+    Friendly,
+    Honored,
+    Exalted,
+}
+
+impl ReputationTier {
+    pub fn from_reputation(reputation: i32) -> Self {
+        match reputation {
+            value if value <= -75 => ReputationTier::Hated,
+            value if value <= -40 => ReputationTier::Hostile,
+            value if value <= -10 => ReputationTier::Unfriendly,
+            value if value < 10 => ReputationTier::Neutral,
+            value if value < 40 => ReputationTier::Friendly,
+            value if value < 75 => ReputationTier::Honored,
+            _ => ReputationTier::Exalted,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ReputationSource {
+    // the story node the choice was made on
+    StoryChoice(u32),
+    Kill,
+    Quest(String),
+    // e.g. being seen casting forbidden magic
+    Notoriety,
+    // a change that rippled out from a change with a related faction
+    Spillover(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReputationChange {
+    pub faction_id: String,
+    pub amount: i32,
+    pub source: ReputationSource,
+}
+
+/// A minimum standing with a faction, e.g. to unlock a dialogue option or a vendor's wares.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReputationRequirement {
+    pub faction_id: String,
+    pub minimum_tier: ReputationTier,
+}
+
+/// The player's standing with every faction along with what changed it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerReputation {
+    reputations: HashMap<String, i32>,
+    history: Vec<ReputationChange>,
+}
+
+impl PlayerReputation {
+    pub fn new() -> Self {
+        PlayerReputation {
+            reputations: HashMap::new(),
+            history: Vec::new(),
+        }
+    }
+
+    pub fn get_reputation(&self, faction_id: &str, factions: &FactionRegistry) -> i32 {
+        self.reputations
+            .get(faction_id)
+            .copied()
+            .unwrap_or_else(|| factions.get_faction(faction_id).map_or(0, |faction| faction.starting_reputation))
+    }
+
+    /// The reputation a vendor or NPC of the given faction sees, 0 for anyone without a faction.
+    pub fn get_reputation_with(&self, faction_id: Option<&str>, factions: &FactionRegistry) -> i32 {
+        faction_id.map_or(0, |faction_id| self.get_reputation(faction_id, factions))
+    }
+
+    pub fn get_tier(&self, faction_id: &str, factions: &FactionRegistry) -> ReputationTier {
+        ReputationTier::from_reputation(self.get_reputation(faction_id, factions))
+    }
+
+    pub fn meets_requirement(&self, requirement: &ReputationRequirement, factions: &FactionRegistry) -> bool {
+        self.get_tier(&requirement.faction_id, factions) >= requirement.minimum_tier
+    }
+
+    /// Whether members of the faction attack the player on sight.
+    pub fn is_hostile(&self, faction_id: &str, factions: &FactionRegistry) -> bool {
+        self.get_tier(faction_id, factions) <= ReputationTier::Hostile
+    }
+
+    pub fn get_history(&self) -> &[ReputationChange] {
+        &self.history
+    }
+
+    /// Changes the reputation with a faction and ripples part of the change out to related factions:
+    /// helping the church pleases its allies and angers the cult. Returns every change that was made,
+    /// with the amounts actually applied once reputations are kept within their limits.
+    pub fn change_reputation(&mut self, faction_id: &str, amount: i32, source: ReputationSource, factions: &FactionRegistry) -> Vec<ReputationChange> {
+        let mut changes = vec![ReputationChange { faction_id: faction_id.to_string(), amount, source }];
+
+        for (related_id, standing) in factions.get_related_factions(faction_id) {
+            let spillover = amount * standing * SPILLOVER_PERCENT / (MAX_STANDING * 100);
+            if spillover != 0 {
+                changes.push(ReputationChange { faction_id: related_id, amount: spillover, source: ReputationSource::Spillover(faction_id.to_string()) });
+            }
+        }
+
+        for change in changes.iter_mut() {
+            let reputation = self.get_reputation(&change.faction_id, factions);
+            let updated = (reputation + change.amount).clamp(MIN_STANDING, MAX_STANDING);
+            self.reputations.insert(change.faction_id.clone(), updated);
+            change.amount = updated - reputation;
+        }
+
+        self.history.extend(changes.iter().cloned());
+        changes
+    }
+
+    pub fn record_kill(&mut self, victim_faction_id: &str, factions: &FactionRegistry) -> Vec<ReputationChange> {
+        self.change_reputation(victim_faction_id, -KILL_REPUTATION_PENALTY, ReputationSource::Kill, factions)
+    }
+
+    pub fn record_story_choice(&mut self, node_id: u32, faction_id: &str, amount: i32, factions: &FactionRegistry) -> Vec<ReputationChange> {
+        self.change_reputation(faction_id, amount, ReputationSource::StoryChoice(node_id), factions)
+    }
+
+    pub fn record_quest(&mut self, quest_id: &str, faction_id: &str, amount: i32, factions: &FactionRegistry) -> Vec<ReputationChange> {
+        self.change_reputation(faction_id, amount, ReputationSource::Quest(quest_id.to_string()), factions)
+    }
+
+    /// Notoriety (e.g. from forbidden magic) counts against the faction that hunts it.
+    pub fn record_notoriety(&mut self, faction_id: &str, notoriety: i32, factions: &FactionRegistry) -> Vec<ReputationChange> {
+        self.change_reputation(faction_id, -notoriety, ReputationSource::Notoriety, factions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::factions::faction::{Faction, FactionKind, FactionRegistry};
+    use crate::factions::reputation::{PlayerReputation, ReputationRequirement, ReputationTier};
+
+    fn create_factions() -> FactionRegistry {
+        let faction = |id: &str, kind: FactionKind, starting_reputation: i32| Faction {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            kind,
+            starting_reputation,
+        };
+
+        let mut factions = FactionRegistry::new();
+        factions.register_faction(faction("church", FactionKind::Church, 10));
+        factions.register_faction(faction("cult", FactionKind::Cult, -50));
+        factions.register_faction(faction("kingdom_north", FactionKind::Kingdom, 0));
+        factions.set_relationship("church", "cult", -100);
+        factions.set_relationship("kingdom_north", "church", 60);
+        factions
+    }
+
+    #[test]
+    fn test_relationships_go_both_ways() {
+        let factions = create_factions();
+
+        assert_eq!(factions.get_relationship("cult", "church"), -100);
+        assert_eq!(factions.get_relationship("church", "church"), 100);
+        assert_eq!(factions.get_relationship("cult", "kingdom_north"), 0);
+        assert!(factions.are_hostile("church", "cult"));
+        assert!(!factions.are_hostile("church", "kingdom_north"));
+    }
+
+    #[test]
+    fn test_reputation_changes_spill_over() {
+        let factions = create_factions();
+        let mut reputation = PlayerReputation::new();
+
+        assert_eq!(reputation.get_tier("church", &factions), ReputationTier::Friendly);
+        assert!(reputation.is_hostile("cult", &factions));
+
+        let changes = reputation.record_quest("burn_the_grove", "church", 40, &factions);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(reputation.get_reputation("church", &factions), 50);
+        assert_eq!(reputation.get_reputation("cult", &factions), -70);
+        assert_eq!(reputation.get_reputation("kingdom_north", &factions), 12);
+
+        reputation.record_kill("cult", &factions);
+        assert_eq!(reputation.get_tier("cult", &factions), ReputationTier::Hated);
+
+        // the church's enemies don't mind a heretic
+        reputation.record_notoriety("church", 20, &factions);
+        assert_eq!(reputation.get_reputation("church", &factions), 32);
+        assert_eq!(reputation.get_tier("cult", &factions), ReputationTier::Hostile);
+        assert!(reputation.meets_requirement(&ReputationRequirement { faction_id: "church".to_string(), minimum_tier: ReputationTier::Friendly }, &factions));
+        assert!(!reputation.meets_requirement(&ReputationRequirement { faction_id: "church".to_string(), minimum_tier: ReputationTier::Honored }, &factions));
+        assert_eq!(reputation.get_reputation_with(None, &factions), 0);
+    }
+
+    #[test]
+    fn test_history_records_what_was_applied() {
+        let factions = create_factions();
+        let mut reputation = PlayerReputation::new();
+
+        // the cult starts at -50 and the church at 10 (12 once the kill spills over), neither can pass 100 either way
+        let changes = reputation.record_kill("cult", &factions);
+        assert_eq!(changes[0].amount, -5);
+        let changes = reputation.record_story_choice(4, "church", 100, &factions);
+        assert_eq!(changes[0].amount, 88);
+        assert_eq!(changes[1].faction_id, "cult");
+        assert_eq!(changes[1].amount, -45);
+        assert_eq!(reputation.get_reputation("cult", &factions), -100);
+
+        let total: i32 = reputation.get_history().iter().filter(|change| change.faction_id == "cult").map(|change| change.amount).sum();
+        assert_eq!(-50 + total, reputation.get_reputation("cult", &factions));
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+use crate::status_effects::active_effects::ApplyStatusResult;
+use crate::status_effects::status_effect::{StackingRule, StatusDuration, StatusEffectDefinition, StatusStatModifier};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsumableEffect {
+    RestoreResource { pool: ResourcePoolEnum, amount: i32 },
+    StatBuff { stat: StatsEnum, amount: i32, duration_turns: u32 },
+    // negative amounts cure madness, positive amounts add to it
+    ModifyMadness { amount: i32 },
+    TeachSkill { skill_id: String },
+    TriggerStoryFlag { flag: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UseRestriction {
+    Anytime,
+    CombatOnly,
+    OutOfCombatOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsumableDefinition {
+    pub effects: Vec<ConsumableEffect>,
+    pub cooldown_turns: u32,
+    pub restriction: UseRestriction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemUseOutcome {
+    ResourceChanged { pool: ResourcePoolEnum, amount: i32 },
+    StatBuffApplied { stat: StatsEnum, amount: i32, duration_turns: u32 },
+    SkillLearned(String),
+    SkillAlreadyKnown(String),
+    // story flags live outside of the target, so the caller is responsible for setting them
+    StoryFlagTriggered(String),
+}
+
+/// Anything a consumable can be used on.
+pub trait ConsumableTarget: ResourcePoolValues {
+    fn apply_status_effect(&mut self, definition: &StatusEffectDefinition) -> ApplyStatusResult;
+    /// Returns false if the skill was already known.
+    fn learn_skill(&mut self, skill_id: &str) -> bool;
+}
+
+impl ConsumableEffect {
+    pub fn apply<T: ConsumableTarget>(&self, source_item_id: &str, target: &mut T) -> ItemUseOutcome {
+        match self {
+            ConsumableEffect::RestoreResource { pool, amount } => {
+                let applied = target.modify_resource(*pool, *amount);
+                ItemUseOutcome::ResourceChanged { pool: *pool, amount: applied }
+            },
+            ConsumableEffect::StatBuff { stat, amount, duration_turns } => {
+                target.apply_status_effect(&create_stat_buff(source_item_id, *stat, *amount, *duration_turns));
+                ItemUseOutcome::StatBuffApplied { stat: *stat, amount: *amount, duration_turns: *duration_turns }
+            },
+            ConsumableEffect::ModifyMadness { amount } => {
+                let applied = target.modify_resource(ResourcePoolEnum::Madness, *amount);
+                ItemUseOutcome::ResourceChanged { pool: ResourcePoolEnum::Madness, amount: applied }
+            },
+            ConsumableEffect::TeachSkill { skill_id } => {
+                match target.learn_skill(skill_id) {
+                    true => ItemUseOutcome::SkillLearned(skill_id.clone()),
+                    false => ItemUseOutcome::SkillAlreadyKnown(skill_id.clone()),
+                }
+            },
+            ConsumableEffect::TriggerStoryFlag { flag } => ItemUseOutcome::StoryFlagTriggered(flag.clone()),
+        }
+    }
+}
+
+/// A stat buff from an item is an ordinary status effect, so it shows up wherever effects are read.
+/// Using the same item again refreshes the buff instead of stacking it.
+fn create_stat_buff(source_item_id: &str, stat: StatsEnum, amount: i32, duration_turns: u32) -> StatusEffectDefinition {
+    StatusEffectDefinition {
+        id: format!("{}_{:?}", source_item_id, stat).to_lowercase(),
+        name: format!("{:?} {:+}", stat, amount),
+        is_harmful: amount < 0,
+        duration: StatusDuration::Turns(duration_turns),
+        stacking: StackingRule::Refresh,
+        tick_changes: Vec::new(),
+        stat_modifiers: vec![StatusStatModifier { stat, amount }],
+        attribute_modifiers: Vec::new(),
+        grants_immunity_to: Vec::new(),
+    }
+}
+
+impl ConsumableDefinition {
+    pub fn is_usable(&self, in_combat: bool) -> bool {
+        match self.restriction {
+            UseRestriction::Anytime => true,
+            UseRestriction::CombatOnly => in_combat,
+            UseRestriction::OutOfCombatOnly => !in_combat,
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::inventory::consumables::{ConsumableTarget, ItemUseOutcome, UseRestriction};
+use crate::inventory::item::{ItemDefinition, ItemRegistry};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InventoryError {
+    #[error("Unknown item: {0}")]
+    UnknownItem(String),
+    #[error("Not enough {item_id}: requested {requested}, available {available}")]
+    NotEnoughItems { item_id: String, requested: u32, available: u32 },
+    #[error("Not enough space for {0}")]
+    ContainerFull(String),
+    #[error("Item can not be used: {0}")]
+    NotConsumable(String),
+    #[error("{item_id} is on cooldown for {remaining_turns} more turns")]
+    OnCooldown { item_id: String, remaining_turns: u32 },
+    #[error("{0} can not be used in combat")]
+    NotUsableInCombat(String),
+    #[error("{0} can only be used in combat")]
+    OnlyUsableInCombat(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item_id: String,
+    pub quantity: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryContainer {
+    stacks: Vec<ItemStack>,
+    max_slots: Option<usize>,
+    cooldowns: HashMap<String, u32>,
+}
+
+impl InventoryContainer {
+    pub fn new(max_slots: Option<usize>) -> Self {
+        InventoryContainer {
+            stacks: Vec::new(),
+            max_slots,
+            cooldowns: HashMap::new(),
+        }
+    }
+
+    pub fn get_stacks(&self) -> &[ItemStack] {
+        &self.stacks
+    }
+
+    pub fn get_item_count(&self, item_id: &str) -> u32 {
+        self.stacks
+            .iter()
+            .filter(|stack| stack.item_id == item_id)
+            .map(|stack| stack.quantity)
+            .sum()
+    }
+
+    pub fn has_item(&self, item_id: &str, quantity: u32) -> bool {
+        self.get_item_count(item_id) >= quantity
+    }
+
+    /// Checks whether `quantity` of the item fits, topping up existing stacks before opening new slots.
+    pub fn can_add_item(&self, item: &ItemDefinition, quantity: u32) -> bool {
+        let Some(max_slots) = self.max_slots else {
+            return true;
+        };
+
+        let free_in_stacks: u32 = self.stacks
+            .iter()
+            .filter(|stack| stack.item_id == item.id)
+            .map(|stack| item.max_stack.saturating_sub(stack.quantity))
+            .sum();
+
+        let overflow = quantity.saturating_sub(free_in_stacks);
+        let slots_needed = overflow.div_ceil(item.max_stack) as usize;
+
+        self.stacks.len() + slots_needed <= max_slots
+    }
+
+    pub fn add_item(&mut self, item: &ItemDefinition, quantity: u32) -> Result<(), InventoryError> {
+        if !self.can_add_item(item, quantity) {
+            return Err(InventoryError::ContainerFull(item.id.clone()));
+        }
+
+        let mut remaining = quantity;
+
+        for stack in self.stacks.iter_mut().filter(|stack| stack.item_id == item.id) {
+            let added = remaining.min(item.max_stack.saturating_sub(stack.quantity));
+            stack.quantity += added;
+            remaining -= added;
+        }
+
+        while remaining > 0 {
+            let added = remaining.min(item.max_stack);
+            self.stacks.push(ItemStack {
+                item_id: item.id.clone(),
+                quantity: added,
+            });
+            remaining -= added;
+        }
+
+        Ok(())
+    }
+
+    /// Removes from the last matching stacks first, leaving nothing changed if there are not enough.
+    pub fn remove_item(&mut self, item_id: &str, quantity: u32) -> Result<(), InventoryError> {
+        let available = self.get_item_count(item_id);
+        if available < quantity {
+            return Err(InventoryError::NotEnoughItems {
+                item_id: item_id.to_string(),
+                requested: quantity,
+                available,
+            });
+        }
+
+        let mut remaining = quantity;
+
+        for stack in self.stacks.iter_mut().rev().filter(|stack| stack.item_id == item_id) {
+            let removed = remaining.min(stack.quantity);
+            stack.quantity -= removed;
+            remaining -= removed;
+        }
+
+        self.stacks.retain(|stack| stack.quantity > 0);
+
+        Ok(())
+    }
+
+    pub fn get_cooldown(&self, item_id: &str) -> u32 {
+        self.cooldowns
+            .get(item_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn tick_cooldowns(&mut self) {
+        self.cooldowns
+            .values_mut()
+            .for_each(|remaining| *remaining = remaining.saturating_sub(1));
+
+        self.cooldowns.retain(|_, remaining| *remaining > 0);
+    }
+
+    /// Uses one of the given consumable on the target, applying its effects in order,
+    /// removing it from the stack and starting its cooldown.
+    pub fn use_item<T: ConsumableTarget>(
+        &mut self,
+        registry: &ItemRegistry,
+        item_id: &str,
+        target: &mut T,
+        in_combat: bool
+    ) -> Result<Vec<ItemUseOutcome>, InventoryError> {
+        let item = registry
+            .get_item(item_id)
+            .ok_or_else(|| InventoryError::UnknownItem(item_id.to_string()))?;
+
+        let consumable = item
+            .get_consumable()
+            .ok_or_else(|| InventoryError::NotConsumable(item_id.to_string()))?;
+
+        if !self.has_item(item_id, 1) {
+            return Err(InventoryError::NotEnoughItems {
+                item_id: item_id.to_string(),
+                requested: 1,
+                available: 0,
+            });
+        }
+
+        let remaining_turns = self.get_cooldown(item_id);
+        if remaining_turns > 0 {
+            return Err(InventoryError::OnCooldown {
+                item_id: item_id.to_string(),
+                remaining_turns,
+            });
+        }
+
+        if !consumable.is_usable(in_combat) {
+            return match consumable.restriction {
+                UseRestriction::CombatOnly => Err(InventoryError::OnlyUsableInCombat(item_id.to_string())),
+                _ => Err(InventoryError::NotUsableInCombat(item_id.to_string())),
+            };
+        }
+
+        self.remove_item(item_id, 1)?;
+
+        if consumable.cooldown_turns > 0 {
+            self.cooldowns.insert(item_id.to_string(), consumable.cooldown_turns);
+        }
+
+        Ok(consumable.effects
+            .iter()
+            .map(|effect| effect.apply(item_id, target))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+    use crate::inventory::consumables::{ConsumableDefinition, ConsumableEffect, ItemUseOutcome, UseRestriction};
+    use crate::inventory::inventory_container::{InventoryContainer, InventoryError};
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::state_management::player_state::PlayerState;
+
+    fn create_registry() -> ItemRegistry {
+        let mut registry = ItemRegistry::new();
+        registry.register_item(ItemDefinition::new(
+            "healing_draught",
+            "Healing Draught",
+            "A bitter red tonic.",
+            ItemCategory::Consumable(ConsumableDefinition {
+                effects: vec![ConsumableEffect::RestoreResource { pool: ResourcePoolEnum::Health, amount: 50 }],
+                cooldown_turns: 2,
+                restriction: UseRestriction::Anytime,
+            }),
+            10,
+            5,
+        ));
+        registry.register_item(ItemDefinition::new(
+            "forbidden_tome",
+            "Forbidden Tome",
+            "The pages are warm to the touch.",
+            ItemCategory::Consumable(ConsumableDefinition {
+                effects: vec![
+                    ConsumableEffect::TeachSkill { skill_id: String::from("whispered_hex") },
+                    ConsumableEffect::ModifyMadness { amount: 5 },
+                    ConsumableEffect::StatBuff { stat: StatsEnum::Intelligence, amount: 2, duration_turns: 3 },
+                ],
+                cooldown_turns: 0,
+                restriction: UseRestriction::OutOfCombatOnly,
+            }),
+            100,
+            1,
+        ));
+        registry
+    }
+
+    #[test]
+    fn test_add_item_respects_slots() {
+        let registry = create_registry();
+        let draught = registry.get_item("healing_draught").unwrap();
+        let mut container = InventoryContainer::new(Some(2));
+
+        container.add_item(draught, 7).unwrap();
+        assert_eq!(container.get_stacks().len(), 2);
+        assert_eq!(container.get_item_count("healing_draught"), 7);
+
+        assert_eq!(container.add_item(draught, 4), Err(InventoryError::ContainerFull(String::from("healing_draught"))));
+        assert_eq!(container.get_item_count("healing_draught"), 7);
+    }
+
+    #[test]
+    fn test_use_item_applies_effects_and_cooldown() {
+        let registry = create_registry();
+        let mut player = PlayerState::new();
+        player.inventory.add_item(registry.get_item("healing_draught").unwrap(), 2).unwrap();
+        player.set_resource(ResourcePoolEnum::Health, 10);
+
+        let outcome = player.use_item(&registry, "healing_draught", true).unwrap();
+        assert_eq!(outcome, vec![ItemUseOutcome::ResourceChanged { pool: ResourcePoolEnum::Health, amount: 50 }]);
+        assert_eq!(player.get_resource(ResourcePoolEnum::Health), 60);
+        assert_eq!(player.inventory.get_item_count("healing_draught"), 1);
+
+        assert!(matches!(player.use_item(&registry, "healing_draught", true), Err(InventoryError::OnCooldown { .. })));
+
+        player.end_turn();
+        player.end_turn();
+        player.use_item(&registry, "healing_draught", true).unwrap();
+        assert_eq!(player.inventory.get_item_count("healing_draught"), 0);
+    }
+
+    #[test]
+    fn test_use_item_combat_restriction() {
+        let registry = create_registry();
+        let mut player = PlayerState::new();
+        player.inventory.add_item(registry.get_item("forbidden_tome").unwrap(), 1).unwrap();
+
+        assert_eq!(
+            player.use_item(&registry, "forbidden_tome", true),
+            Err(InventoryError::NotUsableInCombat(String::from("forbidden_tome")))
+        );
+        assert_eq!(player.inventory.get_item_count("forbidden_tome"), 1);
+
+        player.use_item(&registry, "forbidden_tome", false).unwrap();
+        assert!(player.known_skills.contains("whispered_hex"));
+        assert_eq!(player.get_resource(ResourcePoolEnum::Madness), 5);
+        assert_eq!(player.get_effective_stat(StatsEnum::Intelligence), 12);
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::inventory::consumables::ConsumableDefinition;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemCategory {
+    Consumable(ConsumableDefinition),
+    Equipment,
+    Material,
+    Tool,
+    Quest,
+    Miscellaneous,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub category: ItemCategory,
+    pub value: u32,
+    pub max_stack: u32,
+}
+
+impl ItemDefinition {
+    pub fn new(id: &str, name: &str, description: &str, category: ItemCategory, value: u32, max_stack: u32) -> Self {
+        ItemDefinition {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            category,
+            value,
+            max_stack: max_stack.max(1),
+        }
+    }
+
+    pub fn get_consumable(&self) -> Option<&ConsumableDefinition> {
+        match &self.category {
+            ItemCategory::Consumable(consumable) => Some(consumable),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ItemRegistry {
+    items: HashMap<String, ItemDefinition>,
+}
+
+impl ItemRegistry {
+    pub fn new() -> Self {
+        ItemRegistry {
+            items: HashMap::new(),
+        }
+    }
+
+    pub fn register_item(&mut self, item: ItemDefinition) {
+        self.items.insert(item.id.clone(), item);
+    }
+
+    pub fn get_item(&self, item_id: &str) -> Option<&ItemDefinition> {
+        self.items.get(item_id)
+    }
+}
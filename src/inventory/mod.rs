@@ -0,0 +1,3 @@
+pub mod item;
+pub mod inventory_container;
+pub mod consumables;
\ No newline at end of file
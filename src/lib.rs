@@ -0,0 +1,19 @@
+pub mod textures;
+pub mod actors;
+pub mod equipment;
+pub mod story_tree;
+pub mod dialogue;
+pub mod quests;
+pub mod inventory;
+pub mod state_management;
+pub mod economy;
+pub mod loot;
+pub mod crafting;
+pub mod status_effects;
+pub mod combat;
+pub mod abilities;
+pub mod ai;
+pub mod world;
+pub mod factions;
+pub mod party;
+pub mod utils;
@@ -0,0 +1,171 @@
+use crate::inventory::inventory_container::{InventoryContainer, InventoryError, ItemStack};
+use crate::inventory::item::ItemRegistry;
+use crate::loot::loot_table::{LootContext, LootError, LootTableRegistry};
+use crate::utils::rng::SeededRng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LootContainerKind {
+    Chest,
+    Corpse,
+    Shrine,
+}
+
+/// Something in the world the player can loot from. The loot table is only rolled
+/// the first time the container is opened, after that the contents are whatever is left.
+#[derive(Debug, Clone)]
+pub struct LootContainer {
+    pub name: String,
+    pub kind: LootContainerKind,
+    pub loot_table_id: Option<String>,
+    pub contents: InventoryContainer,
+    // how many times the loot table is rolled, e.g. more for an elite NPC's corpse
+    pub rolls: u32,
+    is_rolled: bool,
+}
+
+impl LootContainer {
+    pub fn new(name: &str, kind: LootContainerKind, loot_table_id: Option<String>) -> Self {
+        LootContainer {
+            name: name.to_string(),
+            kind,
+            loot_table_id,
+            contents: InventoryContainer::new(None),
+            rolls: 1,
+            is_rolled: false,
+        }
+    }
+
+    pub fn with_rolls(mut self, rolls: u32) -> Self {
+        self.rolls = rolls;
+        self
+    }
+
+    /// The corpse left behind by a defeated NPC. Pass the NPC's level in the `LootContext` when it's opened.
+    pub fn new_corpse(npc_name: &str, loot_table_id: Option<String>) -> Self {
+        Self::new(&format!("Corpse of {npc_name}"), LootContainerKind::Corpse, loot_table_id)
+    }
+
+    pub fn is_rolled(&self) -> bool {
+        self.is_rolled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.get_stacks().is_empty()
+    }
+
+    /// Rolls the loot table the first time, then returns the contents. Nothing is added if any drop
+    /// turns out to be an unknown item, so a failed open can be retried.
+    pub fn open(
+        &mut self,
+        tables: &LootTableRegistry,
+        items: &ItemRegistry,
+        context: &LootContext,
+        rng: &mut SeededRng
+    ) -> Result<&[ItemStack], LootError> {
+        if !self.is_rolled {
+            if let Some(table_id) = &self.loot_table_id {
+                let mut stacks = Vec::new();
+                for _ in 0..self.rolls {
+                    stacks.extend(tables.roll(table_id, context, rng)?);
+                }
+
+                let drops = stacks
+                    .into_iter()
+                    .map(|stack| items
+                        .get_item(&stack.item_id)
+                        .map(|item| (item, stack.quantity))
+                        .ok_or(LootError::UnknownItem(stack.item_id)))
+                    .collect::<Result<Vec<_>, LootError>>()?;
+
+                for (item, quantity) in drops {
+                    // contents have no slot limit
+                    let _ = self.contents.add_item(item, quantity);
+                }
+            }
+
+            self.is_rolled = true;
+        }
+
+        Ok(self.contents.get_stacks())
+    }
+
+    /// Returns the stack that was taken.
+    pub fn take_item(&mut self, items: &ItemRegistry, item_id: &str, quantity: u32, into: &mut InventoryContainer) -> Result<ItemStack, InventoryError> {
+        let item = items
+            .get_item(item_id)
+            .ok_or_else(|| InventoryError::UnknownItem(item_id.to_string()))?;
+
+        let available = self.contents.get_item_count(item_id);
+        if available < quantity {
+            return Err(InventoryError::NotEnoughItems { item_id: item_id.to_string(), requested: quantity, available });
+        }
+
+        into.add_item(item, quantity)?;
+        self.contents.remove_item(item_id, quantity)?;
+        Ok(ItemStack { item_id: item_id.to_string(), quantity })
+    }
+
+    /// Moves everything that fits into the given inventory, leaving the rest behind.
+    /// Returns the stacks that were taken.
+    pub fn take_all(&mut self, items: &ItemRegistry, into: &mut InventoryContainer) -> Vec<ItemStack> {
+        let mut taken = Vec::new();
+
+        for stack in self.contents.get_stacks().to_vec() {
+            let Some(item) = items.get_item(&stack.item_id) else {
+                continue;
+            };
+
+            let fitting = (1..=stack.quantity)
+                .rev()
+                .find(|quantity| into.can_add_item(item, *quantity))
+                .unwrap_or(0);
+
+            if fitting > 0 && into.add_item(item, fitting).is_ok() && self.contents.remove_item(&stack.item_id, fitting).is_ok() {
+                taken.push(ItemStack { item_id: stack.item_id.clone(), quantity: fitting });
+            }
+        }
+
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::loot::loot_container::{LootContainer, LootContainerKind};
+    use crate::loot::loot_table::{LootContext, LootDrop, LootEntry, LootError, LootTable, LootTableRegistry};
+    use crate::utils::rng::SeededRng;
+
+    #[test]
+    fn test_open_with_an_unknown_item_adds_nothing() {
+        let guaranteed = |item_id: &str| LootEntry {
+            drop: LootDrop::Item { item_id: item_id.to_string(), min_quantity: 1, max_quantity: 1 },
+            weight: 1,
+            conditions: Vec::new(),
+        };
+        let mut tables = LootTableRegistry::new();
+        tables.register_table(LootTable {
+            id: String::from("chest"),
+            guaranteed: vec![guaranteed("rope"), guaranteed("lantern")],
+            entries: Vec::new(),
+            min_rolls: 0,
+            max_rolls: 0,
+        });
+
+        let mut items = ItemRegistry::new();
+        items.register_item(ItemDefinition::new("rope", "Rope", "Fifty feet.", ItemCategory::Miscellaneous, 1, 5));
+        let flags = HashSet::new();
+        let context = LootContext { level: 1, story_flags: &flags };
+        let mut chest = LootContainer::new("Chest", LootContainerKind::Chest, Some(String::from("chest")));
+
+        assert_eq!(chest.open(&tables, &items, &context, &mut SeededRng::new(1)).unwrap_err(), LootError::UnknownItem(String::from("lantern")));
+        assert!(chest.is_empty());
+        assert!(!chest.is_rolled());
+
+        items.register_item(ItemDefinition::new("lantern", "Lantern", "Still has oil.", ItemCategory::Miscellaneous, 3, 1));
+        chest.open(&tables, &items, &context, &mut SeededRng::new(1)).unwrap();
+        assert_eq!(chest.contents.get_item_count("rope"), 1);
+        assert_eq!(chest.contents.get_item_count("lantern"), 1);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::inventory::inventory_container::ItemStack;
+use crate::utils::rng::SeededRng;
+
+// nested tables deeper than this are treated as a broken (probably cyclic) definition
+const MAX_TABLE_DEPTH: u32 = 8;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LootError {
+    #[error("Unknown loot table: {0}")]
+    UnknownTable(String),
+    #[error("Unknown item in loot: {0}")]
+    UnknownItem(String),
+    #[error("Loot table {0} nests too deeply, check for cycles")]
+    TableTooDeep(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LootCondition {
+    MinLevel(u32),
+    MaxLevel(u32),
+    StoryFlagSet(String),
+    StoryFlagNotSet(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LootDrop {
+    Item { item_id: String, min_quantity: u32, max_quantity: u32 },
+    Table(String),
+    Nothing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LootEntry {
+    pub drop: LootDrop,
+    pub weight: u32,
+    pub conditions: Vec<LootCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LootTable {
+    pub id: String,
+    // always dropped (if their conditions pass), regardless of the weighted rolls
+    pub guaranteed: Vec<LootEntry>,
+    pub entries: Vec<LootEntry>,
+    pub min_rolls: u32,
+    pub max_rolls: u32,
+}
+
+/// The state loot conditions are checked against, e.g. the level of the defeated NPC.
+pub struct LootContext<'a> {
+    pub level: u32,
+    pub story_flags: &'a HashSet<String>,
+}
+
+impl LootCondition {
+    pub fn is_met(&self, context: &LootContext) -> bool {
+        match self {
+            LootCondition::MinLevel(level) => context.level >= *level,
+            LootCondition::MaxLevel(level) => context.level <= *level,
+            LootCondition::StoryFlagSet(flag) => context.story_flags.contains(flag),
+            LootCondition::StoryFlagNotSet(flag) => !context.story_flags.contains(flag),
+        }
+    }
+}
+
+impl LootEntry {
+    pub fn is_available(&self, context: &LootContext) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.is_met(context))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LootTableRegistry {
+    tables: HashMap<String, LootTable>,
+}
+
+impl LootTableRegistry {
+    pub fn new() -> Self {
+        LootTableRegistry {
+            tables: HashMap::new(),
+        }
+    }
+
+    pub fn register_table(&mut self, table: LootTable) {
+        self.tables.insert(table.id.clone(), table);
+    }
+
+    pub fn get_table(&self, table_id: &str) -> Option<&LootTable> {
+        self.tables.get(table_id)
+    }
+
+    /// Rolls a table and everything nested in it, merging the drops into one stack per item.
+    pub fn roll(&self, table_id: &str, context: &LootContext, rng: &mut SeededRng) -> Result<Vec<ItemStack>, LootError> {
+        let mut drops = Vec::new();
+        self.roll_table(table_id, context, rng, 0, &mut drops)?;
+
+        Ok(drops)
+    }
+
+    fn roll_table(&self, table_id: &str, context: &LootContext, rng: &mut SeededRng, depth: u32, drops: &mut Vec<ItemStack>) -> Result<(), LootError> {
+        if depth > MAX_TABLE_DEPTH {
+            return Err(LootError::TableTooDeep(table_id.to_string()));
+        }
+
+        let table = self
+            .get_table(table_id)
+            .ok_or_else(|| LootError::UnknownTable(table_id.to_string()))?;
+
+        for entry in table.guaranteed.iter().filter(|entry| entry.is_available(context)) {
+            self.roll_drop(&entry.drop, context, rng, depth, drops)?;
+        }
+
+        let available: Vec<&LootEntry> = table.entries
+            .iter()
+            .filter(|entry| entry.weight > 0 && entry.is_available(context))
+            .collect();
+        let total_weight: u32 = available.iter().map(|entry| entry.weight).sum();
+
+        if total_weight == 0 {
+            return Ok(());
+        }
+
+        for _ in 0..rng.range_u32(table.min_rolls, table.max_rolls) {
+            let mut roll = rng.range_u32(1, total_weight);
+
+            for entry in &available {
+                if roll <= entry.weight {
+                    self.roll_drop(&entry.drop, context, rng, depth, drops)?;
+                    break;
+                }
+                roll -= entry.weight;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn roll_drop(&self, drop: &LootDrop, context: &LootContext, rng: &mut SeededRng, depth: u32, drops: &mut Vec<ItemStack>) -> Result<(), LootError> {
+        match drop {
+            LootDrop::Item { item_id, min_quantity, max_quantity } => {
+                let quantity = rng.range_u32(*min_quantity, *max_quantity);
+                if quantity == 0 {
+                    return Ok(());
+                }
+
+                match drops.iter_mut().find(|stack| &stack.item_id == item_id) {
+                    Some(stack) => stack.quantity += quantity,
+                    None => drops.push(ItemStack { item_id: item_id.clone(), quantity }),
+                }
+            },
+            LootDrop::Table(table_id) => self.roll_table(table_id, context, rng, depth + 1, drops)?,
+            LootDrop::Nothing => {},
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::inventory::inventory_container::ItemStack;
+    use crate::loot::loot_table::{LootCondition, LootContext, LootDrop, LootEntry, LootError, LootTable, LootTableRegistry};
+    use crate::utils::rng::SeededRng;
+
+    fn item_entry(item_id: &str, min_quantity: u32, max_quantity: u32, weight: u32, conditions: Vec<LootCondition>) -> LootEntry {
+        LootEntry {
+            drop: LootDrop::Item { item_id: item_id.to_string(), min_quantity, max_quantity },
+            weight,
+            conditions,
+        }
+    }
+
+    fn create_registry() -> LootTableRegistry {
+        let mut registry = LootTableRegistry::new();
+        registry.register_table(LootTable {
+            id: String::from("cultist_trinkets"),
+            guaranteed: Vec::new(),
+            entries: vec![item_entry("bone_charm", 1, 1, 1, Vec::new())],
+            min_rolls: 1,
+            max_rolls: 1,
+        });
+        registry.register_table(LootTable {
+            id: String::from("cultist"),
+            guaranteed: vec![
+                item_entry("copper_crown", 2, 6, 1, Vec::new()),
+                item_entry("cult_letter", 1, 1, 1, vec![LootCondition::StoryFlagSet(String::from("cult_investigation"))]),
+            ],
+            entries: vec![
+                LootEntry { drop: LootDrop::Table(String::from("cultist_trinkets")), weight: 1, conditions: Vec::new() },
+                item_entry("ritual_candle", 1, 3, 1, vec![LootCondition::MinLevel(50)]),
+            ],
+            min_rolls: 2,
+            max_rolls: 2,
+        });
+        registry.register_table(LootTable {
+            id: String::from("loop"),
+            guaranteed: vec![LootEntry { drop: LootDrop::Table(String::from("loop")), weight: 1, conditions: Vec::new() }],
+            entries: Vec::new(),
+            min_rolls: 0,
+            max_rolls: 0,
+        });
+        registry
+    }
+
+    #[test]
+    fn test_roll_is_deterministic_and_respects_conditions() {
+        let registry = create_registry();
+        let flags = HashSet::from([String::from("cult_investigation")]);
+        let context = LootContext { level: 3, story_flags: &flags };
+
+        let first = registry.roll("cultist", &context, &mut SeededRng::new(7)).unwrap();
+        let second = registry.roll("cultist", &context, &mut SeededRng::new(7)).unwrap();
+        assert_eq!(first, second);
+
+        // the level 50 candle is filtered out, so both rolls land on the nested trinket table
+        assert!(first.contains(&ItemStack { item_id: String::from("bone_charm"), quantity: 2 }));
+        assert!(first.iter().any(|stack| stack.item_id == "cult_letter"));
+        assert!(!first.iter().any(|stack| stack.item_id == "ritual_candle"));
+
+        let coins = first.iter().find(|stack| stack.item_id == "copper_crown").unwrap();
+        assert!((2..=6).contains(&coins.quantity));
+    }
+
+    #[test]
+    fn test_cyclic_tables_are_rejected() {
+        let registry = create_registry();
+        let flags = HashSet::new();
+        let context = LootContext { level: 1, story_flags: &flags };
+
+        assert_eq!(registry.roll("loop", &context, &mut SeededRng::new(1)), Err(LootError::TableTooDeep(String::from("loop"))));
+        assert_eq!(registry.roll("missing", &context, &mut SeededRng::new(1)), Err(LootError::UnknownTable(String::from("missing"))));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod loot_table;
+pub mod loot_container;
\ No newline at end of file
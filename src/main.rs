@@ -1,10 +1,3 @@
-mod textures;
-mod actors;
-mod equipment;
-mod story_tree;
-mod inventory;
-mod state_management;
-
 use common::dbl_buffer::DoubleBuffer;
 
 fn main() {
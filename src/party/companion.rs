@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::abilities::ability::AbilityRegistry;
+use crate::actors::player::PlayerStats;
+use crate::combat::combatant::Combatant;
+use crate::equipment::equipment_manager::{Equipment, EquipmentManager};
+use crate::inventory::inventory_container::InventoryContainer;
+use crate::state_management::player_state::calculate_experience_to_next_level;
+use crate::status_effects::active_effects::StatusEffects;
+
+// approval ranges from -100 to 100
+const MIN_APPROVAL: i32 = -100;
+const MAX_APPROVAL: i32 = 100;
+const COMPANION_INVENTORY_SLOTS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LoyaltyTier {
+    Resentful,
+    Wary,
+    Neutral,
+    Loyal,
+    Devoted,
+}
+
+impl LoyaltyTier {
+    pub fn from_approval(approval: i32) -> Self {
+        match approval {
+            value if value <= -50 => LoyaltyTier::Resentful,
+            value if value < -10 => LoyaltyTier::Wary,
+            value if value <= 10 => LoyaltyTier::Neutral,
+            value if value < 50 => LoyaltyTier::Loyal,
+            _ => LoyaltyTier::Devoted,
+        }
+    }
+}
+
+/// A character that travels with the player. Companions have their own stats, equipment, pack and status effects,
+/// and opinions about the player's choices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Companion {
+    pub id: String,
+    pub name: String,
+    pub stats: PlayerStats,
+    pub experience: u32,
+    pub experience_to_next_level: u32,
+    pub equipment: EquipmentManager,
+    pub inventory: InventoryContainer,
+    pub status_effects: StatusEffects,
+    pub ability_ids: Vec<String>,
+    pub approval: i32,
+    // the companion leaves the party once approval drops to this or below
+    pub leave_threshold: i32,
+    // how the companion feels about choices with a given tag, e.g. "mercy" or "forbidden_magic"
+    pub opinions: HashMap<String, i32>,
+}
+
+impl Companion {
+    pub fn new(id: &str, name: &str, stats: PlayerStats) -> Self {
+        Companion {
+            id: id.to_string(),
+            name: name.to_string(),
+            stats,
+            experience: 0,
+            experience_to_next_level: calculate_experience_to_next_level(stats.level),
+            equipment: EquipmentManager::new(),
+            inventory: InventoryContainer::new(Some(COMPANION_INVENTORY_SLOTS)),
+            status_effects: StatusEffects::new(),
+            ability_ids: Vec::new(),
+            approval: 0,
+            leave_threshold: -50,
+            opinions: HashMap::new(),
+        }
+    }
+
+    pub fn with_equipment(mut self, equipment: Equipment) -> Self {
+        self.equipment.equip(equipment);
+        self
+    }
+
+    pub fn with_opinion(mut self, tag: &str, approval: i32) -> Self {
+        self.opinions.insert(tag.to_string(), approval);
+        self
+    }
+
+    pub fn with_abilities(mut self, ability_ids: Vec<String>) -> Self {
+        self.ability_ids = ability_ids;
+        self
+    }
+
+    pub fn get_loyalty_tier(&self) -> LoyaltyTier {
+        LoyaltyTier::from_approval(self.approval)
+    }
+
+    pub fn wants_to_leave(&self) -> bool {
+        self.approval <= self.leave_threshold
+    }
+
+    pub fn change_approval(&mut self, amount: i32) -> i32 {
+        let before = self.approval;
+        self.approval = (self.approval + amount).clamp(MIN_APPROVAL, MAX_APPROVAL);
+        self.approval - before
+    }
+
+    /// The combined approval change for a choice with the given tags.
+    pub fn get_reaction(&self, tags: &[String]) -> i32 {
+        tags
+            .iter()
+            .filter_map(|tag| self.opinions.get(tag))
+            .sum()
+    }
+
+    /// Returns how many levels were gained.
+    pub fn gain_experience(&mut self, experience: u32) -> u32 {
+        self.experience += experience;
+
+        let mut levels = 0;
+        while self.experience >= self.experience_to_next_level {
+            self.experience -= self.experience_to_next_level;
+            self.stats.level_up();
+            self.experience_to_next_level = calculate_experience_to_next_level(self.stats.level);
+            levels += 1;
+        }
+        levels
+    }
+
+    /// The companion as they are right now, fighting with whatever is in their main hand.
+    /// Call `Combatant::write_back_to_companion` once the fight is over.
+    pub fn to_combatant(&self, abilities: &AbilityRegistry) -> Combatant {
+        let mut combatant = Combatant::from_player_stats(&self.name, &self.stats)
+            .with_weapon(self.equipment.get_weapon_stats())
+            .with_abilities(abilities.get_known_abilities(&self.ability_ids));
+        combatant.status_effects = self.status_effects.clone();
+        combatant
+    }
+}
@@ -0,0 +1,2 @@
+pub mod companion;
+pub mod roster;
\ No newline at end of file
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::abilities::ability::AbilityRegistry;
+use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+use crate::party::companion::Companion;
+
+const DEFAULT_MAX_COMPANIONS: usize = 3;
+// companions get this share of the experience the player earns
+const DEFAULT_SHARED_EXPERIENCE_PERCENT: u32 = 75;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PartyError {
+    #[error("The party is full ({0} companions)")]
+    PartyFull(usize),
+    #[error("{0} is already in the party")]
+    AlreadyInParty(String),
+    #[error("{0} is not in the party")]
+    NotInParty(String),
+    #[error("Invalid party data: {0}")]
+    InvalidData(String),
+}
+
+/// How companions line up around the player when a fight starts. The enemy is assumed to be towards +x.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Formation {
+    // side by side with the player
+    Line,
+    // behind the player in single file
+    Column,
+    // fanning out behind the player on both sides
+    Wedge,
+}
+
+impl Formation {
+    /// The offset from the player for the companion at the given place in the roster.
+    pub fn get_offset(&self, index: usize) -> GridPosition {
+        let rank = index as i32 / 2 + 1;
+        let side = match index % 2 {
+            0 => 1,
+            _ => -1,
+        };
+
+        match self {
+            Formation::Line => GridPosition::new(0, rank * side),
+            Formation::Column => GridPosition::new(-(index as i32 + 1), 0),
+            Formation::Wedge => GridPosition::new(-rank, rank * side),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalChange {
+    pub companion_id: String,
+    pub amount: i32,
+}
+
+/// What a choice did to the party: every approval change and anyone who walked out over it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChoiceReaction {
+    pub changes: Vec<ApprovalChange>,
+    pub departed: Vec<Companion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Party {
+    companions: Vec<Companion>,
+    pub max_companions: usize,
+    pub formation: Formation,
+    pub shared_experience_percent: u32,
+}
+
+impl Party {
+    pub fn new() -> Self {
+        Party {
+            companions: Vec::new(),
+            max_companions: DEFAULT_MAX_COMPANIONS,
+            formation: Formation::Wedge,
+            shared_experience_percent: DEFAULT_SHARED_EXPERIENCE_PERCENT,
+        }
+    }
+
+    pub fn get_companions(&self) -> &[Companion] {
+        &self.companions
+    }
+
+    pub fn get_companion(&self, companion_id: &str) -> Option<&Companion> {
+        self.companions
+            .iter()
+            .find(|companion| companion.id == companion_id)
+    }
+
+    pub fn get_companion_mut(&mut self, companion_id: &str) -> Option<&mut Companion> {
+        self.companions
+            .iter_mut()
+            .find(|companion| companion.id == companion_id)
+    }
+
+    pub fn recruit(&mut self, companion: Companion) -> Result<(), PartyError> {
+        if self.get_companion(&companion.id).is_some() {
+            return Err(PartyError::AlreadyInParty(companion.id));
+        }
+        if self.companions.len() >= self.max_companions {
+            return Err(PartyError::PartyFull(self.max_companions));
+        }
+
+        self.companions.push(companion);
+        Ok(())
+    }
+
+    pub fn dismiss(&mut self, companion_id: &str) -> Result<Companion, PartyError> {
+        let index = self.companions
+            .iter()
+            .position(|companion| companion.id == companion_id)
+            .ok_or_else(|| PartyError::NotInParty(companion_id.to_string()))?;
+
+        Ok(self.companions.remove(index))
+    }
+
+    /// Lets every companion react to a story choice with the given tags.
+    /// Companions whose approval drops to their leave threshold leave the party.
+    pub fn react_to_choice(&mut self, tags: &[String]) -> ChoiceReaction {
+        let mut reaction = ChoiceReaction::default();
+
+        for companion in &mut self.companions {
+            let amount = companion.change_approval(companion.get_reaction(tags));
+            if amount != 0 {
+                reaction.changes.push(ApprovalChange { companion_id: companion.id.clone(), amount });
+            }
+        }
+
+        let (departed, staying) = std::mem::take(&mut self.companions)
+            .into_iter()
+            .partition(|companion| companion.wants_to_leave());
+        self.companions = staying;
+        reaction.departed = departed;
+
+        reaction
+    }
+
+    /// Gives every companion their share of the experience the player earned,
+    /// returning the ids of companions that levelled up.
+    pub fn share_experience(&mut self, experience: u32) -> Vec<String> {
+        let share = experience * self.shared_experience_percent / 100;
+
+        self.companions
+            .iter_mut()
+            .filter_map(|companion| match companion.gain_experience(share) {
+                0 => None,
+                _ => Some(companion.id.clone()),
+            })
+            .collect()
+    }
+
+    /// The player and every companion, ready to be dropped into a fight with the companions in formation.
+    pub fn create_combatants(&self, player: Combatant, abilities: &AbilityRegistry) -> Vec<Combatant> {
+        let origin = player.position;
+        let companions = self.companions
+            .iter()
+            .enumerate()
+            .map(|(index, companion)| {
+                let offset = self.formation.get_offset(index);
+                let mut combatant = companion.to_combatant(abilities)
+                    .with_position(GridPosition::new(origin.x + offset.x, origin.y + offset.y));
+                combatant.team = CombatTeam::Party;
+                combatant
+            });
+
+        std::iter::once(player).chain(companions).collect()
+    }
+
+    /// Carries what a fight did to each companion back to them. Takes the combatants as they were
+    /// lined up by `create_combatants`, player first.
+    pub fn write_back_combatants(&mut self, combatants: &[Combatant]) {
+        for (companion, combatant) in self.companions.iter_mut().zip(combatants.iter().skip(1)) {
+            combatant.write_back_to_companion(companion);
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, PartyError> {
+        serde_json::to_string(self).map_err(|error| PartyError::InvalidData(error.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PartyError> {
+        serde_json::from_str(json).map_err(|error| PartyError::InvalidData(error.to_string()))
+    }
+}
+
+impl Default for Party {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::abilities::ability::AbilityRegistry;
+    use crate::actors::models::{ComputedAttributes, ResourcePoolEnum, ResourcePoolValues, StatBlock};
+    use crate::actors::player::PlayerStats;
+    use crate::combat::combatant::{Combatant, GridPosition};
+    use crate::equipment::equipment_manager::{Equipment, EquipmentSlot, WeaponStats, WeaponType};
+    use crate::party::companion::{Companion, LoyaltyTier};
+    use crate::party::roster::{Party, PartyError};
+    use crate::state_management::player_state::PlayerState;
+
+    fn create_sword() -> Equipment {
+        Equipment {
+            slot: EquipmentSlot::MainHand(WeaponType::Sword),
+            name: "Arming Sword".to_string(),
+            description: String::new(),
+            required_stats: StatBlock::default(),
+            stat_modifiers: StatBlock::default(),
+            attribute_modifiers: ComputedAttributes {
+                health: 0,
+                mana: 0,
+                stamina: 0,
+                physical_resistance: 0,
+                psychological_resistance: 0,
+                madness_limit: 0,
+            },
+            weapon_stats: Some(WeaponStats::new(&WeaponType::Sword, 6, 10)),
+        }
+    }
+
+    fn create_companion(id: &str) -> Companion {
+        Companion::new(id, id, PlayerStats::new())
+            .with_equipment(create_sword())
+            .with_opinion("mercy", 15)
+            .with_opinion("forbidden_magic", -30)
+    }
+
+    #[test]
+    fn test_roster_limits() {
+        let mut party = Party::new();
+        party.max_companions = 2;
+
+        party.recruit(create_companion("squire")).unwrap();
+        assert_eq!(party.recruit(create_companion("squire")), Err(PartyError::AlreadyInParty("squire".to_string())));
+        party.recruit(create_companion("hedge_witch")).unwrap();
+        assert_eq!(party.recruit(create_companion("monk")), Err(PartyError::PartyFull(2)));
+
+        assert_eq!(party.dismiss("squire").unwrap().id, "squire");
+        assert_eq!(party.dismiss("squire"), Err(PartyError::NotInParty("squire".to_string())));
+    }
+
+    #[test]
+    fn test_companions_leave_over_choices() {
+        let mut party = Party::new();
+        party.recruit(create_companion("squire")).unwrap();
+        party.recruit(create_companion("hedge_witch").with_opinion("forbidden_magic", 20)).unwrap();
+
+        let reaction = party.react_to_choice(&["mercy".to_string()]);
+        assert_eq!(reaction.changes.len(), 2);
+        assert_eq!(party.get_companion("squire").unwrap().get_loyalty_tier(), LoyaltyTier::Loyal);
+
+        let forbidden = vec!["forbidden_magic".to_string()];
+        party.react_to_choice(&forbidden);
+        party.react_to_choice(&forbidden);
+        assert_eq!(party.get_companion("squire").unwrap().get_loyalty_tier(), LoyaltyTier::Wary);
+
+        let reaction = party.react_to_choice(&forbidden);
+        assert_eq!(reaction.departed.len(), 1);
+        assert_eq!(reaction.departed[0].id, "squire");
+        assert_eq!(party.get_companions().len(), 1);
+        assert_eq!(party.get_companion("hedge_witch").unwrap().get_loyalty_tier(), LoyaltyTier::Devoted);
+    }
+
+    #[test]
+    fn test_shared_experience_combat_and_saving() {
+        let mut party = Party::new();
+        party.recruit(create_companion("squire")).unwrap();
+        party.recruit(create_companion("hedge_witch")).unwrap();
+
+        assert_eq!(party.share_experience(200), vec!["squire".to_string(), "hedge_witch".to_string()]);
+        assert_eq!(party.get_companion("squire").unwrap().stats.level, 2);
+
+        let player = Combatant::from_player_stats("Player", &PlayerStats::new()).with_position(GridPosition::new(3, 3));
+        let combatants = party.create_combatants(player, &AbilityRegistry::new());
+        assert_eq!(combatants.len(), 3);
+        assert_eq!(combatants[1].position, GridPosition::new(2, 4));
+        assert_eq!(combatants[2].position, GridPosition::new(2, 2));
+        assert_eq!(combatants[1].weapon.max_damage, 10);
+
+        let saved = party.to_json().unwrap();
+        assert_eq!(Party::from_json(&saved).unwrap(), party);
+    }
+
+    #[test]
+    fn test_companions_keep_their_wounds_and_share_player_experience() {
+        let mut player = PlayerState::new();
+        player.party.recruit(create_companion("squire")).unwrap();
+        player.party.recruit(Companion::new("monk", "monk", PlayerStats::new())).unwrap();
+
+        let leader = Combatant::from_player(&player);
+        let mut combatants = player.party.create_combatants(leader, &AbilityRegistry::new());
+        assert_eq!(combatants[2].weapon, WeaponStats::unarmed());
+
+        let health = combatants[1].resources.get_resource(ResourcePoolEnum::Health);
+        combatants[1].modify_resource(ResourcePoolEnum::Health, -12);
+        combatants[2].modify_resource(ResourcePoolEnum::Madness, 5);
+        player.party.write_back_combatants(&combatants);
+
+        let squire = player.party.get_companion("squire").unwrap();
+        assert_eq!(squire.stats.resources.get_resource(ResourcePoolEnum::Health), health - 12);
+        let monk = player.party.get_companion("monk").unwrap();
+        assert_eq!(monk.stats.resources.get_resource(ResourcePoolEnum::Madness), 5);
+
+        player.gain_experience(200);
+        assert_eq!(player.party.get_companion("squire").unwrap().stats.level, 2);
+        assert_eq!(player.party.get_companion("monk").unwrap().experience, 150 - 100);
+    }
+}
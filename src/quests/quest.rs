@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::factions::faction::FactionRegistry;
+use crate::inventory::inventory_container::ItemStack;
+use crate::inventory::item::ItemRegistry;
+use crate::state_management::player_state::PlayerState;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum QuestError {
+    #[error("Unknown quest: {0}")]
+    UnknownQuest(String),
+    #[error("Quest {0} has already been started")]
+    AlreadyStarted(String),
+    #[error("Quest {0} is not active")]
+    NotActive(String),
+    #[error("Quest {quest_id} is invalid: {reason}")]
+    InvalidQuest { quest_id: String, reason: String },
+    #[error("Invalid quest data: {0}")]
+    InvalidData(String),
+}
+
+/// Something that happened in the game that quests listen for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuestEvent {
+    Killed(String),
+    ItemCollected { item_id: String, quantity: u32 },
+    LocationReached(String),
+    TalkedTo(String),
+    // the player picked the option on `node_id` that leads to `next_node_id`
+    StoryChoice { node_id: u32, next_node_id: u32 },
+    FlagSet(String),
+}
+
+/// Items taken from loot count towards collect objectives, e.g. the stacks `LootContainer::take_all` returns.
+impl From<&ItemStack> for QuestEvent {
+    fn from(stack: &ItemStack) -> Self {
+        QuestEvent::ItemCollected { item_id: stack.item_id.clone(), quantity: stack.quantity }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ObjectiveKind {
+    Kill { npc_id: String, count: u32 },
+    Collect { item_id: String, quantity: u32 },
+    Reach(String),
+    TalkTo(String),
+    StoryChoice { node_id: u32, next_node_id: u32 },
+    Flag(String),
+}
+
+impl ObjectiveKind {
+    pub fn get_required_count(&self) -> u32 {
+        match self {
+            ObjectiveKind::Kill { count, .. } => (*count).max(1),
+            ObjectiveKind::Collect { quantity, .. } => (*quantity).max(1),
+            _ => 1,
+        }
+    }
+
+    /// How far the event takes the objective, 0 if it has nothing to do with it.
+    pub fn count_event(&self, event: &QuestEvent) -> u32 {
+        match (self, event) {
+            (ObjectiveKind::Kill { npc_id, .. }, QuestEvent::Killed(killed_id)) if npc_id == killed_id => 1,
+            (ObjectiveKind::Collect { item_id, .. }, QuestEvent::ItemCollected { item_id: collected_id, quantity }) if item_id == collected_id => *quantity,
+            (ObjectiveKind::Reach(location_id), QuestEvent::LocationReached(reached_id)) if location_id == reached_id => 1,
+            (ObjectiveKind::TalkTo(npc_id), QuestEvent::TalkedTo(talked_to_id)) if npc_id == talked_to_id => 1,
+            (ObjectiveKind::StoryChoice { node_id, next_node_id }, QuestEvent::StoryChoice { node_id: chosen_node_id, next_node_id: chosen_next_node_id }) => {
+                match node_id == chosen_node_id && next_node_id == chosen_next_node_id {
+                    true => 1,
+                    false => 0,
+                }
+            },
+            (ObjectiveKind::Flag(flag), QuestEvent::FlagSet(set_flag)) if flag == set_flag => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestObjective {
+    pub id: String,
+    pub description: String,
+    pub kind: ObjectiveKind,
+    // optional objectives don't hold the stage up, and failing them doesn't fail the quest
+    #[serde(default)]
+    pub optional: bool,
+    // e.g. the merchant the player is escorting being killed
+    #[serde(default)]
+    pub fails_on: Option<ObjectiveKind>,
+}
+
+/// Where a stage leads once its objectives are done.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestBranch {
+    // the objectives that have to be complete for the branch to be taken, every required one when empty
+    #[serde(default)]
+    pub objective_ids: Vec<String>,
+    // the stage to move on to, without one the quest is completed with the outcome
+    #[serde(default)]
+    pub next_stage_id: Option<String>,
+    #[serde(default)]
+    pub outcome_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestStage {
+    pub id: String,
+    // added to the journal when the stage is reached
+    #[serde(default)]
+    pub journal_entry: String,
+    #[serde(default)]
+    pub objectives: Vec<QuestObjective>,
+    // checked in order, without any the quest moves on to the next stage (or completes) once every required objective is done
+    #[serde(default)]
+    pub branches: Vec<QuestBranch>,
+}
+
+impl QuestStage {
+    pub fn get_objective(&self, objective_id: &str) -> Option<&QuestObjective> {
+        self.objectives.iter().find(|objective| objective.id == objective_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuestReward {
+    Experience(u32),
+    Item { item_id: String, quantity: u32 },
+    Currency { currency_id: String, amount: u32 },
+    Reputation { faction_id: String, amount: i32 },
+    SetFlag(String),
+}
+
+impl QuestReward {
+    /// Gives the reward, returning false if it couldn't be given, e.g. an item that doesn't fit in the inventory.
+    pub fn apply(&self, quest_id: &str, player: &mut PlayerState, story_flags: &mut HashSet<String>, factions: &FactionRegistry, items: &ItemRegistry) -> bool {
+        match self {
+            QuestReward::Experience(experience) => player.gain_experience(*experience),
+            QuestReward::Item { item_id, quantity } => {
+                let Some(item) = items.get_item(item_id) else {
+                    return false;
+                };
+                return player.inventory.add_item(item, *quantity).is_ok();
+            },
+            QuestReward::Currency { currency_id, amount } => player.purse.deposit(currency_id, *amount),
+            QuestReward::Reputation { faction_id, amount } => {
+                player.reputation.record_quest(quest_id, faction_id, *amount, factions);
+            },
+            QuestReward::SetFlag(flag) => {
+                story_flags.insert(flag.clone());
+            },
+        }
+        true
+    }
+}
+
+/// One of the ways a quest can end, e.g. sparing or killing the heretic priest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestOutcome {
+    pub id: String,
+    #[serde(default)]
+    pub journal_entry: String,
+    // given on top of the quest's own rewards
+    #[serde(default)]
+    pub rewards: Vec<QuestReward>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // the quest starts at the first stage
+    pub stages: Vec<QuestStage>,
+    // given however the quest is completed
+    #[serde(default)]
+    pub rewards: Vec<QuestReward>,
+    #[serde(default)]
+    pub outcomes: Vec<QuestOutcome>,
+    #[serde(default)]
+    pub failure_journal_entry: String,
+}
+
+impl Quest {
+    pub fn get_stage(&self, stage_id: &str) -> Option<&QuestStage> {
+        self.stages.iter().find(|stage| stage.id == stage_id)
+    }
+
+    pub fn get_outcome(&self, outcome_id: &str) -> Option<&QuestOutcome> {
+        self.outcomes.iter().find(|outcome| outcome.id == outcome_id)
+    }
+
+    /// The stage after the given one in the list, None for the last stage.
+    pub fn get_next_stage(&self, stage_id: &str) -> Option<&QuestStage> {
+        let index = self.stages.iter().position(|stage| stage.id == stage_id)?;
+        self.stages.get(index + 1)
+    }
+
+    /// Checks that every stage, objective and outcome the quest refers to exists.
+    pub fn validate(&self) -> Result<(), QuestError> {
+        let invalid = |reason: String| QuestError::InvalidQuest { quest_id: self.id.clone(), reason };
+
+        if self.stages.is_empty() {
+            return Err(invalid("it has no stages".to_string()));
+        }
+
+        let mut stage_ids = HashSet::new();
+        for stage in &self.stages {
+            if !stage_ids.insert(stage.id.as_str()) {
+                return Err(invalid(format!("stage {} is defined more than once", stage.id)));
+            }
+
+            let mut objective_ids = HashSet::new();
+            if let Some(objective) = stage.objectives.iter().find(|objective| !objective_ids.insert(objective.id.as_str())) {
+                return Err(invalid(format!("objective {} is defined more than once in stage {}", objective.id, stage.id)));
+            }
+
+            for branch in &stage.branches {
+                if let Some(objective_id) = branch.objective_ids.iter().find(|objective_id| stage.get_objective(objective_id).is_none()) {
+                    return Err(invalid(format!("a branch of stage {} needs missing objective {}", stage.id, objective_id)));
+                }
+                if let Some(next_stage_id) = branch.next_stage_id.as_ref().filter(|next_stage_id| self.get_stage(next_stage_id).is_none()) {
+                    return Err(invalid(format!("a branch of stage {} leads to missing stage {}", stage.id, next_stage_id)));
+                }
+                if let Some(outcome_id) = branch.outcome_id.as_ref().filter(|outcome_id| self.get_outcome(outcome_id).is_none()) {
+                    return Err(invalid(format!("a branch of stage {} ends with missing outcome {}", stage.id, outcome_id)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct QuestRegistry {
+    quests: HashMap<String, Quest>,
+}
+
+impl QuestRegistry {
+    pub fn new() -> Self {
+        QuestRegistry {
+            quests: HashMap::new(),
+        }
+    }
+
+    pub fn load_from_json(&mut self, json: &str) -> Result<(), QuestError> {
+        let quests: Vec<Quest> = serde_json::from_str(json).map_err(|error| QuestError::InvalidData(error.to_string()))?;
+        for quest in quests {
+            self.register_quest(quest)?;
+        }
+        Ok(())
+    }
+
+    pub fn register_quest(&mut self, quest: Quest) -> Result<(), QuestError> {
+        quest.validate()?;
+        self.quests.insert(quest.id.clone(), quest);
+        Ok(())
+    }
+
+    pub fn get_quest(&self, quest_id: &str) -> Result<&Quest, QuestError> {
+        self.quests
+            .get(quest_id)
+            .ok_or_else(|| QuestError::UnknownQuest(quest_id.to_string()))
+    }
+}
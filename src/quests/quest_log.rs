@@ -0,0 +1,676 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+use crate::factions::faction::FactionRegistry;
+use crate::inventory::item::ItemRegistry;
+use crate::quests::quest::{ObjectiveKind, Quest, QuestError, QuestEvent, QuestRegistry, QuestReward};
+use crate::state_management::player_state::PlayerState;
+use crate::story_tree::story_effects::{StoryOutcome, StoryWorld};
+use crate::story_tree::story_graph::StoryChoice;
+use crate::story_tree::story_history::StorySnapshot;
+use crate::story_tree::story_management::PlayerStoryTree;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuestStatus {
+    Active,
+    // along with the outcome the quest ended with, if it has more than one ending
+    Completed(Option<String>),
+    Failed,
+}
+
+/// What a story condition can ask about a quest.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuestState {
+    NotStarted,
+    Active,
+    AtStage(String),
+    Completed,
+    CompletedWith(String),
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ObjectiveState {
+    Active,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectiveProgress {
+    pub count: u32,
+    // progress towards the objective's failure, e.g. escorted merchants killed
+    pub failure_count: u32,
+    pub state: ObjectiveState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestProgress {
+    pub quest_id: String,
+    pub status: QuestStatus,
+    // the stage the quest is at, or ended at
+    pub stage_id: String,
+    // the objectives of the current stage
+    pub objectives: HashMap<String, ObjectiveProgress>,
+}
+
+impl QuestProgress {
+    pub fn get_objective(&self, objective_id: &str) -> Option<&ObjectiveProgress> {
+        self.objectives.get(objective_id)
+    }
+
+    fn is_objective_complete(&self, objective_id: &str) -> bool {
+        self.get_objective(objective_id).is_some_and(|objective| objective.state == ObjectiveState::Completed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JournalEntryKind {
+    Started,
+    StageReached(String),
+    ObjectiveCompleted(String),
+    ObjectiveFailed(String),
+    Completed(Option<String>),
+    Failed,
+}
+
+/// A line in the player's journal, with the authored text for it (which can be empty).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub quest_id: String,
+    pub kind: JournalEntryKind,
+    pub text: String,
+}
+
+/// What changed in the player's quests, for the UI to announce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuestUpdate {
+    Started(String),
+    StageReached { quest_id: String, stage_id: String },
+    ObjectiveProgressed { quest_id: String, objective_id: String, count: u32, required: u32 },
+    ObjectiveCompleted { quest_id: String, objective_id: String },
+    ObjectiveFailed { quest_id: String, objective_id: String },
+    Completed { quest_id: String, outcome_id: Option<String> },
+    Failed(String),
+    RewardGiven(QuestReward),
+    // e.g. an item that didn't fit, left for the game to drop at the player's feet
+    RewardNotGiven(QuestReward),
+}
+
+/// The player's quests and their journal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuestLog {
+    quests: HashMap<String, QuestProgress>,
+    journal: Vec<JournalEntry>,
+}
+
+impl QuestLog {
+    pub fn new() -> Self {
+        QuestLog {
+            quests: HashMap::new(),
+            journal: Vec::new(),
+        }
+    }
+
+    pub fn get_progress(&self, quest_id: &str) -> Option<&QuestProgress> {
+        self.quests.get(quest_id)
+    }
+
+    pub fn get_status(&self, quest_id: &str) -> Option<&QuestStatus> {
+        self.get_progress(quest_id).map(|progress| &progress.status)
+    }
+
+    pub fn get_active_quest_ids(&self) -> Vec<&str> {
+        let mut quest_ids: Vec<&str> = self.quests
+            .values()
+            .filter(|progress| progress.status == QuestStatus::Active)
+            .map(|progress| progress.quest_id.as_str())
+            .collect();
+        quest_ids.sort();
+        quest_ids
+    }
+
+    /// Every journal entry, oldest first.
+    pub fn get_journal(&self) -> &[JournalEntry] {
+        &self.journal
+    }
+
+    pub fn get_journal_for(&self, quest_id: &str) -> Vec<&JournalEntry> {
+        self.journal
+            .iter()
+            .filter(|entry| entry.quest_id == quest_id)
+            .collect()
+    }
+
+    pub fn is_in_state(&self, quest_id: &str, state: &QuestState) -> bool {
+        let Some(progress) = self.get_progress(quest_id) else {
+            return *state == QuestState::NotStarted;
+        };
+
+        match (state, &progress.status) {
+            (QuestState::Active, QuestStatus::Active) => true,
+            (QuestState::AtStage(stage_id), QuestStatus::Active) => progress.stage_id == *stage_id,
+            (QuestState::Completed, QuestStatus::Completed(_)) => true,
+            (QuestState::CompletedWith(outcome_id), QuestStatus::Completed(Some(completed_with))) => outcome_id == completed_with,
+            (QuestState::Failed, QuestStatus::Failed) => true,
+            _ => false,
+        }
+    }
+
+    fn add_journal_entry(&mut self, quest_id: &str, kind: JournalEntryKind, text: &str) {
+        self.journal.push(JournalEntry { quest_id: quest_id.to_string(), kind, text: text.to_string() });
+    }
+}
+
+/// Moves quests along for one call, collecting what changed.
+struct QuestRun<'a> {
+    log: &'a mut QuestLog,
+    registry: &'a QuestRegistry,
+    player: &'a mut PlayerState,
+    story_flags: &'a mut HashSet<String>,
+    factions: &'a FactionRegistry,
+    items: &'a ItemRegistry,
+    // events raised while running, e.g. flags set by rewards
+    pending_events: VecDeque<QuestEvent>,
+    updates: Vec<QuestUpdate>,
+}
+
+impl QuestRun<'_> {
+    fn start(&mut self, quest_id: &str) -> Result<(), QuestError> {
+        let quest = self.registry.get_quest(quest_id)?;
+        if self.log.quests.contains_key(quest_id) {
+            return Err(QuestError::AlreadyStarted(quest_id.to_string()));
+        }
+
+        self.log.quests.insert(quest_id.to_string(), QuestProgress {
+            quest_id: quest_id.to_string(),
+            status: QuestStatus::Active,
+            stage_id: String::new(),
+            objectives: HashMap::new(),
+        });
+        self.log.add_journal_entry(quest_id, JournalEntryKind::Started, &quest.description);
+        self.updates.push(QuestUpdate::Started(quest_id.to_string()));
+
+        self.enter_stage(quest, &quest.stages[0].id);
+        self.settle(quest);
+        Ok(())
+    }
+
+    fn get_active_progress(&mut self, quest_id: &str) -> Option<&mut QuestProgress> {
+        self.log.quests
+            .get_mut(quest_id)
+            .filter(|progress| progress.status == QuestStatus::Active)
+    }
+
+    fn enter_stage(&mut self, quest: &Quest, stage_id: &str) {
+        let Some(stage) = quest.get_stage(stage_id) else {
+            return;
+        };
+
+        // flags the story has already set count straight away
+        let objectives = stage.objectives
+            .iter()
+            .map(|objective| {
+                let state = match &objective.kind {
+                    ObjectiveKind::Flag(flag) if self.story_flags.contains(flag) => ObjectiveState::Completed,
+                    _ => ObjectiveState::Active,
+                };
+                let count = match state {
+                    ObjectiveState::Completed => 1,
+                    _ => 0,
+                };
+                (objective.id.clone(), ObjectiveProgress { count, failure_count: 0, state })
+            })
+            .collect();
+
+        let Some(progress) = self.get_active_progress(&quest.id) else {
+            return;
+        };
+        progress.stage_id = stage.id.clone();
+        progress.objectives = objectives;
+
+        self.log.add_journal_entry(&quest.id, JournalEntryKind::StageReached(stage.id.clone()), &stage.journal_entry);
+        self.updates.push(QuestUpdate::StageReached { quest_id: quest.id.clone(), stage_id: stage.id.clone() });
+    }
+
+    /// Where the current stage leads now, as the next stage or the outcome to complete the quest with.
+    /// Stages without objectives wait for the story to advance them.
+    fn get_transition(quest: &Quest, progress: &QuestProgress, forced: bool) -> Option<(Option<String>, Option<String>)> {
+        let stage = quest.get_stage(&progress.stage_id)?;
+        let required_done = !stage.objectives.is_empty() && stage.objectives
+            .iter()
+            .filter(|objective| !objective.optional)
+            .all(|objective| progress.is_objective_complete(&objective.id));
+
+        if stage.branches.is_empty() {
+            return (forced || required_done).then(|| (quest.get_next_stage(&stage.id).map(|next_stage| next_stage.id.clone()), None));
+        }
+
+        stage.branches
+            .iter()
+            .find(|branch| forced || match branch.objective_ids.is_empty() {
+                true => required_done,
+                false => branch.objective_ids.iter().all(|objective_id| progress.is_objective_complete(objective_id)),
+            })
+            .map(|branch| (branch.next_stage_id.clone(), branch.outcome_id.clone()))
+    }
+
+    /// Follows the quest through every stage it can move on from, failing it if a required objective failed.
+    fn settle(&mut self, quest: &Quest) {
+        // every stage can be passed through at most once without waiting on anything new
+        for _ in 0..=quest.stages.len() {
+            let Some(progress) = self.log.quests.get(&quest.id).filter(|progress| progress.status == QuestStatus::Active) else {
+                return;
+            };
+
+            let required_failed = quest.get_stage(&progress.stage_id).is_some_and(|stage| stage.objectives
+                .iter()
+                .any(|objective| !objective.optional && progress.get_objective(&objective.id).is_some_and(|objective| objective.state == ObjectiveState::Failed)));
+            if required_failed {
+                self.fail(quest);
+                return;
+            }
+
+            match Self::get_transition(quest, progress, false) {
+                Some((Some(next_stage_id), _)) => self.enter_stage(quest, &next_stage_id),
+                Some((None, outcome_id)) => return self.complete(quest, outcome_id),
+                None => return,
+            }
+        }
+    }
+
+    fn advance(&mut self, quest_id: &str) -> Result<(), QuestError> {
+        let quest = self.registry.get_quest(quest_id)?;
+        let progress = self.log.quests
+            .get(quest_id)
+            .filter(|progress| progress.status == QuestStatus::Active)
+            .ok_or_else(|| QuestError::NotActive(quest_id.to_string()))?;
+
+        match Self::get_transition(quest, progress, true) {
+            Some((Some(next_stage_id), _)) => {
+                self.enter_stage(quest, &next_stage_id);
+                self.settle(quest);
+            },
+            Some((None, outcome_id)) => self.complete(quest, outcome_id),
+            None => {},
+        }
+        Ok(())
+    }
+
+    fn complete(&mut self, quest: &Quest, outcome_id: Option<String>) {
+        let outcome = outcome_id.as_deref().and_then(|outcome_id| quest.get_outcome(outcome_id));
+        if let Some(progress) = self.get_active_progress(&quest.id) {
+            progress.status = QuestStatus::Completed(outcome_id.clone());
+        }
+
+        let text = outcome.map_or("", |outcome| outcome.journal_entry.as_str());
+        self.log.add_journal_entry(&quest.id, JournalEntryKind::Completed(outcome_id.clone()), text);
+        self.updates.push(QuestUpdate::Completed { quest_id: quest.id.clone(), outcome_id });
+
+        let rewards = quest.rewards.iter().chain(outcome.into_iter().flat_map(|outcome| outcome.rewards.iter()));
+        for reward in rewards {
+            let given = reward.apply(&quest.id, self.player, self.story_flags, self.factions, self.items);
+            if let (true, QuestReward::SetFlag(flag)) = (given, reward) {
+                self.pending_events.push_back(QuestEvent::FlagSet(flag.clone()));
+            }
+            self.updates.push(match given {
+                true => QuestUpdate::RewardGiven(reward.clone()),
+                false => QuestUpdate::RewardNotGiven(reward.clone()),
+            });
+        }
+    }
+
+    fn fail(&mut self, quest: &Quest) {
+        if let Some(progress) = self.get_active_progress(&quest.id) {
+            progress.status = QuestStatus::Failed;
+        }
+        self.log.add_journal_entry(&quest.id, JournalEntryKind::Failed, &quest.failure_journal_entry);
+        self.updates.push(QuestUpdate::Failed(quest.id.clone()));
+    }
+
+    fn handle_event(&mut self, event: &QuestEvent) {
+        let quest_ids: Vec<String> = self.log.get_active_quest_ids().into_iter().map(|quest_id| quest_id.to_string()).collect();
+
+        for quest_id in quest_ids {
+            let Ok(quest) = self.registry.get_quest(&quest_id) else {
+                continue;
+            };
+            let Some(progress) = self.log.quests.get_mut(&quest_id) else {
+                continue;
+            };
+            let Some(stage) = quest.get_stage(&progress.stage_id) else {
+                continue;
+            };
+
+            for objective in &stage.objectives {
+                let Some(objective_progress) = progress.objectives.get_mut(&objective.id).filter(|objective| objective.state == ObjectiveState::Active) else {
+                    continue;
+                };
+
+                if let Some(failure) = &objective.fails_on {
+                    objective_progress.failure_count += failure.count_event(event);
+                    if objective_progress.failure_count >= failure.get_required_count() {
+                        objective_progress.state = ObjectiveState::Failed;
+                        self.log.journal.push(JournalEntry { quest_id: quest_id.clone(), kind: JournalEntryKind::ObjectiveFailed(objective.id.clone()), text: objective.description.clone() });
+                        self.updates.push(QuestUpdate::ObjectiveFailed { quest_id: quest_id.clone(), objective_id: objective.id.clone() });
+                        continue;
+                    }
+                }
+
+                let amount = objective.kind.count_event(event);
+                if amount == 0 {
+                    continue;
+                }
+
+                let required = objective.kind.get_required_count();
+                objective_progress.count = (objective_progress.count + amount).min(required);
+                self.updates.push(QuestUpdate::ObjectiveProgressed { quest_id: quest_id.clone(), objective_id: objective.id.clone(), count: objective_progress.count, required });
+
+                if objective_progress.count >= required {
+                    objective_progress.state = ObjectiveState::Completed;
+                    self.log.journal.push(JournalEntry { quest_id: quest_id.clone(), kind: JournalEntryKind::ObjectiveCompleted(objective.id.clone()), text: objective.description.clone() });
+                    self.updates.push(QuestUpdate::ObjectiveCompleted { quest_id: quest_id.clone(), objective_id: objective.id.clone() });
+                }
+            }
+
+            self.settle(quest);
+        }
+    }
+
+    fn handle_pending_events(&mut self) {
+        while let Some(event) = self.pending_events.pop_front() {
+            self.handle_event(&event);
+        }
+    }
+}
+
+/// Runs quests against the player's quest log, which is taken out of the player for the duration
+/// so rewards can be given to the same player.
+fn run_quests<T>(registry: &QuestRegistry, world: &mut StoryWorld, story_tree: &mut PlayerStoryTree, action: impl FnOnce(&mut QuestRun) -> T) -> (T, Vec<QuestUpdate>) {
+    let mut log = std::mem::take(&mut world.player.quests);
+    let mut run = QuestRun {
+        log: &mut log,
+        registry,
+        player: world.player,
+        story_flags: &mut story_tree.story_flags,
+        factions: world.factions,
+        items: world.items,
+        pending_events: VecDeque::new(),
+        updates: Vec::new(),
+    };
+
+    let result = action(&mut run);
+    run.handle_pending_events();
+    let updates = run.updates;
+
+    world.player.quests = log;
+    (result, updates)
+}
+
+pub fn start_quest(quest_id: &str, registry: &QuestRegistry, world: &mut StoryWorld, story_tree: &mut PlayerStoryTree) -> Result<Vec<QuestUpdate>, QuestError> {
+    let (result, updates) = run_quests(registry, world, story_tree, |run| run.start(quest_id));
+    result.map(|_| updates)
+}
+
+/// Moves a quest on from its current stage whatever its objectives say, taking the stage's first branch.
+pub fn advance_quest(quest_id: &str, registry: &QuestRegistry, world: &mut StoryWorld, story_tree: &mut PlayerStoryTree) -> Result<Vec<QuestUpdate>, QuestError> {
+    let (result, updates) = run_quests(registry, world, story_tree, |run| run.advance(quest_id));
+    result.map(|_| updates)
+}
+
+/// Counts an event towards the objectives of every active quest.
+pub fn handle_quest_event(event: &QuestEvent, registry: &QuestRegistry, world: &mut StoryWorld, story_tree: &mut PlayerStoryTree) -> Vec<QuestUpdate> {
+    run_quests(registry, world, story_tree, |run| run.handle_event(event)).1
+}
+
+/// Call after a story choice: starts and advances the quests its effects name, then counts the choice
+/// and any flags it set towards quest objectives. Starting a quest the player already has does nothing.
+/// If any quest can't be started or advanced none of the choice's quest changes are kept.
+pub fn handle_story_choice(choice: &StoryChoice, registry: &QuestRegistry, world: &mut StoryWorld, story_tree: &mut PlayerStoryTree) -> Result<Vec<QuestUpdate>, QuestError> {
+    let snapshot = StorySnapshot::capture(world.player, story_tree);
+
+    let (result, updates) = run_quests(registry, world, story_tree, |run| {
+        for outcome in &choice.outcomes {
+            match outcome {
+                StoryOutcome::QuestStarted(quest_id) if !run.log.quests.contains_key(quest_id) => run.start(quest_id)?,
+                StoryOutcome::QuestAdvanced(quest_id) => run.advance(quest_id)?,
+                StoryOutcome::FlagSet(flag) => run.pending_events.push_back(QuestEvent::FlagSet(flag.clone())),
+                _ => {},
+            }
+        }
+
+        if let Some(next_node_id) = choice.choice.next_node_id {
+            run.pending_events.push_back(QuestEvent::StoryChoice { node_id: choice.choice.node_id, next_node_id });
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => Ok(updates),
+        Err(error) => {
+            snapshot.restore(world.player, story_tree);
+            Err(error)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::models::{StatBlock, StatsEnum};
+    use crate::combat::combat_engine::{Combat, CombatStatus};
+    use crate::combat::combatant::{Combatant, CombatTeam, GridPosition};
+    use crate::combat::controller::AggressiveController;
+    use crate::economy::currency::{Currency, CurrencyRegistry};
+    use crate::economy::vendor::{RestockEntry, Trader, Vendor};
+    use crate::equipment::equipment_manager::{WeaponStats, WeaponType};
+    use crate::factions::faction::FactionRegistry;
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::loot::loot_container::LootContainer;
+    use crate::quests::quest::{QuestError, QuestEvent, QuestRegistry, QuestReward};
+    use crate::quests::quest_log::{advance_quest, handle_quest_event, handle_story_choice, start_quest, JournalEntryKind, QuestState, QuestStatus, QuestUpdate};
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_conditions::StoryCondition;
+    use crate::story_tree::story_effects::StoryWorld;
+    use crate::story_tree::story_management::PlayerStoryTree;
+    use crate::story_tree::story_script::load_story;
+    use crate::utils::rng::SeededRng;
+    use crate::world::game_clock::{GameClock, GameTime};
+
+    const QUESTS: &str = r#"[
+        {
+            "id": "cleanse_the_chapel",
+            "name": "Cleanse the Chapel",
+            "description": "Something stirs beneath the chapel.",
+            "stages": [
+                {
+                    "id": "clear_the_crypt",
+                    "journal_entry": "The priest begged me to clear the crypt.",
+                    "objectives": [
+                        {"id": "ghouls", "description": "Kill 3 ghouls", "kind": {"Kill": {"npc_id": "ghoul", "count": 3}}},
+                        {"id": "candles", "description": "Collect candles", "kind": {"Collect": {"item_id": "candle", "quantity": 2}}, "optional": true},
+                        {"id": "priest", "description": "Keep the priest alive", "kind": {"Reach": "crypt_floor"}, "fails_on": {"Kill": {"npc_id": "priest", "count": 1}}}
+                    ]
+                },
+                {
+                    "id": "judge_the_priest",
+                    "journal_entry": "The priest raised them. What now?",
+                    "objectives": [
+                        {"id": "spare", "description": "Spare him", "kind": {"StoryChoice": {"node_id": 1, "next_node_id": 2}}, "optional": true},
+                        {"id": "kill", "description": "Kill him", "kind": {"Flag": "priest_dead"}, "optional": true}
+                    ],
+                    "branches": [
+                        {"objective_ids": ["spare"], "outcome_id": "mercy"},
+                        {"objective_ids": ["kill"], "outcome_id": "judgement"}
+                    ]
+                }
+            ],
+            "rewards": [{"Experience": 50}],
+            "outcomes": [
+                {"id": "mercy", "journal_entry": "I let him go.", "rewards": [{"Item": {"item_id": "holy_water", "quantity": 1}}, {"SetFlag": "priest_spared"}]},
+                {"id": "judgement", "journal_entry": "He will raise no more."}
+            ],
+            "failure_journal_entry": "The priest is dead and the crypt is lost."
+        },
+        {
+            "id": "rumours",
+            "name": "Rumours",
+            "stages": [
+                {"id": "listen", "journal_entry": "People whisper about the priest."},
+                {"id": "spread", "objectives": [{"id": "told", "description": "Hear that he was spared", "kind": {"Flag": "priest_spared"}}]}
+            ]
+        }
+    ]"#;
+
+    const JUDGEMENT: &str = "\
+== 1 The Priest @start
++ Spare him -> 2
+    ! quest advance rumours
++ Kill him -> 3
+    ! set priest_dead
+
+== 2 Mercy @end
+
+== 3 Judgement @end
+";
+
+    fn create_registries() -> (QuestRegistry, ItemRegistry) {
+        let mut quests = QuestRegistry::new();
+        quests.load_from_json(QUESTS).unwrap();
+        let mut items = ItemRegistry::new();
+        items.register_item(ItemDefinition::new("holy_water", "Holy Water", "Blessed.", ItemCategory::Quest, 0, 1));
+        (quests, items)
+    }
+
+    #[test]
+    fn test_quests_follow_events_and_story_choices() {
+        let (quests, items) = create_registries();
+        let factions = FactionRegistry::new();
+        let mut player = PlayerState::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut story_tree = PlayerStoryTree::new();
+
+        start_quest("cleanse_the_chapel", &quests, &mut world, &mut story_tree).unwrap();
+        start_quest("rumours", &quests, &mut world, &mut story_tree).unwrap();
+        assert_eq!(start_quest("rumours", &quests, &mut world, &mut story_tree), Err(QuestError::AlreadyStarted("rumours".to_string())));
+        assert!(world.player.quests.is_in_state("cleanse_the_chapel", &QuestState::AtStage("clear_the_crypt".to_string())));
+
+        for _ in 0..3 {
+            handle_quest_event(&QuestEvent::Killed("ghoul".to_string()), &quests, &mut world, &mut story_tree);
+        }
+        let progress = world.player.quests.get_progress("cleanse_the_chapel").unwrap();
+        assert_eq!(progress.get_objective("ghouls").unwrap().count, 3);
+        assert_eq!(progress.stage_id, "clear_the_crypt");
+
+        // the candles are optional, reaching the crypt floor is all that's left
+        let updates = handle_quest_event(&QuestEvent::LocationReached("crypt_floor".to_string()), &quests, &mut world, &mut story_tree);
+        assert!(updates.contains(&QuestUpdate::StageReached { quest_id: "cleanse_the_chapel".to_string(), stage_id: "judge_the_priest".to_string() }));
+
+        // sparing the priest completes the quest with mercy, and its flag moves the rumours on once the story has advanced them
+        let graph = load_story(JUDGEMENT).unwrap();
+        graph.start(&mut story_tree).unwrap();
+        let choice = graph.choose_option(&mut story_tree, 0, &mut world, &mut SeededRng::new(1)).unwrap();
+        let updates = handle_story_choice(&choice, &quests, &mut world, &mut story_tree).unwrap();
+
+        assert!(updates.contains(&QuestUpdate::Completed { quest_id: "cleanse_the_chapel".to_string(), outcome_id: Some("mercy".to_string()) }));
+        assert!(updates.contains(&QuestUpdate::RewardGiven(QuestReward::Experience(50))));
+        assert!(world.player.inventory.has_item("holy_water", 1));
+        assert!(story_tree.story_flags.contains("priest_spared"));
+        assert_eq!(world.player.quests.get_status("rumours"), Some(&QuestStatus::Completed(None)));
+        assert!(StoryCondition::Quest { quest_id: "cleanse_the_chapel".to_string(), state: QuestState::CompletedWith("mercy".to_string()) }
+            .is_met(&world.get_context(), &story_tree));
+
+        let journal: Vec<&JournalEntryKind> = world.player.quests.get_journal_for("cleanse_the_chapel").iter().map(|entry| &entry.kind).collect();
+        assert_eq!(journal.first(), Some(&&JournalEntryKind::Started));
+        assert_eq!(journal.last(), Some(&&JournalEntryKind::Completed(Some("mercy".to_string()))));
+        assert_eq!(advance_quest("rumours", &quests, &mut world, &mut story_tree), Err(QuestError::NotActive("rumours".to_string())));
+    }
+
+    #[test]
+    fn test_kills_loot_and_purchases_count_towards_quests() {
+        let (quests, mut items) = create_registries();
+        items.register_item(ItemDefinition::new("candle", "Candle", "Tallow, mostly.", ItemCategory::Material, 10, 10));
+        let factions = FactionRegistry::new();
+        let mut player = PlayerState::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut story_tree = PlayerStoryTree::new();
+        start_quest("cleanse_the_chapel", &quests, &mut world, &mut story_tree).unwrap();
+
+        let mut ghoul_stats = StatBlock::default();
+        ghoul_stats.set_stat(StatsEnum::Strength, 2);
+        let mut combatants = vec![
+            Combatant::from_player(world.player)
+                .with_weapon(WeaponStats::new(&WeaponType::Sword, 10, 16))
+                .with_position(GridPosition::new(0, 0)),
+        ];
+        for y in 0..3 {
+            combatants.push(Combatant::from_npc("Ghoul", CombatTeam::Hostile, &BaseNpcStats::new(1, Some(ghoul_stats)))
+                .with_npc_id("ghoul")
+                .with_position(GridPosition::new(2, y - 1)));
+        }
+        let mut combat = Combat::new(combatants, 5);
+        assert_eq!(combat.run(&mut AggressiveController, 100), CombatStatus::Victory(CombatTeam::Party));
+        for event in combat.get_quest_events() {
+            handle_quest_event(&event, &quests, &mut world, &mut story_tree);
+        }
+
+        let mut corpse = LootContainer::new_corpse("Ghoul", None);
+        let _ = corpse.contents.add_item(items.get_item("candle").unwrap(), 1);
+        for stack in corpse.take_all(&items, &mut world.player.inventory) {
+            handle_quest_event(&QuestEvent::from(&stack), &quests, &mut world, &mut story_tree);
+        }
+
+        let mut currencies = CurrencyRegistry::new();
+        currencies.register_currency(Currency { id: String::from("crown"), name: String::from("Crown"), base_units_per_coin: 10 });
+        let clock = GameClock::new(1, GameTime::new(9, 0));
+        let mut vendor = Vendor::new("Chandler", None, "crown", vec![RestockEntry { item_id: String::from("candle"), quantity: 5 }], 24);
+        vendor.update_stock(&items, &clock);
+        world.player.purse.deposit("crown", 100);
+        let player = &mut *world.player;
+        let mut trader = Trader { inventory: &mut player.inventory, purse: &mut player.purse, charisma: 10, reputation: 0 };
+        let record = vendor.buy(&mut trader, &items, &currencies, "candle", 1, &clock).unwrap();
+        handle_quest_event(&record.to_quest_event().unwrap(), &quests, &mut world, &mut story_tree);
+
+        let progress = world.player.quests.get_progress("cleanse_the_chapel").unwrap();
+        assert_eq!(progress.get_objective("ghouls").unwrap().count, 3);
+        assert_eq!(progress.get_objective("candles").unwrap().count, 2);
+    }
+
+    #[test]
+    fn test_failing_a_required_objective_fails_the_quest() {
+        let (quests, items) = create_registries();
+        let factions = FactionRegistry::new();
+        let mut player = PlayerState::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut story_tree = PlayerStoryTree::new();
+
+        start_quest("cleanse_the_chapel", &quests, &mut world, &mut story_tree).unwrap();
+        let updates = handle_quest_event(&QuestEvent::Killed("priest".to_string()), &quests, &mut world, &mut story_tree);
+        assert_eq!(updates.last(), Some(&QuestUpdate::Failed("cleanse_the_chapel".to_string())));
+        assert!(world.player.quests.is_in_state("cleanse_the_chapel", &QuestState::Failed));
+        assert_eq!(world.player.quests.get_journal().last().unwrap().text, "The priest is dead and the crypt is lost.");
+        assert!(world.player.quests.get_active_quest_ids().is_empty());
+
+        let mut broken = QuestRegistry::new();
+        let error = broken.load_from_json(r#"[{"id": "lost", "name": "Lost", "stages": [{"id": "a", "branches": [{"next_stage_id": "b"}]}]}]"#).unwrap_err();
+        assert_eq!(error, QuestError::InvalidQuest { quest_id: "lost".to_string(), reason: "a branch of stage a leads to missing stage b".to_string() });
+    }
+
+    #[test]
+    fn test_a_failed_story_choice_keeps_no_quest_changes() {
+        let (quests, items) = create_registries();
+        let factions = FactionRegistry::new();
+        let mut player = PlayerState::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut story_tree = PlayerStoryTree::new();
+
+        // the chapel quest was never started, so advancing it fails after the rumours have started
+        let graph = load_story("== 1 Tavern @start\n+ Listen -> 2\n    ! quest start rumours\n    ! quest advance cleanse_the_chapel\n\n== 2 Rumours @end\n").unwrap();
+        graph.start(&mut story_tree).unwrap();
+        let choice = graph.choose_option(&mut story_tree, 0, &mut world, &mut SeededRng::new(1)).unwrap();
+        let before = world.player.clone();
+
+        assert_eq!(handle_story_choice(&choice, &quests, &mut world, &mut story_tree), Err(QuestError::NotActive("cleanse_the_chapel".to_string())));
+        assert_eq!(world.player.quests.get_status("rumours"), None);
+        assert_eq!(*world.player, before);
+    }
+}
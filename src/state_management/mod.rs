@@ -1,2 +1,2 @@
-mod main_game_state;
-mod player_state;
\ No newline at end of file
+pub mod main_game_state;
+pub mod player_state;
\ No newline at end of file
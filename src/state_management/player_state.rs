@@ -1,13 +1,38 @@
+use std::collections::HashSet;
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatValues, StatsEnum};
 use crate::actors::player::PlayerStats;
+use crate::crafting::recipe::{CraftingError, CraftingOutcome, CraftingStation, RecipeBook};
+use crate::dialogue::conversation::ConversationMemory;
+use crate::economy::currency::Purse;
+use crate::factions::reputation::PlayerReputation;
+use crate::inventory::consumables::{ConsumableTarget, ItemUseOutcome};
+use crate::inventory::inventory_container::{InventoryContainer, InventoryError};
+use crate::inventory::item::ItemRegistry;
+use crate::party::roster::Party;
+use crate::quests::quest_log::QuestLog;
+use crate::status_effects::active_effects::{ApplyStatusResult, StatusEffects, StatusEvent};
+use crate::status_effects::status_effect::StatusEffectDefinition;
+use crate::utils::rng::SeededRng;
 
-#[derive(Debug, Eq, PartialEq)]
+const PLAYER_INVENTORY_SLOTS: usize = 40;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PlayerState {
+    pub name: String,
     pub player_stats: PlayerStats,
     pub current_experience: u32,
     pub experience_to_next_level: u32,
+    pub inventory: InventoryContainer,
+    pub purse: Purse,
+    pub status_effects: StatusEffects,
+    pub known_skills: HashSet<String>,
+    pub reputation: PlayerReputation,
+    pub party: Party,
+    pub conversations: ConversationMemory,
+    pub quests: QuestLog,
 }
 
-fn calculate_experience_to_next_level(level: u32) -> u32 {
+pub fn calculate_experience_to_next_level(level: u32) -> u32 {
     match level {
         0..=19 => {
             // Use a smaller scaling factor or a different formula for levels below 20
@@ -26,9 +51,18 @@ impl PlayerState {
         let player_stats = PlayerStats::new();
 
         PlayerState {
+            name: String::new(),
             player_stats,
             current_experience: 0,
             experience_to_next_level: 100,
+            inventory: InventoryContainer::new(Some(PLAYER_INVENTORY_SLOTS)),
+            purse: Purse::new(),
+            status_effects: StatusEffects::new(),
+            known_skills: HashSet::new(),
+            reputation: PlayerReputation::new(),
+            party: Party::new(),
+            conversations: ConversationMemory::new(),
+            quests: QuestLog::new(),
         }
     }
 
@@ -38,11 +72,87 @@ impl PlayerState {
         self.experience_to_next_level = calculate_experience_to_next_level(self.player_stats.level);
     }
 
+    /// Companions in the party get their share of the experience too.
     pub fn gain_experience(&mut self, experience: u32) {
+        self.party.share_experience(experience);
         self.current_experience += experience;
 
         if self.current_experience >= self.experience_to_next_level {
             self.level_up();
         }
     }
+
+    /// The stat with every status effect (including item buffs) applied.
+    pub fn get_effective_stat(&self, stat: StatsEnum) -> i32 {
+        self.status_effects.get_modified_stat(&self.player_stats, stat)
+    }
+
+    /// Uses a consumable from the player's own inventory on the player.
+    pub fn use_item(&mut self, registry: &ItemRegistry, item_id: &str, in_combat: bool) -> Result<Vec<ItemUseOutcome>, InventoryError> {
+        // the inventory is taken out for the duration of the call so the player can be both the owner and the target
+        let mut inventory = std::mem::take(&mut self.inventory);
+        let outcome = inventory.use_item(registry, item_id, self, in_combat);
+        self.inventory = inventory;
+
+        outcome
+    }
+
+    /// Crafts out of the player's own inventory, with buffs counting towards the player's stats.
+    pub fn craft(&mut self, book: &RecipeBook, recipe_id: &str, items: &ItemRegistry, station: Option<CraftingStation>, rng: &mut SeededRng) -> Result<CraftingOutcome, CraftingError> {
+        let mut inventory = std::mem::take(&mut self.inventory);
+        let outcome = book.craft(recipe_id, self, &mut inventory, items, station, rng);
+        self.inventory = inventory;
+
+        outcome
+    }
+
+    pub fn end_turn(&mut self) -> Vec<StatusEvent> {
+        self.inventory.tick_cooldowns();
+        self.status_effects.tick_turn(&mut self.player_stats)
+    }
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        PlayerState::new()
+    }
+}
+
+/// Reads stats with status effects applied, so anything generic over `StatValues` sees the player's buffs.
+impl StatValues for PlayerState {
+    fn get_stat(&self, stat: StatsEnum) -> i32 {
+        self.get_effective_stat(stat)
+    }
+
+    fn set_stat(&mut self, stat: StatsEnum, value: i32) {
+        self.player_stats.set_stat(stat, value);
+    }
+
+    fn temp_modify_stat(&self, stat: StatsEnum, multiplier: f32) -> i32 {
+        (self.get_effective_stat(stat) as f32 * multiplier).floor() as i32
+    }
+}
+
+impl ResourcePoolValues for PlayerState {
+    fn get_resource(&self, pool: ResourcePoolEnum) -> i32 {
+        self.player_stats.get_resource(pool)
+    }
+
+    fn get_resource_limit(&self, pool: ResourcePoolEnum) -> i32 {
+        self.player_stats.get_resource_limit(pool)
+    }
+
+    fn set_resource(&mut self, pool: ResourcePoolEnum, value: i32) {
+        self.player_stats.set_resource(pool, value);
+    }
+}
+
+impl ConsumableTarget for PlayerState {
+    fn apply_status_effect(&mut self, definition: &StatusEffectDefinition) -> ApplyStatusResult {
+        self.status_effects.apply_effect(definition)
+    }
+
+    fn learn_skill(&mut self, skill_id: &str) -> bool {
+        self.known_skills.insert(skill_id.to_string())
+    }
 }
\ No newline at end of file
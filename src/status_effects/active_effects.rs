@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ComputedAttributeEnum, ComputedAttributeValues, ResourcePoolEnum, ResourcePoolValues, StatsEnum, StatValues};
+use crate::status_effects::status_effect::{StackingRule, StatusDuration, StatusEffectDefinition};
+
+// effects that last for game time instead of turns apply their tick changes this often
+const MINUTES_PER_TICK: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveStatusEffect {
+    pub definition: StatusEffectDefinition,
+    pub stacks: u32,
+    pub remaining: StatusDuration,
+    minutes_until_tick: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyStatusResult {
+    Applied,
+    Refreshed,
+    Stacked(u32),
+    AlreadyActive,
+    Immune,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusEvent {
+    Ticked { effect_id: String, pool: ResourcePoolEnum, amount: i32 },
+    Expired(String),
+}
+
+/// The status effects currently held by one actor, along with the effects it is innately immune to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEffects {
+    active: Vec<ActiveStatusEffect>,
+    pub immunities: HashSet<String>,
+}
+
+impl ActiveStatusEffect {
+    fn new(definition: StatusEffectDefinition) -> Self {
+        ActiveStatusEffect {
+            remaining: definition.duration,
+            definition,
+            stacks: 1,
+            minutes_until_tick: MINUTES_PER_TICK,
+        }
+    }
+
+    fn apply_ticks<T: ResourcePoolValues>(&self, target: &mut T, events: &mut Vec<StatusEvent>) {
+        for tick in &self.definition.tick_changes {
+            let amount = target.modify_resource(tick.pool, tick.amount * self.stacks as i32);
+            events.push(StatusEvent::Ticked { effect_id: self.definition.id.clone(), pool: tick.pool, amount });
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        matches!(self.remaining, StatusDuration::Turns(0) | StatusDuration::GameMinutes(0))
+    }
+}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        StatusEffects {
+            active: Vec::new(),
+            immunities: HashSet::new(),
+        }
+    }
+
+    pub fn get_active_effects(&self) -> &[ActiveStatusEffect] {
+        &self.active
+    }
+
+    pub fn has_effect(&self, effect_id: &str) -> bool {
+        self.active
+            .iter()
+            .any(|effect| effect.definition.id == effect_id)
+    }
+
+    pub fn is_immune(&self, effect_id: &str) -> bool {
+        self.immunities.contains(effect_id) || self.active
+            .iter()
+            .any(|effect| effect.definition.grants_immunity_to.iter().any(|id| id == effect_id))
+    }
+
+    pub fn apply_effect(&mut self, definition: &StatusEffectDefinition) -> ApplyStatusResult {
+        if self.is_immune(&definition.id) {
+            return ApplyStatusResult::Immune;
+        }
+
+        let Some(existing) = self.active.iter_mut().find(|effect| effect.definition.id == definition.id) else {
+            // an effect that grants immunity also cleanses what it protects against
+            self.active.retain(|effect| !definition.grants_immunity_to.contains(&effect.definition.id));
+            self.active.push(ActiveStatusEffect::new(definition.clone()));
+            return ApplyStatusResult::Applied;
+        };
+
+        match definition.stacking {
+            StackingRule::Refresh => {
+                existing.remaining = definition.duration;
+                ApplyStatusResult::Refreshed
+            },
+            StackingRule::StackIntensity { max_stacks } => {
+                existing.stacks = (existing.stacks + 1).min(max_stacks.max(1));
+                existing.remaining = definition.duration;
+                ApplyStatusResult::Stacked(existing.stacks)
+            },
+            StackingRule::Unique => ApplyStatusResult::AlreadyActive,
+        }
+    }
+
+    pub fn remove_effect(&mut self, effect_id: &str) -> bool {
+        let count = self.active.len();
+        self.active.retain(|effect| effect.definition.id != effect_id);
+        self.active.len() != count
+    }
+
+    pub fn clear_harmful_effects(&mut self) {
+        self.active.retain(|effect| !effect.definition.is_harmful);
+    }
+
+    /// Applies one turn of tick changes to the target and counts down turn based effects.
+    /// Permanent effects tick every turn too but never run out.
+    pub fn tick_turn<T: ResourcePoolValues>(&mut self, target: &mut T) -> Vec<StatusEvent> {
+        let mut events = Vec::new();
+
+        for effect in self.active.iter_mut() {
+            match effect.remaining {
+                StatusDuration::Turns(turns) => {
+                    effect.apply_ticks(target, &mut events);
+                    effect.remaining = StatusDuration::Turns(turns.saturating_sub(1));
+                },
+                StatusDuration::Permanent => effect.apply_ticks(target, &mut events),
+                StatusDuration::GameMinutes(_) => {},
+            }
+        }
+
+        self.remove_expired(&mut events);
+        events
+    }
+
+    /// Advances effects that last for game time, ticking them once every ten minutes that pass.
+    pub fn advance_time<T: ResourcePoolValues>(&mut self, minutes: u64, target: &mut T) -> Vec<StatusEvent> {
+        let mut events = Vec::new();
+
+        for effect in self.active.iter_mut() {
+            if let StatusDuration::GameMinutes(remaining) = effect.remaining {
+                let elapsed = minutes.min(remaining);
+                let mut minutes_left = elapsed;
+
+                while minutes_left >= effect.minutes_until_tick {
+                    minutes_left -= effect.minutes_until_tick;
+                    effect.minutes_until_tick = MINUTES_PER_TICK;
+                    effect.apply_ticks(target, &mut events);
+                }
+
+                effect.minutes_until_tick -= minutes_left;
+                effect.remaining = StatusDuration::GameMinutes(remaining - elapsed);
+            }
+        }
+
+        self.remove_expired(&mut events);
+        events
+    }
+
+    fn remove_expired(&mut self, events: &mut Vec<StatusEvent>) {
+        for effect in self.active.iter().filter(|effect| effect.is_expired()) {
+            events.push(StatusEvent::Expired(effect.definition.id.clone()));
+        }
+
+        self.active.retain(|effect| !effect.is_expired());
+    }
+
+    pub fn get_stat_bonus(&self, stat: StatsEnum) -> i32 {
+        self.active
+            .iter()
+            .flat_map(|effect| effect.definition.stat_modifiers
+                .iter()
+                .filter(move |modifier| modifier.stat == stat)
+                .map(move |modifier| modifier.amount * effect.stacks as i32))
+            .sum()
+    }
+
+    pub fn get_attribute_bonus(&self, attribute: ComputedAttributeEnum) -> i32 {
+        self.active
+            .iter()
+            .flat_map(|effect| effect.definition.attribute_modifiers
+                .iter()
+                .filter(move |modifier| modifier.attribute == attribute)
+                .map(move |modifier| modifier.amount * effect.stacks as i32))
+            .sum()
+    }
+
+    pub fn get_modified_stat<T: StatValues>(&self, stats: &T, stat: StatsEnum) -> i32 {
+        stats.get_stat(stat) + self.get_stat_bonus(stat)
+    }
+
+    pub fn get_modified_attribute<T: ComputedAttributeValues>(&self, attributes: &T, attribute: ComputedAttributeEnum) -> i32 {
+        attributes.get_computed_attribute(attribute) + self.get_attribute_bonus(attribute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::base_npc::BaseNpcStats;
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+    use crate::status_effects::active_effects::{ApplyStatusResult, StatusEffects, StatusEvent};
+    use crate::status_effects::status_effect::{ResourceTick, StackingRule, StatusDuration, StatusEffectDefinition, StatusStatModifier};
+
+    fn bleeding() -> StatusEffectDefinition {
+        StatusEffectDefinition {
+            id: String::from("bleeding"),
+            name: String::from("Bleeding"),
+            is_harmful: true,
+            duration: StatusDuration::Turns(2),
+            stacking: StackingRule::StackIntensity { max_stacks: 3 },
+            tick_changes: vec![ResourceTick { pool: ResourcePoolEnum::Health, amount: -5 }],
+            stat_modifiers: vec![StatusStatModifier { stat: StatsEnum::Dexterity, amount: -1 }],
+            attribute_modifiers: Vec::new(),
+            grants_immunity_to: Vec::new(),
+        }
+    }
+
+    fn blessed() -> StatusEffectDefinition {
+        StatusEffectDefinition {
+            id: String::from("blessed"),
+            name: String::from("Blessed"),
+            is_harmful: false,
+            duration: StatusDuration::GameMinutes(30),
+            stacking: StackingRule::Unique,
+            tick_changes: vec![ResourceTick { pool: ResourcePoolEnum::Health, amount: 2 }],
+            stat_modifiers: Vec::new(),
+            attribute_modifiers: Vec::new(),
+            grants_immunity_to: vec![String::from("bleeding")],
+        }
+    }
+
+    #[test]
+    fn test_stacking_ticks_and_expiry() {
+        let mut npc = BaseNpcStats::default();
+        let mut effects = StatusEffects::new();
+
+        assert_eq!(effects.apply_effect(&bleeding()), ApplyStatusResult::Applied);
+        assert_eq!(effects.apply_effect(&bleeding()), ApplyStatusResult::Stacked(2));
+        assert_eq!(effects.get_modified_stat(&npc, StatsEnum::Dexterity), 8);
+
+        let health = npc.get_resource(ResourcePoolEnum::Health);
+        effects.tick_turn(&mut npc);
+        assert_eq!(npc.get_resource(ResourcePoolEnum::Health), health - 10);
+
+        let events = effects.tick_turn(&mut npc);
+        assert_eq!(events.last(), Some(&StatusEvent::Expired(String::from("bleeding"))));
+        assert!(!effects.has_effect("bleeding"));
+        assert_eq!(npc.get_resource(ResourcePoolEnum::Health), health - 20);
+    }
+
+    #[test]
+    fn test_immunity_and_game_time_duration() {
+        let mut npc = BaseNpcStats::default();
+        npc.set_resource(ResourcePoolEnum::Health, 50);
+        let mut effects = StatusEffects::new();
+
+        effects.apply_effect(&bleeding());
+        assert_eq!(effects.apply_effect(&blessed()), ApplyStatusResult::Applied);
+        assert!(!effects.has_effect("bleeding"));
+        assert_eq!(effects.apply_effect(&bleeding()), ApplyStatusResult::Immune);
+        assert_eq!(effects.apply_effect(&blessed()), ApplyStatusResult::AlreadyActive);
+
+        effects.advance_time(25, &mut npc);
+        assert_eq!(npc.get_resource(ResourcePoolEnum::Health), 54);
+        effects.advance_time(5, &mut npc);
+        assert_eq!(npc.get_resource(ResourcePoolEnum::Health), 56);
+        assert!(!effects.has_effect("blessed"));
+    }
+
+    #[test]
+    fn test_permanent_effects_tick_without_expiring() {
+        let mut npc = BaseNpcStats::default();
+        let mut effects = StatusEffects::new();
+        let curse = StatusEffectDefinition {
+            id: String::from("eldritch_touched"),
+            name: String::from("Eldritch Touched"),
+            duration: StatusDuration::Permanent,
+            stacking: StackingRule::Unique,
+            tick_changes: vec![ResourceTick { pool: ResourcePoolEnum::Madness, amount: 3 }],
+            ..bleeding()
+        };
+
+        effects.apply_effect(&curse);
+        for _ in 0..4 {
+            effects.tick_turn(&mut npc);
+        }
+        assert_eq!(npc.get_resource(ResourcePoolEnum::Madness), 12);
+        assert!(effects.has_effect("eldritch_touched"));
+    }
+}
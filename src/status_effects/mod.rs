@@ -0,0 +1,2 @@
+pub mod status_effect;
+pub mod active_effects;
\ No newline at end of file
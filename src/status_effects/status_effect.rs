@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ComputedAttributeEnum, ResourcePoolEnum, StatsEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusDuration {
+    Turns(u32),
+    GameMinutes(u64),
+    Permanent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StackingRule {
+    // reapplying resets the duration
+    Refresh,
+    // reapplying adds a stack (up to the max) and resets the duration, tick and modifier values scale with stacks
+    StackIntensity { max_stacks: u32 },
+    // reapplying while active does nothing
+    Unique,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResourceTick {
+    pub pool: ResourcePoolEnum,
+    // negative values damage, positive values heal
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatusStatModifier {
+    pub stat: StatsEnum,
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatusAttributeModifier {
+    pub attribute: ComputedAttributeEnum,
+    pub amount: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatusEffectDefinition {
+    pub id: String,
+    pub name: String,
+    pub is_harmful: bool,
+    pub duration: StatusDuration,
+    pub stacking: StackingRule,
+    pub tick_changes: Vec<ResourceTick>,
+    pub stat_modifiers: Vec<StatusStatModifier>,
+    pub attribute_modifiers: Vec<StatusAttributeModifier>,
+    // ids of other effects that can't be applied while this one is active
+    pub grants_immunity_to: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct StatusEffectRegistry {
+    effects: HashMap<String, StatusEffectDefinition>,
+}
+
+impl StatusEffectRegistry {
+    pub fn new() -> Self {
+        StatusEffectRegistry {
+            effects: HashMap::new(),
+        }
+    }
+
+    pub fn register_effect(&mut self, effect: StatusEffectDefinition) {
+        self.effects.insert(effect.id.clone(), effect);
+    }
+
+    pub fn get_effect(&self, effect_id: &str) -> Option<&StatusEffectDefinition> {
+        self.effects.get(effect_id)
+    }
+}
@@ -1 +1,10 @@
-mod story_management;
\ No newline at end of file
+pub mod story_management;
+pub mod story_graph;
+pub mod story_validation;
+pub mod story_conditions;
+pub mod story_effects;
+pub mod story_script;
+pub mod story_variables;
+pub mod story_text;
+pub mod story_history;
+pub mod story_export;
\ No newline at end of file
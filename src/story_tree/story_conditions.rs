@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{MadnessTier, ResourcePoolValues, StatsEnum};
+use crate::factions::faction::FactionRegistry;
+use crate::factions::reputation::ReputationRequirement;
+use crate::quests::quest_log::QuestState;
+use crate::state_management::player_state::PlayerState;
+use crate::story_tree::story_management::PlayerStoryTree;
+use crate::utils::rng::SeededRng;
+
+// stat checks roll this die and add the stat to it
+const STAT_CHECK_DIE: u32 = 20;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoryCondition {
+    StatAtLeast { stat: StatsEnum, value: i32 },
+    // the stat plus a d20 has to reach the difficulty, rolled when the option is chosen
+    StatCheck { stat: StatsEnum, difficulty: i32 },
+    HasItem { item_id: String, quantity: u32 },
+    Reputation(ReputationRequirement),
+    MadnessAtLeast(MadnessTier),
+    MadnessAtMost(MadnessTier),
+    VisitedNode(u32),
+    // the player picked the option on `node_id` that leads to `next_node_id`
+    ChoseOption { node_id: u32, next_node_id: u32 },
+    FlagSet(String),
+    Quest { quest_id: String, state: QuestState },
+    All(Vec<StoryCondition>),
+    Any(Vec<StoryCondition>),
+    Not(Box<StoryCondition>),
+}
+
+/// The state story conditions are checked against, other than the player's own progress through the story.
+pub struct StoryContext<'a> {
+    pub player: &'a PlayerState,
+    pub factions: &'a FactionRegistry,
+}
+
+impl StoryCondition {
+    /// A short description of what the condition asks for, shown as the reason an option is greyed out.
+    pub fn describe(&self) -> String {
+        match self {
+            StoryCondition::StatAtLeast { stat, value } => format!("{:?} {}", stat, value),
+            StoryCondition::StatCheck { stat, difficulty } => format!("{:?} check (DC {})", stat, difficulty),
+            StoryCondition::HasItem { item_id, quantity } => format!("{} x {}", quantity, item_id),
+            StoryCondition::Reputation(requirement) => format!("{:?} with {}", requirement.minimum_tier, requirement.faction_id),
+            StoryCondition::MadnessAtLeast(tier) => format!("at least {:?}", tier),
+            StoryCondition::MadnessAtMost(tier) => format!("no worse than {:?}", tier),
+            StoryCondition::VisitedNode(node_id) => format!("visited node {}", node_id),
+            StoryCondition::ChoseOption { node_id, next_node_id } => format!("chose {} on node {}", next_node_id, node_id),
+            StoryCondition::FlagSet(flag) => flag.clone(),
+            StoryCondition::Quest { quest_id, state } => format!("{} {:?}", quest_id, state),
+            StoryCondition::All(conditions) => Self::describe_all(conditions, " and "),
+            StoryCondition::Any(conditions) => Self::describe_all(conditions, " or "),
+            StoryCondition::Not(condition) => format!("not {}", condition.describe()),
+        }
+    }
+
+    fn describe_all(conditions: &[StoryCondition], separator: &str) -> String {
+        let descriptions: Vec<String> = conditions.iter().map(|condition| condition.describe()).collect();
+        format!("({})", descriptions.join(separator))
+    }
+
+    /// Checks the condition, returning the reason it failed. Stat checks are only rolled when given an rng,
+    /// otherwise they are undecided and the condition passes if some roll could pass it, so the option can be
+    /// offered before the player commits to it.
+    pub fn evaluate(&self, context: &StoryContext, story_tree: &PlayerStoryTree, rng: Option<&mut SeededRng>) -> Result<(), String> {
+        self.evaluate_assuming(context, story_tree, rng, true)
+    }
+
+    // `checks_pass` is what an unrolled stat check counts as, flipped under a `Not` so a negated check
+    // is assumed to fail and the `Not` can still pass
+    fn evaluate_assuming(&self, context: &StoryContext, story_tree: &PlayerStoryTree, mut rng: Option<&mut SeededRng>, checks_pass: bool) -> Result<(), String> {
+        let passed = match self {
+            StoryCondition::StatAtLeast { stat, value } => context.player.get_effective_stat(*stat) >= *value,
+            StoryCondition::StatCheck { stat, difficulty } => match rng {
+                Some(rng) => context.player.get_effective_stat(*stat) + rng.roll_die(STAT_CHECK_DIE) as i32 >= *difficulty,
+                None => checks_pass,
+            },
+            StoryCondition::HasItem { item_id, quantity } => context.player.inventory.has_item(item_id, *quantity),
+            StoryCondition::Reputation(requirement) => context.player.reputation.meets_requirement(requirement, context.factions),
+            StoryCondition::MadnessAtLeast(tier) => context.player.get_madness_tier() >= *tier,
+            StoryCondition::MadnessAtMost(tier) => context.player.get_madness_tier() <= *tier,
+            StoryCondition::VisitedNode(node_id) => story_tree.get_story_node(*node_id).is_some(),
+            StoryCondition::ChoseOption { node_id, next_node_id } => story_tree
+                .get_past_node_choice(*node_id)
+                .is_some_and(|option| option.next_node_id == *next_node_id),
+            StoryCondition::FlagSet(flag) => story_tree.story_flags.contains(flag),
+            StoryCondition::Quest { quest_id, state } => context.player.quests.is_in_state(quest_id, state),
+            StoryCondition::All(conditions) => {
+                return conditions
+                    .iter()
+                    .try_for_each(|condition| condition.evaluate_assuming(context, story_tree, rng.as_deref_mut(), checks_pass));
+            }
+            StoryCondition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.evaluate_assuming(context, story_tree, rng.as_deref_mut(), checks_pass).is_ok()),
+            StoryCondition::Not(condition) => condition.evaluate_assuming(context, story_tree, rng, !checks_pass).is_err(),
+        };
+
+        match passed {
+            true => Ok(()),
+            false => Err(self.describe()),
+        }
+    }
+
+    pub fn is_met(&self, context: &StoryContext, story_tree: &PlayerStoryTree) -> bool {
+        self.evaluate(context, story_tree, None).is_ok()
+    }
+
+    pub fn has_stat_check(&self) -> bool {
+        match self {
+            StoryCondition::StatCheck { .. } => true,
+            StoryCondition::All(conditions) | StoryCondition::Any(conditions) => conditions.iter().any(|condition| condition.has_stat_check()),
+            StoryCondition::Not(condition) => condition.has_stat_check(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{MadnessTier, ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+    use crate::factions::faction::FactionRegistry;
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_conditions::{StoryCondition, StoryContext};
+    use crate::story_tree::story_management::{PlayerStoryTree, StoryNode, StoryOption};
+    use crate::utils::rng::SeededRng;
+
+    #[test]
+    fn test_conditions_combine() {
+        let mut player = PlayerState::new();
+        player.modify_resource(ResourcePoolEnum::Madness, 1000);
+        let factions = FactionRegistry::new();
+        let context = StoryContext { player: &player, factions: &factions };
+
+        let mut story_tree = PlayerStoryTree::new();
+        story_tree.story_flags.insert("met_the_priest".to_string());
+        story_tree.add_past_node_choice(StoryNode::new(1, String::new(), String::new(), vec![], false, true), StoryOption::new(3, "Lie"));
+
+        let strong = StoryCondition::StatAtLeast { stat: StatsEnum::Strength, value: 30 };
+        let lied = StoryCondition::ChoseOption { node_id: 1, next_node_id: 3 };
+        assert!(!strong.is_met(&context, &story_tree));
+        assert!(lied.is_met(&context, &story_tree));
+        assert!(StoryCondition::MadnessAtLeast(MadnessTier::Broken).is_met(&context, &story_tree));
+
+        let either = StoryCondition::Any(vec![strong.clone(), StoryCondition::FlagSet("met_the_priest".to_string())]);
+        assert!(either.is_met(&context, &story_tree));
+
+        let both = StoryCondition::All(vec![lied, StoryCondition::Not(Box::new(strong.clone()))]);
+        assert!(both.is_met(&context, &story_tree));
+
+        let locked = StoryCondition::All(vec![either, strong]);
+        assert_eq!(locked.evaluate(&context, &story_tree, None), Err("Strength 30".to_string()));
+    }
+
+    #[test]
+    fn test_stat_checks_roll_only_when_chosen() {
+        let player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let context = StoryContext { player: &player, factions: &factions };
+        let story_tree = PlayerStoryTree::new();
+
+        let impossible = StoryCondition::StatCheck { stat: StatsEnum::Wisdom, difficulty: 100 };
+        assert!(impossible.is_met(&context, &story_tree));
+        assert!(impossible.evaluate(&context, &story_tree, Some(&mut SeededRng::new(7))).is_err());
+
+        let easy = StoryCondition::StatCheck { stat: StatsEnum::Wisdom, difficulty: 2 };
+        assert!(easy.evaluate(&context, &story_tree, Some(&mut SeededRng::new(7))).is_ok());
+
+        // failing a check is just as possible before the roll, so a negated check is offered too
+        let failed = StoryCondition::Not(Box::new(impossible.clone()));
+        assert!(failed.is_met(&context, &story_tree));
+        assert!(failed.evaluate(&context, &story_tree, Some(&mut SeededRng::new(7))).is_ok());
+        assert!(StoryCondition::Not(Box::new(StoryCondition::Not(Box::new(impossible)))).is_met(&context, &story_tree));
+
+        // unless something else already decides it
+        let certain = StoryCondition::Not(Box::new(StoryCondition::Any(vec![easy, StoryCondition::StatAtLeast { stat: StatsEnum::Wisdom, value: 1 }])));
+        assert!(!certain.is_met(&context, &story_tree));
+    }
+}
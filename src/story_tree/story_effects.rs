@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+use crate::factions::faction::FactionRegistry;
+use crate::factions::reputation::ReputationChange;
+use crate::inventory::inventory_container::InventoryError;
+use crate::inventory::item::ItemRegistry;
+use crate::state_management::player_state::PlayerState;
+use crate::story_tree::story_conditions::StoryContext;
+use crate::story_tree::story_management::PlayerStoryTree;
+use crate::story_tree::story_variables::StoryValue;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoryEffect {
+    GainExperience(u32),
+    AddItem { item_id: String, quantity: u32 },
+    RemoveItem { item_id: String, quantity: u32 },
+    SetFlag(String),
+    ClearFlag(String),
+    // variable names are paths such as `npc.blacksmith.trust`, see `VariableScope::parse_path`
+    SetVariable { name: String, value: StoryValue },
+    ChangeVariable { name: String, amount: i32 },
+    ChangeReputation { faction_id: String, amount: i32 },
+    // negative amounts cure madness, positive amounts add to it
+    ModifyMadness(i32),
+    // combat and quests are run by the game, so these are handed back to the caller as outcomes
+    StartCombat { encounter_id: String },
+    StartQuest(String),
+    AdvanceQuest(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoryOutcome {
+    ExperienceGained(u32),
+    ItemAdded { item_id: String, quantity: u32 },
+    ItemRemoved { item_id: String, quantity: u32 },
+    FlagSet(String),
+    FlagCleared(String),
+    VariableSet { name: String, value: StoryValue },
+    ReputationChanged(Vec<ReputationChange>),
+    ResourceChanged { pool: ResourcePoolEnum, amount: i32 },
+    CombatStarted(String),
+    QuestStarted(String),
+    QuestAdvanced(String),
+}
+
+/// Everything a story choice can change, along with the registries its effects are looked up in.
+pub struct StoryWorld<'a> {
+    pub player: &'a mut PlayerState,
+    pub factions: &'a FactionRegistry,
+    pub items: &'a ItemRegistry,
+}
+
+impl StoryWorld<'_> {
+    pub fn get_context(&self) -> StoryContext<'_> {
+        StoryContext {
+            player: self.player,
+            factions: self.factions,
+        }
+    }
+}
+
+impl StoryEffect {
+    /// Applies the effect for a choice made on the given node. Failed effects can leave earlier changes
+    /// behind, use `apply_effects` to apply a whole list or none of it.
+    pub fn apply(&self, node_id: u32, player: &mut PlayerState, story_tree: &mut PlayerStoryTree, factions: &FactionRegistry, items: &ItemRegistry) -> Result<StoryOutcome, InventoryError> {
+        let outcome = match self {
+            StoryEffect::GainExperience(experience) => {
+                player.gain_experience(*experience);
+                StoryOutcome::ExperienceGained(*experience)
+            },
+            StoryEffect::AddItem { item_id, quantity } => {
+                let item = items
+                    .get_item(item_id)
+                    .ok_or_else(|| InventoryError::UnknownItem(item_id.clone()))?;
+                player.inventory.add_item(item, *quantity)?;
+                StoryOutcome::ItemAdded { item_id: item_id.clone(), quantity: *quantity }
+            },
+            StoryEffect::RemoveItem { item_id, quantity } => {
+                player.inventory.remove_item(item_id, *quantity)?;
+                StoryOutcome::ItemRemoved { item_id: item_id.clone(), quantity: *quantity }
+            },
+            StoryEffect::SetFlag(flag) => {
+                story_tree.story_flags.insert(flag.clone());
+                StoryOutcome::FlagSet(flag.clone())
+            },
+            StoryEffect::ClearFlag(flag) => {
+                story_tree.story_flags.remove(flag);
+                StoryOutcome::FlagCleared(flag.clone())
+            },
+            StoryEffect::SetVariable { name, value } => {
+                story_tree.variables.set_by_path(name, value.clone());
+                StoryOutcome::VariableSet { name: name.clone(), value: value.clone() }
+            },
+            StoryEffect::ChangeVariable { name, amount } => {
+                let value = story_tree.variables.add_by_path(name, *amount);
+                StoryOutcome::VariableSet { name: name.clone(), value: StoryValue::Int(value) }
+            },
+            StoryEffect::ChangeReputation { faction_id, amount } => {
+                StoryOutcome::ReputationChanged(player.reputation.record_story_choice(node_id, faction_id, *amount, factions))
+            },
+            StoryEffect::ModifyMadness(amount) => {
+                let applied = player.modify_resource(ResourcePoolEnum::Madness, *amount);
+                StoryOutcome::ResourceChanged { pool: ResourcePoolEnum::Madness, amount: applied }
+            },
+            StoryEffect::StartCombat { encounter_id } => StoryOutcome::CombatStarted(encounter_id.clone()),
+            StoryEffect::StartQuest(quest_id) => StoryOutcome::QuestStarted(quest_id.clone()),
+            StoryEffect::AdvanceQuest(quest_id) => StoryOutcome::QuestAdvanced(quest_id.clone()),
+        };
+
+        Ok(outcome)
+    }
+}
+
+/// Applies every effect or, if any of them fails, none of them.
+pub fn apply_effects(effects: &[StoryEffect], node_id: u32, world: &mut StoryWorld, story_tree: &mut PlayerStoryTree) -> Result<Vec<StoryOutcome>, InventoryError> {
+    // the effects are applied to copies which only replace the originals once they have all succeeded
+    let mut player = world.player.clone();
+    let mut updated_tree = story_tree.clone();
+
+    let outcomes = effects
+        .iter()
+        .map(|effect| effect.apply(node_id, &mut player, &mut updated_tree, world.factions, world.items))
+        .collect::<Result<Vec<StoryOutcome>, InventoryError>>()?;
+
+    *world.player = player;
+    *story_tree = updated_tree;
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+    use crate::factions::faction::FactionRegistry;
+    use crate::inventory::inventory_container::InventoryError;
+    use crate::inventory::item::{ItemCategory, ItemDefinition, ItemRegistry};
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_effects::{apply_effects, StoryEffect, StoryOutcome, StoryWorld};
+    use crate::story_tree::story_management::PlayerStoryTree;
+
+    #[test]
+    fn test_effects_apply_all_or_nothing() {
+        let mut items = ItemRegistry::new();
+        items.register_item(ItemDefinition::new("chapel_key", "Chapel Key", "An iron key.", ItemCategory::Quest, 0, 1));
+        let factions = FactionRegistry::new();
+        let mut player = PlayerState::new();
+        let mut story_tree = PlayerStoryTree::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+
+        let effects = vec![
+            StoryEffect::AddItem { item_id: "chapel_key".to_string(), quantity: 1 },
+            StoryEffect::SetFlag("has_chapel_key".to_string()),
+            StoryEffect::ModifyMadness(10),
+            StoryEffect::StartQuest("cleanse_the_chapel".to_string()),
+        ];
+        let outcomes = apply_effects(&effects, 1, &mut world, &mut story_tree).unwrap();
+        assert_eq!(outcomes.len(), 4);
+        assert_eq!(outcomes[3], StoryOutcome::QuestStarted("cleanse_the_chapel".to_string()));
+        assert!(world.player.inventory.has_item("chapel_key", 1));
+        assert!(story_tree.story_flags.contains("has_chapel_key"));
+
+        // the second key can't be removed, so the first one stays and the flag is kept
+        let effects = vec![
+            StoryEffect::RemoveItem { item_id: "chapel_key".to_string(), quantity: 1 },
+            StoryEffect::ClearFlag("has_chapel_key".to_string()),
+            StoryEffect::RemoveItem { item_id: "chapel_key".to_string(), quantity: 1 },
+        ];
+        assert_eq!(
+            apply_effects(&effects, 2, &mut world, &mut story_tree),
+            Err(InventoryError::NotEnoughItems { item_id: "chapel_key".to_string(), requested: 1, available: 0 })
+        );
+        assert!(world.player.inventory.has_item("chapel_key", 1));
+        assert!(story_tree.story_flags.contains("has_chapel_key"));
+        assert_eq!(world.player.get_resource(ResourcePoolEnum::Madness), 10);
+    }
+}
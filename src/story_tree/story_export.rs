@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use crate::story_tree::story_graph::StoryGraph;
+use crate::story_tree::story_history::RecordedChoice;
+use crate::story_tree::story_management::{PlayerStoryTree, StoryNode, StoryOption};
+
+// colours for the route a player took
+const ROUTE_COLOUR: &str = "#d97706";
+const ROUTE_FILL: &str = "#fde68a";
+
+/// One option drawn as an edge. An option with a fallback for a failed stat check is drawn twice.
+struct StoryEdge<'a> {
+    from_node_id: u32,
+    to_node_id: u32,
+    option: &'a StoryOption,
+    is_failure: bool,
+    is_taken: bool,
+}
+
+impl StoryEdge<'_> {
+    fn get_label(&self) -> String {
+        let mut label = self.option.text.clone();
+        if !self.option.conditions.is_empty() {
+            let conditions: Vec<String> = self.option.conditions.iter().map(|condition| condition.describe()).collect();
+            label = format!("{} [{}]", label, conditions.join(" and "));
+        }
+        match self.is_failure {
+            true => format!("{} (failed)", label),
+            false => label,
+        }
+    }
+
+    fn is_conditional(&self) -> bool {
+        !self.option.conditions.is_empty() || self.is_failure
+    }
+}
+
+/// Draws a story graph as a Graphviz DOT or Mermaid diagram for writers to look over. Start nodes are green,
+/// end nodes grey, options with conditions are dashed and a player's route can be highlighted on top.
+pub struct StoryExport<'a> {
+    graph: &'a StoryGraph,
+    route: Vec<RecordedChoice>,
+}
+
+impl<'a> StoryExport<'a> {
+    pub fn new(graph: &'a StoryGraph) -> Self {
+        StoryExport {
+            graph,
+            route: Vec::new(),
+        }
+    }
+
+    /// Highlights the route the player took through the story.
+    pub fn with_player_path(self, story_tree: &PlayerStoryTree) -> Self {
+        self.with_route(story_tree.get_recorded_choices())
+    }
+
+    pub fn with_route(mut self, route: Vec<RecordedChoice>) -> Self {
+        self.route = route;
+        self
+    }
+
+    fn get_visited_node_ids(&self) -> HashSet<u32> {
+        self.route
+            .iter()
+            .flat_map(|choice| std::iter::once(choice.node_id).chain(choice.next_node_id))
+            .collect()
+    }
+
+    fn get_edges(&self) -> Vec<StoryEdge<'a>> {
+        let taken: HashSet<(u32, usize, u32)> = self.route
+            .iter()
+            .filter_map(|choice| choice.next_node_id.map(|next_node_id| (choice.node_id, choice.option_index, next_node_id)))
+            .collect();
+
+        let mut edges = Vec::new();
+        for node in self.graph.get_nodes() {
+            for (option_index, option) in node.options.iter().enumerate() {
+                let destinations = std::iter::once((option.next_node_id, false)).chain(option.failure_node_id.map(|node_id| (node_id, true)));
+
+                for (to_node_id, is_failure) in destinations {
+                    edges.push(StoryEdge {
+                        from_node_id: node.id,
+                        to_node_id,
+                        option,
+                        is_failure,
+                        is_taken: taken.contains(&(node.id, option_index, to_node_id)),
+                    });
+                }
+            }
+        }
+        edges
+    }
+
+    pub fn to_dot(&self) -> String {
+        let visited_node_ids = self.get_visited_node_ids();
+        let mut lines = vec![
+            "digraph story {".to_string(),
+            "    rankdir=LR;".to_string(),
+            "    node [shape=box, style=\"rounded,filled\", fillcolor=\"white\"];".to_string(),
+        ];
+
+        for node in self.graph.get_nodes() {
+            let mut attributes = vec![format!("label=\"{}\"", escape_dot(&get_node_label(node)))];
+            match (node.is_start_node, node.is_end_node) {
+                (true, _) => attributes.push("fillcolor=\"palegreen\"".to_string()),
+                (false, true) => attributes.push("fillcolor=\"lightgrey\", peripheries=2".to_string()),
+                (false, false) => {},
+            }
+            if visited_node_ids.contains(&node.id) {
+                attributes.push(format!("color=\"{}\", penwidth=3", ROUTE_COLOUR));
+            }
+            lines.push(format!("    n{} [{}];", node.id, attributes.join(", ")));
+        }
+
+        for edge in self.get_edges() {
+            let mut attributes = vec![format!("label=\"{}\"", escape_dot(&edge.get_label()))];
+            if edge.is_conditional() {
+                attributes.push("style=dashed".to_string());
+            }
+            if edge.is_taken {
+                attributes.push(format!("color=\"{}\", penwidth=3", ROUTE_COLOUR));
+            }
+            lines.push(format!("    n{} -> n{} [{}];", edge.from_node_id, edge.to_node_id, attributes.join(", ")));
+        }
+
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let visited_node_ids = self.get_visited_node_ids();
+        let mut lines = vec!["flowchart LR".to_string()];
+
+        for node in self.graph.get_nodes() {
+            let label = escape_mermaid(&get_node_label(node));
+            lines.push(match (node.is_start_node, node.is_end_node) {
+                (true, _) => format!("    n{}([\"{}\"]):::start", node.id, label),
+                (false, true) => format!("    n{}(((\"{}\"))):::ending", node.id, label),
+                (false, false) => format!("    n{}[\"{}\"]", node.id, label),
+            });
+        }
+
+        let mut taken_edge_indices = Vec::new();
+        for (index, edge) in self.get_edges().iter().enumerate() {
+            let arrow = match edge.is_conditional() {
+                true => "-.->",
+                false => "-->",
+            };
+            lines.push(format!("    n{} {}|\"{}\"| n{}", edge.from_node_id, arrow, escape_mermaid(&edge.get_label()), edge.to_node_id));
+            if edge.is_taken {
+                taken_edge_indices.push(index.to_string());
+            }
+        }
+
+        lines.push("    classDef start fill:#bbf7d0".to_string());
+        lines.push("    classDef ending fill:#e5e7eb".to_string());
+        if !visited_node_ids.is_empty() {
+            let mut visited: Vec<u32> = visited_node_ids.into_iter().collect();
+            visited.sort();
+            let visited: Vec<String> = visited.iter().map(|node_id| format!("n{}", node_id)).collect();
+            lines.push(format!("    classDef visited stroke:{},stroke-width:3px,fill:{}", ROUTE_COLOUR, ROUTE_FILL));
+            lines.push(format!("    class {} visited", visited.join(",")));
+        }
+        if !taken_edge_indices.is_empty() {
+            lines.push(format!("    linkStyle {} stroke:{},stroke-width:3px", taken_edge_indices.join(","), ROUTE_COLOUR));
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn get_node_label(node: &StoryNode) -> String {
+    match node.title.is_empty() {
+        true => node.id.to_string(),
+        false => format!("{}: {}", node.id, node.title),
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('\n', "<br/>")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::story_tree::story_export::StoryExport;
+    use crate::story_tree::story_history::RecordedChoice;
+    use crate::story_tree::story_script::load_story;
+
+    const DOOR: &str = "\
+== 1 The \"Door\" @start
++ Force it -> 2 else 3
+    ? check strength 15
++ Knock -> 2
+
+== 2 Inside @end
+
+== 3 Bruised @end
+";
+
+    #[test]
+    fn test_dot_export() {
+        let graph = load_story(DOOR).unwrap();
+        let route = vec![RecordedChoice { node_id: 1, option_index: 0, next_node_id: Some(3), after_jump: false }];
+        let dot = StoryExport::new(&graph).with_route(route).to_dot();
+
+        assert!(dot.starts_with("digraph story {"));
+        assert!(dot.contains("n1 [label=\"1: The \\\"Door\\\"\", fillcolor=\"palegreen\", color=\"#d97706\", penwidth=3];"));
+        assert!(dot.contains("n2 [label=\"2: Inside\", fillcolor=\"lightgrey\", peripheries=2];"));
+        assert!(dot.contains("n1 -> n2 [label=\"Force it [Strength check (DC 15)]\", style=dashed];"));
+        assert!(dot.contains("n1 -> n3 [label=\"Force it [Strength check (DC 15)] (failed)\", style=dashed, color=\"#d97706\", penwidth=3];"));
+        assert!(dot.contains("n1 -> n2 [label=\"Knock\"];"));
+    }
+
+    #[test]
+    fn test_mermaid_export() {
+        let graph = load_story(DOOR).unwrap();
+        let mermaid = StoryExport::new(&graph).to_mermaid();
+
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains("n1([\"1: The #quot;Door#quot;\"]):::start"));
+        assert!(mermaid.contains("n3(((\"3: Bruised\"))):::ending"));
+        assert!(mermaid.contains("n1 -.->|\"Force it [Strength check (DC 15)] (failed)\"| n3"));
+        assert!(mermaid.contains("n1 -->|\"Knock\"| n2"));
+        assert!(!mermaid.contains("visited"));
+
+        let route = vec![RecordedChoice { node_id: 1, option_index: 1, next_node_id: Some(2), after_jump: false }];
+        let mermaid = StoryExport::new(&graph).with_route(route).to_mermaid();
+        assert!(mermaid.contains("class n1,n2 visited"));
+        assert!(mermaid.contains("linkStyle 2 stroke:#d97706,stroke-width:3px"));
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use thiserror::Error;
+use crate::inventory::inventory_container::InventoryError;
+use crate::story_tree::story_conditions::StoryContext;
+use crate::story_tree::story_effects::{apply_effects, StoryOutcome, StoryWorld};
+use crate::story_tree::story_history::{RecordedChoice, StoryHistoryEntry, StorySnapshot};
+use crate::story_tree::story_management::{PlayerStoryTree, StoryNode, StoryOption};
+use crate::story_tree::story_validation::{find_start_node_ids, validate_nodes, StoryReport};
+use crate::utils::rng::SeededRng;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StoryGraphError {
+    #[error("Story node {0} already exists")]
+    DuplicateNode(u32),
+    #[error("Story node {0} not found")]
+    NodeNotFound(u32),
+    #[error("Story node {node_id} has no option {option_index}")]
+    OptionNotFound { node_id: u32, option_index: usize },
+    #[error("Option {option_index} on story node {node_id} is not available: {reason}")]
+    OptionUnavailable { node_id: u32, option_index: usize, reason: String },
+    #[error("Failed the check on option {option_index} on story node {node_id}")]
+    CheckFailed { node_id: u32, option_index: usize },
+    #[error("The story has no start node")]
+    NoStartNode,
+    #[error("The story has not been started")]
+    NotStarted,
+    #[error("Story history has no entry {0}")]
+    HistoryEntryNotFound(usize),
+    #[error("Invalid story data: {0}")]
+    InvalidData(String),
+    #[error("Inventory error: {0}")]
+    Inventory(#[from] InventoryError),
+}
+
+/// An option as the player sees it, greyed out when there is a reason it can't be picked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionState<'a> {
+    pub index: usize,
+    pub option: &'a StoryOption,
+    pub unavailable_reason: Option<String>,
+}
+
+impl OptionState<'_> {
+    pub fn is_available(&self) -> bool {
+        self.unavailable_reason.is_none()
+    }
+}
+
+/// Where a choice took the player and everything its effects changed.
+#[derive(Debug, Clone)]
+pub struct StoryChoice<'a> {
+    pub next_node: &'a StoryNode,
+    pub outcomes: Vec<StoryOutcome>,
+    pub choice: RecordedChoice,
+}
+
+/// The authored story: every node keyed by id, linked by the options that lead out of them.
+/// The player's own path through it is kept in a `PlayerStoryTree`.
+#[derive(Debug, Clone, Default)]
+pub struct StoryGraph {
+    nodes: HashMap<u32, StoryNode>,
+}
+
+impl StoryGraph {
+    pub fn new() -> Self {
+        StoryGraph {
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn from_nodes(nodes: Vec<StoryNode>) -> Result<Self, StoryGraphError> {
+        let mut graph = StoryGraph::new();
+        for node in nodes {
+            graph.add_node(node)?;
+        }
+        Ok(graph)
+    }
+
+    /// Loads a story from a JSON list of nodes.
+    pub fn from_json(json: &str) -> Result<Self, StoryGraphError> {
+        let nodes: Vec<StoryNode> = serde_json::from_str(json).map_err(|error| StoryGraphError::InvalidData(error.to_string()))?;
+        Self::from_nodes(nodes)
+    }
+
+    pub fn add_node(&mut self, node: StoryNode) -> Result<(), StoryGraphError> {
+        if self.nodes.contains_key(&node.id) {
+            return Err(StoryGraphError::DuplicateNode(node.id));
+        }
+
+        self.nodes.insert(node.id, node);
+        Ok(())
+    }
+
+    pub fn get_node(&self, node_id: u32) -> Option<&StoryNode> {
+        self.nodes.get(&node_id)
+    }
+
+    /// Every node sorted by id.
+    pub fn get_nodes(&self) -> Vec<&StoryNode> {
+        let mut nodes: Vec<&StoryNode> = self.nodes.values().collect();
+        nodes.sort_by_key(|node| node.id);
+        nodes
+    }
+
+    /// The ids of the nodes the options on this node lead to, without duplicates.
+    pub fn get_next_node_ids(&self, node_id: u32) -> Vec<u32> {
+        let mut next_node_ids: Vec<u32> = self.get_node(node_id)
+            .map(|node| node.options.iter().flat_map(|option| option.get_destination_ids()).collect())
+            .unwrap_or_default();
+
+        next_node_ids.sort();
+        next_node_ids.dedup();
+        next_node_ids
+    }
+
+    /// The ids of every node with an option leading to this node.
+    pub fn get_previous_node_ids(&self, node_id: u32) -> Vec<u32> {
+        self.get_nodes()
+            .into_iter()
+            .filter(|node| node.options.iter().any(|option| option.get_destination_ids().contains(&node_id)))
+            .map(|node| node.id)
+            .collect()
+    }
+
+    /// Nodes flagged as start nodes, or failing that every node nothing leads to, sorted by id.
+    pub fn get_start_nodes(&self) -> Vec<&StoryNode> {
+        find_start_node_ids(self.nodes.values())
+            .into_iter()
+            .filter_map(|node_id| self.get_node(node_id))
+            .collect()
+    }
+
+    /// Checks the story for broken branches, see `validate_nodes`.
+    pub fn validate(&self) -> StoryReport {
+        validate_nodes(self.get_nodes())
+    }
+
+    pub fn get_current_node(&self, player_tree: &PlayerStoryTree) -> Option<&StoryNode> {
+        player_tree.current_node_id.and_then(|node_id| self.get_node(node_id))
+    }
+
+    /// Puts the player on the first start node.
+    pub fn start(&self, player_tree: &mut PlayerStoryTree) -> Result<&StoryNode, StoryGraphError> {
+        let start_node_id = self.get_start_nodes()
+            .first()
+            .map(|node| node.id)
+            .ok_or(StoryGraphError::NoStartNode)?;
+
+        self.start_at(start_node_id, player_tree)
+    }
+
+    /// Puts the player on the given node, e.g. to begin a chapter that doesn't follow on from the last one.
+    pub fn start_at(&self, node_id: u32, player_tree: &mut PlayerStoryTree) -> Result<&StoryNode, StoryGraphError> {
+        let node = self.get_node(node_id).ok_or(StoryGraphError::NodeNotFound(node_id))?;
+        player_tree.jump_to(Some(node_id));
+        Ok(node)
+    }
+
+    /// Every option on the player's current node they can see: the available ones plus any unavailable ones
+    /// that are shown greyed out with the reason.
+    pub fn get_option_states<'a>(&'a self, player_tree: &PlayerStoryTree, context: &StoryContext) -> Vec<OptionState<'a>> {
+        let Some(node) = self.get_current_node(player_tree) else {
+            return Vec::new();
+        };
+
+        node.options
+            .iter()
+            .enumerate()
+            .filter_map(|(index, option)| {
+                let unavailable_reason = option.conditions
+                    .iter()
+                    .try_for_each(|condition| condition.evaluate(context, player_tree, None))
+                    .err();
+
+                match unavailable_reason.is_none() || option.show_when_unavailable {
+                    true => Some(OptionState { index, option, unavailable_reason }),
+                    false => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Picks an option on the player's current node, applies its effects, records the choice in their
+    /// story tree and moves them on to the node it leads to. Stat checks on the option are rolled here,
+    /// failing one skips the effects and sends the player to the option's failure node if it has one.
+    /// If any effect fails nothing changes.
+    pub fn choose_option(&self, player_tree: &mut PlayerStoryTree, option_index: usize, world: &mut StoryWorld, rng: &mut SeededRng) -> Result<StoryChoice<'_>, StoryGraphError> {
+        let node = self.get_current_node(player_tree).ok_or(StoryGraphError::NotStarted)?;
+        let option = node.options
+            .get(option_index)
+            .ok_or(StoryGraphError::OptionNotFound { node_id: node.id, option_index })?;
+
+        let snapshot = StorySnapshot::capture(world.player, player_tree);
+        let context = world.get_context();
+        if let Err(reason) = option.conditions.iter().try_for_each(|condition| condition.evaluate(&context, player_tree, None)) {
+            return Err(StoryGraphError::OptionUnavailable { node_id: node.id, option_index, reason });
+        }
+
+        let passed_checks = !option.has_stat_check() || option.conditions
+            .iter()
+            .all(|condition| condition.evaluate(&context, player_tree, Some(&mut *rng)).is_ok());
+        let next_node_id = match (passed_checks, option.failure_node_id) {
+            (true, _) => option.next_node_id,
+            (false, Some(failure_node_id)) => failure_node_id,
+            (false, None) => {
+                // the failed attempt is still part of the history, it used up a roll
+                let choice = RecordedChoice { node_id: node.id, option_index, next_node_id: None, after_jump: false };
+                player_tree.record_choice(StoryHistoryEntry { choice, option: option.clone(), snapshot });
+                return Err(StoryGraphError::CheckFailed { node_id: node.id, option_index });
+            },
+        };
+        let next_node = self.get_node(next_node_id).ok_or(StoryGraphError::NodeNotFound(next_node_id))?;
+
+        let outcomes = match passed_checks {
+            true => apply_effects(&option.effects, node.id, world, player_tree)?,
+            false => Vec::new(),
+        };
+
+        // the recorded node remembers where the player came from and where this choice took them,
+        // a node the story jumped to has nowhere it came from
+        let mut visited_node = node.clone();
+        visited_node.set_next_node_id(next_node.id);
+        if let Some(previous_node) = player_tree.story_path.iter().rev().find(|previous_node| previous_node.next_node_id == Some(node.id)) {
+            visited_node.set_previous_node_id(previous_node.id);
+        }
+
+        player_tree.add_past_node_choice(visited_node, option.clone());
+        player_tree.current_node_id = Some(next_node.id);
+        let choice = RecordedChoice { node_id: node.id, option_index, next_node_id: Some(next_node.id), after_jump: false };
+        let choice = player_tree.record_choice(StoryHistoryEntry { choice, option: option.clone(), snapshot });
+        Ok(StoryChoice { next_node, outcomes, choice })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::StatsEnum;
+    use crate::factions::faction::FactionRegistry;
+    use crate::inventory::item::ItemRegistry;
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_conditions::StoryCondition;
+    use crate::story_tree::story_effects::{StoryEffect, StoryOutcome, StoryWorld};
+    use crate::story_tree::story_graph::{StoryGraph, StoryGraphError};
+    use crate::story_tree::story_management::{PlayerStoryTree, StoryNode, StoryOption};
+    use crate::utils::rng::SeededRng;
+
+    fn node(id: u32, options: Vec<StoryOption>) -> StoryNode {
+        let is_end_node = options.is_empty();
+        StoryNode::new(id, format!("Node {}", id), String::new(), options, is_end_node, false)
+    }
+
+    fn create_graph() -> StoryGraph {
+        let candle = StoryCondition::HasItem { item_id: "candle".to_string(), quantity: 1 };
+        let blessed = StoryCondition::FlagSet("blessed".to_string());
+        let lift = StoryCondition::StatCheck { stat: StatsEnum::Strength, difficulty: 100 };
+
+        let mut graph = StoryGraph::new();
+        graph.add_node(node(10, vec![StoryOption::new(20, "Enter the chapel"), StoryOption::new(30, "Burn the chapel")])).unwrap();
+        graph.add_node(node(20, vec![
+            StoryOption::new(40, "Pray"),
+            StoryOption::new(30, "Light a candle").with_conditions(vec![candle]).shown_when_unavailable(),
+            StoryOption::new(40, "Take communion").with_conditions(vec![blessed]),
+            StoryOption::new(40, "Lift the altar stone")
+                .with_conditions(vec![lift])
+                .with_effects(vec![StoryEffect::SetFlag("found_the_relic".to_string())])
+                .with_failure_node(30),
+        ])).unwrap();
+        graph.add_node(node(30, vec![StoryOption::new(40, "Flee into the woods").with_effects(vec![StoryEffect::GainExperience(20)])])).unwrap();
+        graph.add_node(node(40, vec![])).unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_graph_edges_and_start_nodes() {
+        let mut graph = create_graph();
+
+        assert_eq!(graph.add_node(node(40, vec![])), Err(StoryGraphError::DuplicateNode(40)));
+        assert_eq!(graph.get_next_node_ids(10), vec![20, 30]);
+        assert_eq!(graph.get_previous_node_ids(40), vec![20, 30]);
+        assert_eq!(graph.get_start_nodes()[0].id, 10);
+
+        graph.add_node(StoryNode::new(1, "Prologue".to_string(), String::new(), vec![StoryOption::new(10, "Wake")], false, true)).unwrap();
+        graph.add_node(StoryNode::new(5, "Epilogue".to_string(), String::new(), vec![], true, false)).unwrap();
+        assert_eq!(graph.get_start_nodes().len(), 1);
+        assert_eq!(graph.get_start_nodes()[0].id, 1);
+    }
+
+    #[test]
+    fn test_choosing_options_records_the_path() {
+        let graph = create_graph();
+        let mut player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut rng = SeededRng::new(3);
+        let mut player_tree = PlayerStoryTree::new();
+
+        assert_eq!(graph.choose_option(&mut player_tree, 0, &mut world, &mut rng).unwrap_err(), StoryGraphError::NotStarted);
+        assert_eq!(graph.start(&mut player_tree).unwrap().id, 10);
+        assert_eq!(graph.choose_option(&mut player_tree, 0, &mut world, &mut rng).unwrap().next_node.id, 20);
+
+        // communion is hidden, the candle is greyed out
+        let states = graph.get_option_states(&player_tree, &world.get_context());
+        assert_eq!(states.iter().map(|state| state.index).collect::<Vec<usize>>(), vec![0, 1, 3]);
+        assert!(!states[1].is_available());
+        assert_eq!(states[1].unavailable_reason, Some("1 x candle".to_string()));
+
+        assert_eq!(
+            graph.choose_option(&mut player_tree, 1, &mut world, &mut rng).unwrap_err(),
+            StoryGraphError::OptionUnavailable { node_id: 20, option_index: 1, reason: "1 x candle".to_string() }
+        );
+        assert_eq!(graph.choose_option(&mut player_tree, 5, &mut world, &mut rng).unwrap_err(), StoryGraphError::OptionNotFound { node_id: 20, option_index: 5 });
+
+        // nobody lifts the altar stone, so the player flees without the relic
+        let choice = graph.choose_option(&mut player_tree, 3, &mut world, &mut rng).unwrap();
+        assert_eq!(choice.next_node.id, 30);
+        assert!(choice.outcomes.is_empty());
+        assert!(!player_tree.story_flags.contains("found_the_relic"));
+
+        let choice = graph.choose_option(&mut player_tree, 0, &mut world, &mut rng).unwrap();
+        assert_eq!(choice.next_node.id, 40);
+        assert_eq!(choice.outcomes, vec![StoryOutcome::ExperienceGained(20)]);
+        assert_eq!(world.player.current_experience, 20);
+        assert_eq!(graph.get_current_node(&player_tree).unwrap().id, 40);
+        assert_eq!(player_tree.get_past_node_choice(10).unwrap().text, "Enter the chapel");
+
+        let last_node = player_tree.get_last_story_node().unwrap();
+        assert_eq!(last_node.id, 30);
+        assert_eq!(last_node.previous_node_id, Some(20));
+        assert_eq!(last_node.next_node_id, Some(40));
+    }
+}
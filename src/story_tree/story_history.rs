@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::state_management::player_state::PlayerState;
+use crate::story_tree::story_effects::{StoryOutcome, StoryWorld};
+use crate::story_tree::story_graph::{StoryGraph, StoryGraphError};
+use crate::story_tree::story_management::{PlayerStoryTree, StoryOption};
+use crate::story_tree::story_variables::StoryVariables;
+use crate::utils::rng::SeededRng;
+
+/// One choice the player made. Saved as a list, these can be replayed against the story.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedChoice {
+    pub node_id: u32,
+    pub option_index: usize,
+    // where the choice led, None when a stat check failed and there was nowhere to fall back to
+    pub next_node_id: Option<u32>,
+    // the story jumped to `node_id` before this choice rather than a choice leading there,
+    // e.g. into a conversation or back out of one
+    #[serde(default)]
+    pub after_jump: bool,
+}
+
+/// The player and their story progress just before a choice, so rewinding can put them back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorySnapshot {
+    player: PlayerState,
+    // the story path only grows, so it's enough to know how long it was
+    story_path_length: usize,
+    current_node_id: Option<u32>,
+    jumped: bool,
+    story_flags: HashSet<String>,
+    variables: StoryVariables,
+}
+
+impl StorySnapshot {
+    pub fn capture(player: &PlayerState, story_tree: &PlayerStoryTree) -> Self {
+        StorySnapshot {
+            player: player.clone(),
+            story_path_length: story_tree.story_path.len(),
+            current_node_id: story_tree.current_node_id,
+            jumped: story_tree.jumped,
+            story_flags: story_tree.story_flags.clone(),
+            variables: story_tree.variables.clone(),
+        }
+    }
+
+    pub fn restore(self, player: &mut PlayerState, story_tree: &mut PlayerStoryTree) {
+        *player = self.player;
+        story_tree.story_path.truncate(self.story_path_length);
+        story_tree.current_node_id = self.current_node_id;
+        story_tree.jumped = self.jumped;
+        story_tree.story_flags = self.story_flags;
+        story_tree.variables = self.variables;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoryHistoryEntry {
+    pub choice: RecordedChoice,
+    // the option as it was when it was chosen
+    pub option: StoryOption,
+    pub snapshot: StorySnapshot,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ReplayDivergence {
+    #[error("the choice was made on node {expected}, but the story is at {actual:?}")]
+    WrongNode { expected: u32, actual: Option<u32> },
+    #[error("the choice led to {actual:?} instead of {expected:?}")]
+    WrongDestination { expected: Option<u32>, actual: Option<u32> },
+    #[error("Story error: {0}")]
+    Story(StoryGraphError),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Replay diverged at choice {step}: {divergence}")]
+pub struct ReplayError {
+    pub step: usize,
+    pub divergence: ReplayDivergence,
+}
+
+/// Makes recorded choices again from wherever `story_tree` is, stopping at the first one that plays out
+/// differently, and returns the outcomes of them all. Choices made after a jump jump there again first. Stat checks are rolled again, so a replay only matches
+/// when its rng is seeded like the original run's and the story was the only thing rolling on it.
+pub fn replay_choices(graph: &StoryGraph, choices: &[RecordedChoice], story_tree: &mut PlayerStoryTree, world: &mut StoryWorld, rng: &mut SeededRng) -> Result<Vec<StoryOutcome>, ReplayError> {
+    let mut outcomes = Vec::new();
+
+    for (step, recorded) in choices.iter().enumerate() {
+        let diverged = |divergence: ReplayDivergence| ReplayError { step, divergence };
+
+        if recorded.after_jump {
+            graph.start_at(recorded.node_id, story_tree).map_err(|error| diverged(ReplayDivergence::Story(error)))?;
+        }
+        if story_tree.current_node_id != Some(recorded.node_id) {
+            return Err(diverged(ReplayDivergence::WrongNode { expected: recorded.node_id, actual: story_tree.current_node_id }));
+        }
+
+        let next_node_id = match graph.choose_option(story_tree, recorded.option_index, world, rng) {
+            Ok(choice) => {
+                outcomes.extend(choice.outcomes);
+                Some(choice.next_node.id)
+            },
+            Err(StoryGraphError::CheckFailed { .. }) => None,
+            Err(error) => return Err(diverged(ReplayDivergence::Story(error))),
+        };
+
+        if next_node_id != recorded.next_node_id {
+            return Err(diverged(ReplayDivergence::WrongDestination { expected: recorded.next_node_id, actual: next_node_id }));
+        }
+    }
+
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues, StatsEnum};
+    use crate::factions::faction::FactionRegistry;
+    use crate::inventory::item::ItemRegistry;
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_effects::StoryWorld;
+    use crate::story_tree::story_graph::{StoryGraph, StoryGraphError};
+    use crate::story_tree::story_history::{replay_choices, RecordedChoice, ReplayDivergence, ReplayError};
+    use crate::story_tree::story_management::PlayerStoryTree;
+    use crate::story_tree::story_script::load_story;
+    use crate::story_tree::story_variables::StoryValue;
+    use crate::utils::rng::SeededRng;
+
+    fn create_graph(summit_node_id: u32) -> StoryGraph {
+        // a coin flip of a climb for a starting player
+        let difficulty = PlayerState::new().get_effective_stat(StatsEnum::Dexterity) + 11;
+        load_story(&format!("\
+== 1 Crossroads @start
++ Visit the shrine -> 2
+    ! xp 10
+    ! var npc.hermit.visits += 1
++ Climb the cliff -> {summit_node_id}
+    ? check dexterity {difficulty}
+    ! set climbed
+
+== 2 Shrine
++ Return to the crossroads -> 1
+    ! madness 5
+
+== 3 Summit @end
+
+== 4 Ledge @end
+")).unwrap()
+    }
+
+    #[test]
+    fn test_rewinding_restores_the_story() {
+        let graph = create_graph(3);
+        let mut player = PlayerState::new();
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut rng = SeededRng::new(5);
+        let mut story_tree = PlayerStoryTree::new();
+
+        graph.start(&mut story_tree).unwrap();
+        for _ in 0..3 {
+            graph.choose_option(&mut story_tree, 0, &mut world, &mut rng).unwrap();
+        }
+
+        // both visits to the crossroads are remembered
+        assert_eq!(story_tree.get_history().len(), 3);
+        assert_eq!(story_tree.get_choices_at(1).len(), 2);
+        assert_eq!(story_tree.variables.get_by_path("npc.hermit.visits"), Some(&StoryValue::Int(2)));
+        assert_eq!(world.player.current_experience, 20);
+
+        let undone = story_tree.rewind_to(2, world.player).unwrap();
+        assert_eq!(undone, RecordedChoice { node_id: 1, option_index: 0, next_node_id: Some(2), after_jump: false });
+        assert_eq!(story_tree.current_node_id, Some(1));
+        assert_eq!(story_tree.variables.get_by_path("npc.hermit.visits"), Some(&StoryValue::Int(1)));
+        assert_eq!(world.player.current_experience, 10);
+        assert_eq!(world.player.get_resource(ResourcePoolEnum::Madness), 5);
+        assert_eq!(story_tree.story_path.len(), 2);
+
+        assert!(story_tree.undo(world.player).is_some());
+        assert_eq!(story_tree.current_node_id, Some(2));
+        assert_eq!(world.player.get_resource(ResourcePoolEnum::Madness), 0);
+        assert_eq!(story_tree.get_past_node_choice(2), None);
+        assert_eq!(story_tree.rewind_to(4, world.player), Err(StoryGraphError::HistoryEntryNotFound(4)));
+    }
+
+    #[test]
+    fn test_replaying_choices_is_deterministic() {
+        let graph = create_graph(3);
+        let factions = FactionRegistry::new();
+        let items = ItemRegistry::new();
+
+        let mut player = PlayerState::new();
+        let mut world = StoryWorld { player: &mut player, factions: &factions, items: &items };
+        let mut rng = SeededRng::new(11);
+        let mut story_tree = PlayerStoryTree::new();
+        graph.start(&mut story_tree).unwrap();
+        graph.choose_option(&mut story_tree, 0, &mut world, &mut rng).unwrap();
+        graph.choose_option(&mut story_tree, 0, &mut world, &mut rng).unwrap();
+        while story_tree.current_node_id == Some(1) {
+            // failed climbs keep the player at the crossroads
+            let _ = graph.choose_option(&mut story_tree, 1, &mut world, &mut rng);
+        }
+        let choices = story_tree.get_recorded_choices();
+        assert_eq!(choices.last().unwrap().next_node_id, Some(3));
+
+        let replay = |graph: &StoryGraph| {
+            let mut replayed_player = PlayerState::new();
+            let mut world = StoryWorld { player: &mut replayed_player, factions: &factions, items: &items };
+            let mut replayed_tree = PlayerStoryTree::new();
+            graph.start(&mut replayed_tree).unwrap();
+            let result = replay_choices(graph, &choices, &mut replayed_tree, &mut world, &mut SeededRng::new(11));
+            (result, replayed_tree, replayed_player)
+        };
+
+        let (result, replayed_tree, replayed_player) = replay(&graph);
+        assert!(result.is_ok());
+        assert_eq!(replayed_tree.get_recorded_choices(), choices);
+        assert_eq!(replayed_tree.story_flags, story_tree.story_flags);
+        assert_eq!(replayed_player, player);
+
+        // moving the summit shows up as soon as the climb succeeds
+        let (result, _, _) = replay(&create_graph(4));
+        assert_eq!(result.unwrap_err(), ReplayError {
+            step: choices.len() - 1,
+            divergence: ReplayDivergence::WrongDestination { expected: Some(3), actual: Some(4) },
+        });
+    }
+}
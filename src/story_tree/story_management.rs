@@ -1,39 +1,96 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::story_tree::story_conditions::{StoryCondition, StoryContext};
+use crate::state_management::player_state::PlayerState;
+use crate::story_tree::story_effects::StoryEffect;
+use crate::story_tree::story_graph::StoryGraphError;
+use crate::story_tree::story_history::{RecordedChoice, StoryHistoryEntry};
+use crate::story_tree::story_text::{render_text, StoryTemplateError};
+use crate::story_tree::story_variables::StoryVariables;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StoryOption {
     pub next_node_id: u32,
     pub text: String,
-    pub is_available: bool,
+    // every condition has to pass for the option to be available
+    #[serde(default)]
+    pub conditions: Vec<StoryCondition>,
+    // unavailable options are hidden unless this is set, in which case they are shown greyed out with the reason
+    #[serde(default)]
+    pub show_when_unavailable: bool,
+    // where a failed stat check leads, without one a failed check leaves the player where they are
+    #[serde(default)]
+    pub failure_node_id: Option<u32>,
+    // applied when the option is chosen and any stat checks on it pass
+    #[serde(default)]
+    pub effects: Vec<StoryEffect>,
 }
 
-#[derive(Debug, Clone)]
+impl StoryOption {
+    pub fn new(next_node_id: u32, text: &str) -> Self {
+        StoryOption {
+            next_node_id,
+            text: text.to_string(),
+            conditions: Vec::new(),
+            show_when_unavailable: false,
+            failure_node_id: None,
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn with_conditions(mut self, conditions: Vec<StoryCondition>) -> Self {
+        self.conditions = conditions;
+        self
+    }
+
+    pub fn with_effects(mut self, effects: Vec<StoryEffect>) -> Self {
+        self.effects = effects;
+        self
+    }
+
+    pub fn with_failure_node(mut self, failure_node_id: u32) -> Self {
+        self.failure_node_id = Some(failure_node_id);
+        self
+    }
+
+    pub fn shown_when_unavailable(mut self) -> Self {
+        self.show_when_unavailable = true;
+        self
+    }
+
+    /// Every node choosing the option can lead to.
+    pub fn get_destination_ids(&self) -> Vec<u32> {
+        std::iter::once(self.next_node_id).chain(self.failure_node_id).collect()
+    }
+
+    pub fn has_stat_check(&self) -> bool {
+        self.conditions.iter().any(|condition| condition.has_stat_check())
+    }
+
+    /// The option's text with its variables filled in, see `StoryTemplate`.
+    pub fn render_text(&self, context: &StoryContext, story_tree: &PlayerStoryTree) -> Result<String, StoryTemplateError> {
+        render_text(&self.text, context, story_tree)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoryNode {
     pub id: u32,
     pub title: String,
     pub text: String,
+    #[serde(default)]
     pub options: Vec<StoryOption>,
+    #[serde(default)]
     pub is_end_node: bool,
+    #[serde(default)]
     pub is_start_node: bool,
     pub previous_node_id: Option<u32>,
     pub next_node_id: Option<u32>,
 }
 
 impl StoryNode {
+    /// Nodes start unlinked, the story graph links them up from their options.
     pub fn new(id: u32, title: String, text: String, options: Vec<StoryOption>, is_end_node: bool, is_start_node: bool) -> Self {
-
-        let previous_node_id = if is_start_node {
-            None
-        } else {
-            Some(id - 1)
-        };
-
-        let next_node_id = if is_end_node {
-            None
-        } else {
-            Some(id + 1)
-        };
-
         StoryNode {
             id,
             title,
@@ -41,11 +98,16 @@ impl StoryNode {
             options,
             is_end_node,
             is_start_node,
-            previous_node_id,
-            next_node_id,
+            previous_node_id: None,
+            next_node_id: None,
         }
     }
 
+    /// The node's text as the player should read it right now, see `StoryTemplate`.
+    pub fn render_text(&self, context: &StoryContext, story_tree: &PlayerStoryTree) -> Result<String, StoryTemplateError> {
+        render_text(&self.text, context, story_tree)
+    }
+
     pub fn set_previous_node_id(&mut self, previous_node_id: u32) {
         self.previous_node_id = Some(previous_node_id);
     }
@@ -55,10 +117,18 @@ impl StoryNode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PlayerStoryTree {
+    // the latest choice made on each node, see the history for every choice in order
     pub past_node_choices: HashMap<u32, StoryOption>,
     pub story_path: Vec<StoryNode>,
+    // the node the player is currently reading, None until the story has been started
+    pub current_node_id: Option<u32>,
+    pub story_flags: HashSet<String>,
+    pub variables: StoryVariables,
+    // set when the story jumps to a node rather than a choice leading there, recorded with the next choice
+    pub(crate) jumped: bool,
+    history: Vec<StoryHistoryEntry>,
 }
 
 impl PlayerStoryTree {
@@ -66,6 +136,11 @@ impl PlayerStoryTree {
         PlayerStoryTree {
             past_node_choices: HashMap::new(),
             story_path: Vec::new(),
+            current_node_id: None,
+            story_flags: HashSet::new(),
+            variables: StoryVariables::new(),
+            jumped: false,
+            history: Vec::new(),
         }
     }
 
@@ -89,4 +164,69 @@ impl PlayerStoryTree {
     pub fn get_past_node_choice(&self, node_id: u32) -> Option<&StoryOption> {
         self.past_node_choices.get(&node_id)
     }
+
+    /// Puts the player on a node without a choice leading there, e.g. into a conversation and back out of it.
+    /// The next choice is recorded as following the jump so it can be replayed.
+    pub fn jump_to(&mut self, node_id: Option<u32>) {
+        self.current_node_id = node_id;
+        self.jumped = true;
+    }
+
+    /// Returns the choice as it was recorded.
+    pub fn record_choice(&mut self, mut entry: StoryHistoryEntry) -> RecordedChoice {
+        entry.choice.after_jump = std::mem::take(&mut self.jumped);
+        let choice = entry.choice.clone();
+        self.history.push(entry);
+        choice
+    }
+
+    /// Every choice the player has made, oldest first.
+    pub fn get_history(&self) -> &[StoryHistoryEntry] {
+        &self.history
+    }
+
+    pub fn get_recorded_choices(&self) -> Vec<RecordedChoice> {
+        self.history
+            .iter()
+            .map(|entry| entry.choice.clone())
+            .collect()
+    }
+
+    /// Every choice made on a node, one for each time the player was there.
+    pub fn get_choices_at(&self, node_id: u32) -> Vec<&RecordedChoice> {
+        self.history
+            .iter()
+            .map(|entry| &entry.choice)
+            .filter(|choice| choice.node_id == node_id)
+            .collect()
+    }
+
+    /// Takes the story and the player back to just before the given choice, forgetting it and every choice after it.
+    pub fn rewind_to(&mut self, history_index: usize, player: &mut PlayerState) -> Result<RecordedChoice, StoryGraphError> {
+        if history_index >= self.history.len() {
+            return Err(StoryGraphError::HistoryEntryNotFound(history_index));
+        }
+
+        let entry = self.history.split_off(history_index).swap_remove(0);
+        entry.snapshot.restore(player, self);
+        self.past_node_choices = self.history
+            .iter()
+            .filter(|entry| entry.choice.next_node_id.is_some())
+            .map(|entry| (entry.choice.node_id, entry.option.clone()))
+            .collect();
+
+        Ok(entry.choice)
+    }
+
+    /// Takes back the last choice, returning it.
+    pub fn undo(&mut self, player: &mut PlayerState) -> Option<RecordedChoice> {
+        let last_index = self.history.len().checked_sub(1)?;
+        self.rewind_to(last_index, player).ok()
+    }
+}
+
+impl Default for PlayerStoryTree {
+    fn default() -> Self {
+        PlayerStoryTree::new()
+    }
 }
@@ -0,0 +1,539 @@
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use crate::factions::reputation::ReputationRequirement;
+use crate::quests::quest_log::QuestState;
+use crate::story_tree::story_conditions::StoryCondition;
+use crate::story_tree::story_effects::StoryEffect;
+use crate::story_tree::story_graph::StoryGraph;
+use crate::story_tree::story_management::{StoryNode, StoryOption};
+use crate::story_tree::story_text::StoryTemplate;
+use crate::story_tree::story_variables::StoryValue;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("line {line}, column {column}: {message}")]
+pub struct StoryScriptError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token<'a> {
+    text: &'a str,
+    // where the word starts in the line, in bytes
+    index: usize,
+    column: usize,
+}
+
+/// Splits a line into words, with brackets as words of their own. Columns start at 1.
+fn tokenize(text: &str, first_column: usize) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+
+    for (column, (index, character)) in text.char_indices().enumerate() {
+        let column = first_column + column;
+
+        if character.is_whitespace() || character == '(' || character == ')' {
+            if let Some((start_index, start_column)) = start.take() {
+                tokens.push(Token { text: &text[start_index..index], index: start_index, column: start_column });
+            }
+            if !character.is_whitespace() {
+                tokens.push(Token { text: &text[index..index + 1], index, column });
+            }
+        } else if start.is_none() {
+            start = Some((index, column));
+        }
+    }
+
+    if let Some((start_index, start_column)) = start {
+        tokens.push(Token { text: &text[start_index..], index: start_index, column: start_column });
+    }
+    tokens
+}
+
+/// Reads enum names like `strength` or `Unhinged` through serde so the script uses the same names as the data files.
+fn parse_name<T: DeserializeOwned>(word: &str) -> Option<T> {
+    let mut characters = word.chars();
+    let name: String = characters
+        .next()
+        .map(|first| first.to_uppercase().chain(characters).collect())
+        .unwrap_or_default();
+
+    serde_json::from_value(serde_json::Value::String(name)).ok()
+}
+
+/// Walks the words of one condition or effect line.
+struct LineParser<'a> {
+    text: &'a str,
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    line: usize,
+    // where the line ends, for errors about missing words
+    end_column: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(text: &'a str, line: usize, first_column: usize) -> Self {
+        LineParser {
+            text,
+            tokens: tokenize(text, first_column),
+            position: 0,
+            line,
+            end_column: first_column + text.chars().count(),
+        }
+    }
+
+    fn error_at(&self, column: usize, message: String) -> StoryScriptError {
+        StoryScriptError { line: self.line, column, message }
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next_word(&mut self, expected: &str) -> Result<Token<'a>, StoryScriptError> {
+        let token = self.peek().ok_or_else(|| self.error_at(self.end_column, format!("expected {}", expected)))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn next_number<T: std::str::FromStr>(&mut self, expected: &str) -> Result<T, StoryScriptError> {
+        let token = self.next_word(expected)?;
+        token.text
+            .parse()
+            .map_err(|_| self.error_at(token.column, format!("expected {}, found '{}'", expected, token.text)))
+    }
+
+    fn next_name<T: DeserializeOwned>(&mut self, expected: &str) -> Result<T, StoryScriptError> {
+        let token = self.next_word(expected)?;
+        parse_name(token.text).ok_or_else(|| self.error_at(token.column, format!("expected {}, found '{}'", expected, token.text)))
+    }
+
+    /// A number, `true`, `false` or "quoted text".
+    fn next_value(&mut self) -> Result<StoryValue, StoryScriptError> {
+        let token = self.next_word("a value")?;
+
+        if token.text.starts_with('"') {
+            let quoted = &self.text[token.index + 1..];
+            let length = quoted
+                .find('"')
+                .ok_or_else(|| self.error_at(token.column, "'\"' is never closed".to_string()))?;
+            // skip the rest of the words inside the quotes
+            let end_index = token.index + 1 + length;
+            while self.peek().is_some_and(|token| token.index <= end_index) {
+                self.position += 1;
+            }
+            return Ok(StoryValue::Text(quoted[..length].to_string()));
+        }
+
+        match token.text {
+            "true" => Ok(StoryValue::Bool(true)),
+            "false" => Ok(StoryValue::Bool(false)),
+            number => number
+                .parse()
+                .map(StoryValue::Int)
+                .map_err(|_| self.error_at(token.column, format!("expected a number, true, false or \"text\", found '{}'", number))),
+        }
+    }
+
+    /// An optional quantity, 1 when left out.
+    fn next_quantity(&mut self) -> Result<u32, StoryScriptError> {
+        match self.peek().is_some_and(|token| token.text != ")" && token.text.parse::<u32>().is_ok()) {
+            true => self.next_number("a quantity"),
+            false => Ok(1),
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), StoryScriptError> {
+        let token = self.next_word(&format!("'{}'", text))?;
+        match token.text == text {
+            true => Ok(()),
+            false => Err(self.error_at(token.column, format!("expected '{}', found '{}'", text, token.text))),
+        }
+    }
+
+    fn finish(&self) -> Result<(), StoryScriptError> {
+        match self.peek() {
+            Some(token) => Err(self.error_at(token.column, format!("unexpected '{}'", token.text))),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<StoryCondition, StoryScriptError> {
+        let mut conditions = vec![self.parse_all()?];
+        while self.peek().is_some_and(|token| token.text == "or") {
+            self.position += 1;
+            conditions.push(self.parse_all()?);
+        }
+
+        Ok(match conditions.len() {
+            1 => conditions.remove(0),
+            _ => StoryCondition::Any(conditions),
+        })
+    }
+
+    fn parse_all(&mut self) -> Result<StoryCondition, StoryScriptError> {
+        let mut conditions = vec![self.parse_unary()?];
+        while self.peek().is_some_and(|token| token.text == "and") {
+            self.position += 1;
+            conditions.push(self.parse_unary()?);
+        }
+
+        Ok(match conditions.len() {
+            1 => conditions.remove(0),
+            _ => StoryCondition::All(conditions),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<StoryCondition, StoryScriptError> {
+        let token = self.next_word("a condition")?;
+
+        match token.text {
+            "not" => Ok(StoryCondition::Not(Box::new(self.parse_unary()?))),
+            "(" => {
+                let condition = self.parse_condition()?;
+                self.expect(")")?;
+                Ok(condition)
+            },
+            "stat" => Ok(StoryCondition::StatAtLeast { stat: self.next_name("a stat")?, value: self.next_number("a stat value")? }),
+            "check" => Ok(StoryCondition::StatCheck { stat: self.next_name("a stat")?, difficulty: self.next_number("a difficulty")? }),
+            "has" => Ok(StoryCondition::HasItem { item_id: self.next_word("an item id")?.text.to_string(), quantity: self.next_quantity()? }),
+            "reputation" => Ok(StoryCondition::Reputation(ReputationRequirement {
+                faction_id: self.next_word("a faction id")?.text.to_string(),
+                minimum_tier: self.next_name("a reputation tier")?,
+            })),
+            "madness" => {
+                let comparison = self.next_word("'>=' or '<='")?;
+                match comparison.text {
+                    ">=" => Ok(StoryCondition::MadnessAtLeast(self.next_name("a madness tier")?)),
+                    "<=" => Ok(StoryCondition::MadnessAtMost(self.next_name("a madness tier")?)),
+                    other => Err(self.error_at(comparison.column, format!("expected '>=' or '<=', found '{}'", other))),
+                }
+            },
+            "visited" => Ok(StoryCondition::VisitedNode(self.next_number("a node id")?)),
+            "chose" => Ok(StoryCondition::ChoseOption { node_id: self.next_number("a node id")?, next_node_id: self.next_number("a node id")? }),
+            "flag" => Ok(StoryCondition::FlagSet(self.next_word("a flag")?.text.to_string())),
+            "quest" => {
+                let quest_id = self.next_word("a quest id")?.text.to_string();
+                let state = self.next_word("a quest state")?;
+                let state = match state.text {
+                    "not_started" => QuestState::NotStarted,
+                    "active" => QuestState::Active,
+                    "completed" => QuestState::Completed,
+                    "failed" => QuestState::Failed,
+                    "stage" => QuestState::AtStage(self.next_word("a stage id")?.text.to_string()),
+                    "outcome" => QuestState::CompletedWith(self.next_word("an outcome id")?.text.to_string()),
+                    other => return Err(self.error_at(state.column, format!("expected a quest state, found '{}'", other))),
+                };
+                Ok(StoryCondition::Quest { quest_id, state })
+            },
+            other => Err(self.error_at(token.column, format!("unknown condition '{}'", other))),
+        }
+    }
+
+    fn parse_effect(&mut self) -> Result<StoryEffect, StoryScriptError> {
+        let token = self.next_word("an effect")?;
+
+        match token.text {
+            "xp" => Ok(StoryEffect::GainExperience(self.next_number("an amount of experience")?)),
+            "give" => Ok(StoryEffect::AddItem { item_id: self.next_word("an item id")?.text.to_string(), quantity: self.next_quantity()? }),
+            "take" => Ok(StoryEffect::RemoveItem { item_id: self.next_word("an item id")?.text.to_string(), quantity: self.next_quantity()? }),
+            "set" => Ok(StoryEffect::SetFlag(self.next_word("a flag")?.text.to_string())),
+            "clear" => Ok(StoryEffect::ClearFlag(self.next_word("a flag")?.text.to_string())),
+            "var" => {
+                let name = self.next_word("a variable name")?.text.to_string();
+                let operator = self.next_word("'=', '+=' or '-='")?;
+                match operator.text {
+                    "=" => Ok(StoryEffect::SetVariable { name, value: self.next_value()? }),
+                    "+=" => Ok(StoryEffect::ChangeVariable { name, amount: self.next_number("a number")? }),
+                    "-=" => {
+                        let column = self.peek().map_or(self.end_column, |token| token.column);
+                        let amount: i32 = self.next_number("a number")?;
+                        match amount.checked_neg() {
+                            Some(amount) => Ok(StoryEffect::ChangeVariable { name, amount }),
+                            None => Err(self.error_at(column, format!("can't subtract {}, it's out of range", amount))),
+                        }
+                    },
+                    other => Err(self.error_at(operator.column, format!("expected '=', '+=' or '-=', found '{}'", other))),
+                }
+            },
+            "reputation" => Ok(StoryEffect::ChangeReputation { faction_id: self.next_word("a faction id")?.text.to_string(), amount: self.next_number("an amount")? }),
+            "madness" => Ok(StoryEffect::ModifyMadness(self.next_number("an amount")?)),
+            "combat" => Ok(StoryEffect::StartCombat { encounter_id: self.next_word("an encounter id")?.text.to_string() }),
+            "quest" => {
+                let action = self.next_word("'start' or 'advance'")?;
+                let quest_id = self.next_word("a quest id")?.text.to_string();
+                match action.text {
+                    "start" => Ok(StoryEffect::StartQuest(quest_id)),
+                    "advance" => Ok(StoryEffect::AdvanceQuest(quest_id)),
+                    other => Err(self.error_at(action.column, format!("expected 'start' or 'advance', found '{}'", other))),
+                }
+            },
+            other => Err(self.error_at(token.column, format!("unknown effect '{}'", other))),
+        }
+    }
+}
+
+/// Checks the `{...}` tags in a piece of text, `positions` being the line and first column of each of its lines.
+fn check_template(text: &str, positions: &[(usize, usize)]) -> Result<(), StoryScriptError> {
+    StoryTemplate::parse(text).map(|_| ()).map_err(|error| {
+        let (line, first_column) = positions[error.line - 1];
+        StoryScriptError { line, column: first_column + error.column - 1, message: error.message }
+    })
+}
+
+struct NodeBuilder {
+    line: usize,
+    id: u32,
+    title: String,
+    text: Vec<String>,
+    // the line and first column of each line of text
+    text_positions: Vec<(usize, usize)>,
+    options: Vec<StoryOption>,
+    is_start_node: bool,
+    is_end_node: bool,
+}
+
+impl NodeBuilder {
+    fn build(mut self) -> Result<(usize, StoryNode), StoryScriptError> {
+        while self.text.last().is_some_and(|line| line.is_empty()) {
+            self.text.pop();
+            self.text_positions.pop();
+        }
+
+        let text = self.text.join("\n");
+        check_template(&text, &self.text_positions)?;
+        Ok((self.line, StoryNode::new(self.id, self.title, text, self.options, self.is_end_node, self.is_start_node)))
+    }
+}
+
+fn parse_header(text: &str, line: usize, first_column: usize) -> Result<NodeBuilder, StoryScriptError> {
+    let mut parser = LineParser::new(text, line, first_column);
+    let id = parser.next_number("a node id")?;
+    let mut node = NodeBuilder { line, id, title: String::new(), text: Vec::new(), text_positions: Vec::new(), options: Vec::new(), is_start_node: false, is_end_node: false };
+
+    let mut title = Vec::new();
+    while let Some(token) = parser.peek() {
+        parser.position += 1;
+        match token.text {
+            "@start" => node.is_start_node = true,
+            "@end" => node.is_end_node = true,
+            tag if tag.starts_with('@') => return Err(parser.error_at(token.column, format!("unknown tag '{}'", tag))),
+            word => title.push(word),
+        }
+    }
+
+    node.title = title.join(" ");
+    Ok(node)
+}
+
+fn parse_option(text: &str, line: usize, first_column: usize) -> Result<StoryOption, StoryScriptError> {
+    let arrow = text.rfind("->").ok_or_else(|| StoryScriptError { line, column: first_column, message: "expected '-> <node id>' after the option text".to_string() })?;
+    let option_text = text[..arrow].trim();
+    if option_text.is_empty() {
+        return Err(StoryScriptError { line, column: first_column, message: "the option has no text".to_string() });
+    }
+    let leading = text.chars().count() - text.trim_start().chars().count();
+    check_template(option_text, &[(line, first_column + leading)])?;
+
+    let target_column = first_column + text[..arrow + 2].chars().count();
+    let mut parser = LineParser::new(&text[arrow + 2..], line, target_column);
+    let mut option = StoryOption::new(parser.next_number("a node id")?, option_text);
+
+    while let Some(token) = parser.peek() {
+        parser.position += 1;
+        match token.text {
+            "else" => option.failure_node_id = Some(parser.next_number("a node id")?),
+            "@show" => option.show_when_unavailable = true,
+            other => return Err(parser.error_at(token.column, format!("unexpected '{}'", other))),
+        }
+    }
+
+    Ok(option)
+}
+
+fn parse_nodes(source: &str) -> Result<Vec<(usize, StoryNode)>, StoryScriptError> {
+    let mut nodes = Vec::new();
+    let mut current: Option<NodeBuilder> = None;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = raw_line.trim_start();
+        let indent = raw_line.chars().count() - text.chars().count();
+        // the column just past a line's leading marker, e.g. the `+` of an option
+        let column_after = |marker: &str| indent + marker.chars().count() + 1;
+
+        if text.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = text.strip_prefix("==") {
+            if let Some(node) = current.take() {
+                nodes.push(node.build()?);
+            }
+            current = Some(parse_header(header, line, column_after("=="))?);
+            continue;
+        }
+
+        let Some(node) = current.as_mut() else {
+            if text.is_empty() {
+                continue;
+            }
+            return Err(StoryScriptError { line, column: indent + 1, message: "expected a node header ('== <id> <title>')".to_string() });
+        };
+
+        if let Some(option) = text.strip_prefix('+') {
+            node.options.push(parse_option(option, line, column_after("+"))?);
+        } else if let Some(condition) = text.strip_prefix('?') {
+            let option = node.options.last_mut().ok_or_else(|| StoryScriptError { line, column: indent + 1, message: "a condition has to follow an option".to_string() })?;
+            let mut parser = LineParser::new(condition, line, column_after("?"));
+            option.conditions.push(parser.parse_condition()?);
+            parser.finish()?;
+        } else if let Some(effect) = text.strip_prefix('!') {
+            let option = node.options.last_mut().ok_or_else(|| StoryScriptError { line, column: indent + 1, message: "an effect has to follow an option".to_string() })?;
+            let mut parser = LineParser::new(effect, line, column_after("!"));
+            option.effects.push(parser.parse_effect()?);
+            parser.finish()?;
+        } else if !node.options.is_empty() {
+            if !text.is_empty() {
+                return Err(StoryScriptError { line, column: indent + 1, message: "node text has to come before its options".to_string() });
+            }
+        } else if !text.is_empty() || !node.text.is_empty() {
+            node.text.push(text.trim_end().to_string());
+            node.text_positions.push((line, indent + 1));
+        }
+    }
+
+    if let Some(node) = current {
+        nodes.push(node.build()?);
+    }
+    Ok(nodes)
+}
+
+/// Parses a story script into its nodes. A script is a list of nodes, each a header followed by its text and options:
+///
+/// ```text
+/// # comments start with a hash
+/// == 1 The Crypt @start
+/// You wake on cold stone, {player_name}.
+///
+/// + Force the door -> 2 else 3 @show
+///     ? check strength 15 and not flag door_barred
+///     ! xp 20
+/// + Pray -> 4
+///     ! madness -5
+/// ```
+///
+/// `@start` and `@end` mark start and end nodes, `else` is where a failed stat check leads and `@show` keeps the
+/// option visible (greyed out) when its conditions fail. Conditions (`?`) combine with `and`, `or`, `not` and brackets:
+/// `stat <stat> <value>`, `check <stat> <difficulty>`, `has <item> [quantity]`, `reputation <faction> <tier>`,
+/// `madness >= <tier>`, `madness <= <tier>`, `visited <node>`, `chose <node> <next node>`, `flag <flag>` and
+/// `quest <quest> not_started|active|completed|failed`, `quest <quest> stage <stage>` or `quest <quest> outcome <outcome>`.
+/// Effects (`!`) are `xp <amount>`, `give <item> [quantity]`, `take <item> [quantity]`, `set <flag>`, `clear <flag>`,
+/// `var <name> = <value>`, `var <name> += <number>`, `var <name> -= <number>`, `reputation <faction> <amount>`, `madness <amount>`, `combat <encounter>`,
+/// `quest start <quest>` and `quest advance <quest>`. Node and option text can use `{...}` tags to show variables
+/// and `{if ...}` blocks, see `StoryTemplate`.
+pub fn parse_story(source: &str) -> Result<Vec<StoryNode>, StoryScriptError> {
+    Ok(parse_nodes(source)?
+        .into_iter()
+        .map(|(_, node)| node)
+        .collect())
+}
+
+/// Parses a story script and builds its graph, see `parse_story` for the format.
+pub fn load_story(source: &str) -> Result<StoryGraph, StoryScriptError> {
+    let mut graph = StoryGraph::new();
+
+    for (line, node) in parse_nodes(source)? {
+        let id = node.id;
+        graph
+            .add_node(node)
+            .map_err(|_| StoryScriptError { line, column: 1, message: format!("node {} is defined more than once", id) })?;
+    }
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{MadnessTier, StatsEnum};
+    use crate::quests::quest_log::QuestState;
+    use crate::story_tree::story_conditions::StoryCondition;
+    use crate::story_tree::story_effects::StoryEffect;
+    use crate::story_tree::story_script::{load_story, parse_story, StoryScriptError};
+    use crate::story_tree::story_variables::StoryValue;
+
+    const CRYPT: &str = "\
+# the opening of the game
+== 1 The Crypt @start
+You wake on cold stone, {player_name}.
+
+The candles have burned down.
+
++ Force the door -> 2 else 3 @show
+    ? check strength 15 and not (flag door_barred or madness >= unhinged)
+    ! xp 20
+    ! quest start escape_the_crypt
++ Pray -> 3
+    ? quest escape_the_crypt not_started or quest escape_the_crypt stage the_chapel
+    ! madness -5
+    ! var prayers += 1
+    ! var npc.priest.last_words = \"Go in peace\"
+
+== 2 The Chapel @end
+Light at last.
+
+== 3 Still Here @end
+";
+
+    #[test]
+    fn test_script_builds_the_story() {
+        let graph = load_story(CRYPT).unwrap();
+        let crypt = graph.get_node(1).unwrap();
+
+        assert!(crypt.is_start_node);
+        assert_eq!(crypt.title, "The Crypt");
+        assert_eq!(crypt.text, "You wake on cold stone, {player_name}.\n\nThe candles have burned down.");
+
+        let force = &crypt.options[0];
+        assert_eq!(force.text, "Force the door");
+        assert_eq!((force.next_node_id, force.failure_node_id, force.show_when_unavailable), (2, Some(3), true));
+        assert_eq!(force.conditions, vec![StoryCondition::All(vec![
+            StoryCondition::StatCheck { stat: StatsEnum::Strength, difficulty: 15 },
+            StoryCondition::Not(Box::new(StoryCondition::Any(vec![
+                StoryCondition::FlagSet("door_barred".to_string()),
+                StoryCondition::MadnessAtLeast(MadnessTier::Unhinged),
+            ]))),
+        ])]);
+        assert_eq!(force.effects, vec![StoryEffect::GainExperience(20), StoryEffect::StartQuest("escape_the_crypt".to_string())]);
+        assert_eq!(crypt.options[1].conditions, vec![StoryCondition::Any(vec![
+            StoryCondition::Quest { quest_id: "escape_the_crypt".to_string(), state: QuestState::NotStarted },
+            StoryCondition::Quest { quest_id: "escape_the_crypt".to_string(), state: QuestState::AtStage("the_chapel".to_string()) },
+        ])]);
+        assert_eq!(crypt.options[1].effects, vec![
+            StoryEffect::ModifyMadness(-5),
+            StoryEffect::ChangeVariable { name: "prayers".to_string(), amount: 1 },
+            StoryEffect::SetVariable { name: "npc.priest.last_words".to_string(), value: StoryValue::Text("Go in peace".to_string()) },
+        ]);
+
+        assert!(graph.get_node(3).unwrap().is_end_node);
+        assert!(graph.validate().is_clean());
+    }
+
+    #[test]
+    fn test_errors_point_at_the_problem() {
+        let error = |source: &str| parse_story(source).unwrap_err();
+
+        assert_eq!(error("Some text"), StoryScriptError { line: 1, column: 1, message: "expected a node header ('== <id> <title>')".to_string() });
+        assert_eq!(error("== 1 Start\n+ Go -> 2\n    ? stat luck"), StoryScriptError { line: 3, column: 16, message: "expected a stat value".to_string() });
+        assert_eq!(error("== 1 Start\n+ Go -> 2\n  ! teleport 5").column, 5);
+        assert_eq!(error("== 1 Start\nHello {name").column, 7);
+        assert_eq!(error("== 1 Start\n  {if madness > 3}\n  The walls whisper."), StoryScriptError { line: 2, column: 3, message: "'{if}' is never closed with '{/if}'".to_string() });
+        assert_eq!(error("== 1 Start\n+ {if x >=} Go -> 2").column, 11);
+        assert_eq!(error("== 1 Start\n+ Go -> 2\n    ! var trust *= 2").message, "expected '=', '+=' or '-=', found '*='");
+        assert_eq!(error("== 1 Start\n+ Go -> 2\n    ! var x -= -2147483648"), StoryScriptError { line: 3, column: 16, message: "can't subtract -2147483648, it's out of range".to_string() });
+        assert_eq!(error("== 1 Start\n+ Go -> 2\n    ? quest escape done").message, "expected a quest state, found 'done'");
+        assert_eq!(error("== 1 Start\n+ Go -> two").message, "expected a node id, found 'two'");
+        assert_eq!(load_story("== 1 A\n== 1 B").unwrap_err().line, 2);
+    }
+}
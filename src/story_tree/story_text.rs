@@ -0,0 +1,490 @@
+use std::cmp::Ordering;
+use thiserror::Error;
+use crate::actors::models::{ResourcePoolValues, StatsEnum};
+use crate::story_tree::story_conditions::StoryContext;
+use crate::story_tree::story_management::PlayerStoryTree;
+use crate::story_tree::story_variables::StoryValue;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("line {line}, column {column}: {message}")]
+pub struct StoryTemplateError {
+    // lines and columns within the text, starting at 1
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    fn from_operator(operator: &str) -> Option<Self> {
+        match operator {
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            _ => None,
+        }
+    }
+
+    fn is_met(&self, ordering: Option<Ordering>) -> bool {
+        match self {
+            Comparison::Equal => ordering == Some(Ordering::Equal),
+            Comparison::NotEqual => ordering != Some(Ordering::Equal),
+            Comparison::Greater => ordering == Some(Ordering::Greater),
+            Comparison::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            Comparison::Less => ordering == Some(Ordering::Less),
+            Comparison::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TemplateOperand {
+    Literal(StoryValue),
+    Name(String),
+}
+
+/// The condition of an `{if}` block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TemplateExpression {
+    Value(TemplateOperand),
+    Compare { left: TemplateOperand, comparison: Comparison, right: TemplateOperand },
+    All(Vec<TemplateExpression>),
+    Any(Vec<TemplateExpression>),
+    Not(Box<TemplateExpression>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TemplatePart {
+    Text(String),
+    Value(String),
+    Conditional { condition: TemplateExpression, then_parts: Vec<TemplatePart>, else_parts: Vec<TemplatePart> },
+}
+
+/// Story text with values filled in when it's shown:
+///
+/// ```text
+/// {player_name} steps into the chapel. {if madness > 3}The walls whisper.{else}It is quiet.{/if}
+/// ```
+///
+/// `{name}` shows a value: `player_name`, `level`, `madness` (the madness tier, 0 for lucid up to 4 for broken),
+/// a stat such as `strength`, `flag.<flag>`, or a story variable such as `prayers`, `npc.blacksmith.trust` or
+/// `quest.cleanse_the_chapel.candles_lit`. Unset variables read as 0. `{if}` conditions compare values with
+/// `==`, `!=`, `>`, `>=`, `<` and `<=` against numbers, `true`, `false` or "quoted text", combine with `and`,
+/// `or`, `not` and brackets, and a value on its own passes unless it's 0, false or empty.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StoryTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+fn is_name(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|character| character.is_alphanumeric() || character == '_' || character == '.')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ExpressionToken {
+    text: String,
+    column: usize,
+}
+
+fn is_operator_character(character: char) -> bool {
+    matches!(character, '=' | '!' | '<' | '>')
+}
+
+/// Splits an `{if}` condition into words, brackets, operators and quoted text.
+fn tokenize_expression(text: &str, line: usize, first_column: usize) -> Result<Vec<ExpressionToken>, StoryTemplateError> {
+    let characters: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < characters.len() {
+        let start = index;
+        let character = characters[index];
+        index += 1;
+
+        if character.is_whitespace() {
+            continue;
+        }
+
+        if character == '"' {
+            while index < characters.len() && characters[index] != '"' {
+                index += 1;
+            }
+            if index == characters.len() {
+                return Err(StoryTemplateError { line, column: first_column + start, message: "'\"' is never closed".to_string() });
+            }
+            index += 1;
+        } else if is_operator_character(character) {
+            while index < characters.len() && is_operator_character(characters[index]) {
+                index += 1;
+            }
+        } else if character != '(' && character != ')' {
+            while index < characters.len() && !characters[index].is_whitespace() && !is_operator_character(characters[index]) && !"()\"".contains(characters[index]) {
+                index += 1;
+            }
+        }
+
+        tokens.push(ExpressionToken { text: characters[start..index].iter().collect(), column: first_column + start });
+    }
+
+    Ok(tokens)
+}
+
+struct ExpressionParser {
+    tokens: Vec<ExpressionToken>,
+    position: usize,
+    line: usize,
+    end_column: usize,
+}
+
+impl ExpressionParser {
+    fn error_at(&self, column: usize, message: String) -> StoryTemplateError {
+        StoryTemplateError { line: self.line, column, message }
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.get(self.position).is_some_and(|token| token.text == text)
+    }
+
+    fn next_token(&mut self, expected: &str) -> Result<ExpressionToken, StoryTemplateError> {
+        let token = self.tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| self.error_at(self.end_column, format!("expected {}", expected)))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn parse_any(&mut self) -> Result<TemplateExpression, StoryTemplateError> {
+        let mut expressions = vec![self.parse_all()?];
+        while self.peek_is("or") {
+            self.position += 1;
+            expressions.push(self.parse_all()?);
+        }
+
+        Ok(match expressions.len() {
+            1 => expressions.remove(0),
+            _ => TemplateExpression::Any(expressions),
+        })
+    }
+
+    fn parse_all(&mut self) -> Result<TemplateExpression, StoryTemplateError> {
+        let mut expressions = vec![self.parse_unary()?];
+        while self.peek_is("and") {
+            self.position += 1;
+            expressions.push(self.parse_unary()?);
+        }
+
+        Ok(match expressions.len() {
+            1 => expressions.remove(0),
+            _ => TemplateExpression::All(expressions),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<TemplateExpression, StoryTemplateError> {
+        if self.peek_is("not") {
+            self.position += 1;
+            return Ok(TemplateExpression::Not(Box::new(self.parse_unary()?)));
+        }
+
+        if self.peek_is("(") {
+            self.position += 1;
+            let expression = self.parse_any()?;
+            let token = self.next_token("')'")?;
+            return match token.text == ")" {
+                true => Ok(expression),
+                false => Err(self.error_at(token.column, format!("expected ')', found '{}'", token.text))),
+            };
+        }
+
+        let left = self.parse_operand()?;
+        let Some(comparison) = self.tokens.get(self.position).and_then(|token| Comparison::from_operator(&token.text)) else {
+            return Ok(TemplateExpression::Value(left));
+        };
+        self.position += 1;
+
+        Ok(TemplateExpression::Compare { left, comparison, right: self.parse_operand()? })
+    }
+
+    fn parse_operand(&mut self) -> Result<TemplateOperand, StoryTemplateError> {
+        let token = self.next_token("a value")?;
+
+        if let Some(text) = token.text.strip_prefix('"').and_then(|text| text.strip_suffix('"')) {
+            return Ok(TemplateOperand::Literal(StoryValue::Text(text.to_string())));
+        }
+
+        match token.text.as_str() {
+            "true" => Ok(TemplateOperand::Literal(StoryValue::Bool(true))),
+            "false" => Ok(TemplateOperand::Literal(StoryValue::Bool(false))),
+            text if text.parse::<i32>().is_ok() => Ok(TemplateOperand::Literal(StoryValue::Int(text.parse().unwrap_or_default()))),
+            text if is_name(text) && !["and", "or", "not"].contains(&text) => Ok(TemplateOperand::Name(text.to_string())),
+            text => Err(self.error_at(token.column, format!("expected a value, found '{}'", text))),
+        }
+    }
+}
+
+fn parse_expression(text: &str, line: usize, first_column: usize) -> Result<TemplateExpression, StoryTemplateError> {
+    let tokens = tokenize_expression(text, line, first_column)?;
+    let mut parser = ExpressionParser { tokens, position: 0, line, end_column: first_column + text.chars().count() };
+    let expression = parser.parse_any()?;
+
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(parser.error_at(token.column, format!("unexpected '{}'", token.text))),
+        None => Ok(expression),
+    }
+}
+
+struct OpenConditional {
+    condition: TemplateExpression,
+    then_parts: Vec<TemplatePart>,
+    else_parts: Option<Vec<TemplatePart>>,
+    line: usize,
+    column: usize,
+}
+
+/// Collects the parts of a template, keeping track of the `{if}` blocks that are still open.
+struct TemplateBuilder {
+    parts: Vec<TemplatePart>,
+    open: Vec<OpenConditional>,
+}
+
+impl TemplateBuilder {
+    fn get_current_parts(&mut self) -> &mut Vec<TemplatePart> {
+        match self.open.last_mut() {
+            Some(OpenConditional { else_parts: Some(else_parts), .. }) => else_parts,
+            Some(conditional) => &mut conditional.then_parts,
+            None => &mut self.parts,
+        }
+    }
+
+    fn add_text(&mut self, text: &mut String) {
+        if !text.is_empty() {
+            let text = std::mem::take(text);
+            self.get_current_parts().push(TemplatePart::Text(text));
+        }
+    }
+
+    /// Adds the contents of a `{...}` tag, `column` being where the `{` is.
+    fn add_tag(&mut self, tag: &str, line: usize, column: usize) -> Result<(), StoryTemplateError> {
+        let error = |message: &str| StoryTemplateError { line, column, message: message.to_string() };
+        let trimmed = tag.trim();
+
+        match trimmed {
+            "else" => match self.open.last_mut() {
+                Some(conditional) if conditional.else_parts.is_none() => conditional.else_parts = Some(Vec::new()),
+                Some(_) => return Err(error("'{if}' has more than one '{else}'")),
+                None => return Err(error("'{else}' without a matching '{if}'")),
+            },
+            "/if" => {
+                let conditional = self.open.pop().ok_or_else(|| error("'{/if}' without a matching '{if}'"))?;
+                self.get_current_parts().push(TemplatePart::Conditional {
+                    condition: conditional.condition,
+                    then_parts: conditional.then_parts,
+                    else_parts: conditional.else_parts.unwrap_or_default(),
+                });
+            },
+            _ if trimmed == "if" || trimmed.starts_with("if ") => {
+                // the condition starts after the `{`, any leading spaces and the `if`
+                let leading = tag.chars().count() - tag.trim_start().chars().count();
+                let condition = parse_expression(&tag.trim_start()[2..], line, column + 1 + leading + 2)?;
+                self.open.push(OpenConditional { condition, then_parts: Vec::new(), else_parts: None, line, column });
+            },
+            name if is_name(name) => self.get_current_parts().push(TemplatePart::Value(name.to_string())),
+            name => return Err(error(&format!("invalid variable name '{}'", name))),
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<StoryTemplate, StoryTemplateError> {
+        match self.open.last() {
+            Some(conditional) => Err(StoryTemplateError { line: conditional.line, column: conditional.column, message: "'{if}' is never closed with '{/if}'".to_string() }),
+            None => Ok(StoryTemplate { parts: self.parts }),
+        }
+    }
+}
+
+/// Looks up a value for a template. Anything that isn't built in is a story variable.
+fn resolve_value(name: &str, context: &StoryContext, story_tree: &PlayerStoryTree) -> StoryValue {
+    let stat = match name {
+        "player_name" => return StoryValue::Text(context.player.name.clone()),
+        "level" => return StoryValue::Int(context.player.player_stats.level as i32),
+        "madness" => return StoryValue::Int(context.player.get_madness_tier() as i32),
+        "strength" => Some(StatsEnum::Strength),
+        "dexterity" => Some(StatsEnum::Dexterity),
+        "constitution" => Some(StatsEnum::Constitution),
+        "intelligence" => Some(StatsEnum::Intelligence),
+        "wisdom" => Some(StatsEnum::Wisdom),
+        "charisma" => Some(StatsEnum::Charisma),
+        "luck" => Some(StatsEnum::Luck),
+        _ => None,
+    };
+
+    if let Some(stat) = stat {
+        return StoryValue::Int(context.player.get_effective_stat(stat));
+    }
+
+    if let Some(flag) = name.strip_prefix("flag.") {
+        return StoryValue::Bool(story_tree.story_flags.contains(flag));
+    }
+
+    story_tree.variables
+        .get_by_path(name)
+        .cloned()
+        .unwrap_or(StoryValue::Int(0))
+}
+
+impl TemplateOperand {
+    fn get_value(&self, context: &StoryContext, story_tree: &PlayerStoryTree) -> StoryValue {
+        match self {
+            TemplateOperand::Literal(value) => value.clone(),
+            TemplateOperand::Name(name) => resolve_value(name, context, story_tree),
+        }
+    }
+}
+
+impl TemplateExpression {
+    pub fn is_met(&self, context: &StoryContext, story_tree: &PlayerStoryTree) -> bool {
+        match self {
+            TemplateExpression::Value(operand) => operand.get_value(context, story_tree).is_truthy(),
+            TemplateExpression::Compare { left, comparison, right } => {
+                comparison.is_met(left.get_value(context, story_tree).compare(&right.get_value(context, story_tree)))
+            },
+            TemplateExpression::All(expressions) => expressions.iter().all(|expression| expression.is_met(context, story_tree)),
+            TemplateExpression::Any(expressions) => expressions.iter().any(|expression| expression.is_met(context, story_tree)),
+            TemplateExpression::Not(expression) => !expression.is_met(context, story_tree),
+        }
+    }
+}
+
+fn render_parts(parts: &[TemplatePart], context: &StoryContext, story_tree: &PlayerStoryTree, output: &mut String) {
+    for part in parts {
+        match part {
+            TemplatePart::Text(text) => output.push_str(text),
+            TemplatePart::Value(name) => output.push_str(&resolve_value(name, context, story_tree).to_text()),
+            TemplatePart::Conditional { condition, then_parts, else_parts } => {
+                let parts = match condition.is_met(context, story_tree) {
+                    true => then_parts,
+                    false => else_parts,
+                };
+                render_parts(parts, context, story_tree, output);
+            },
+        }
+    }
+}
+
+impl StoryTemplate {
+    pub fn parse(text: &str) -> Result<Self, StoryTemplateError> {
+        let mut builder = TemplateBuilder { parts: Vec::new(), open: Vec::new() };
+        let mut literal = String::new();
+        // the text of the tag being read along with where its `{` is
+        let mut tag: Option<(String, usize, usize)> = None;
+        let (mut line, mut column) = (1, 1);
+
+        for character in text.chars() {
+            match (character, tag.as_mut()) {
+                ('{', None) => {
+                    builder.add_text(&mut literal);
+                    tag = Some((String::new(), line, column));
+                },
+                ('}', Some(_)) => {
+                    if let Some((contents, tag_line, tag_column)) = tag.take() {
+                        builder.add_tag(&contents, tag_line, tag_column)?;
+                    }
+                },
+                ('}', None) => return Err(StoryTemplateError { line, column, message: "'}' without a matching '{'".to_string() }),
+                ('{' | '\n', Some((_, tag_line, tag_column))) => {
+                    return Err(StoryTemplateError { line: *tag_line, column: *tag_column, message: "'{' is never closed".to_string() });
+                },
+                (_, Some((contents, _, _))) => contents.push(character),
+                (_, None) => literal.push(character),
+            }
+
+            match character == '\n' {
+                true => (line, column) = (line + 1, 1),
+                false => column += 1,
+            }
+        }
+
+        if let Some((_, tag_line, tag_column)) = tag {
+            return Err(StoryTemplateError { line: tag_line, column: tag_column, message: "'{' is never closed".to_string() });
+        }
+
+        builder.add_text(&mut literal);
+        builder.finish()
+    }
+
+    pub fn get_parts(&self) -> &[TemplatePart] {
+        &self.parts
+    }
+
+    pub fn render(&self, context: &StoryContext, story_tree: &PlayerStoryTree) -> String {
+        let mut output = String::new();
+        render_parts(&self.parts, context, story_tree, &mut output);
+        output
+    }
+}
+
+/// Parses and renders text in one go, for text that is only shown once.
+pub fn render_text(text: &str, context: &StoryContext, story_tree: &PlayerStoryTree) -> Result<String, StoryTemplateError> {
+    Ok(StoryTemplate::parse(text)?.render(context, story_tree))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actors::models::{ResourcePoolEnum, ResourcePoolValues};
+    use crate::factions::faction::FactionRegistry;
+    use crate::state_management::player_state::PlayerState;
+    use crate::story_tree::story_conditions::StoryContext;
+    use crate::story_tree::story_management::PlayerStoryTree;
+    use crate::story_tree::story_text::{render_text, StoryTemplate, StoryTemplateError};
+    use crate::story_tree::story_variables::StoryValue;
+
+    #[test]
+    fn test_templates_read_the_story_state() {
+        let mut player = PlayerState::new();
+        player.name = "Agnes".to_string();
+        let factions = FactionRegistry::new();
+        let mut story_tree = PlayerStoryTree::new();
+        story_tree.variables.set_by_path("npc.blacksmith.mood", StoryValue::Text("grim".to_string()));
+        story_tree.variables.set_by_path("prayers", StoryValue::Int(3));
+
+        let template = StoryTemplate::parse(
+            "{player_name} prays ({prayers}). {if madness > 3}The walls whisper.{else}It is quiet.{/if}\
+            {if npc.blacksmith.mood == \"grim\" and not (flag.forgiven or prayers < 2)} The smith glares.{/if}"
+        ).unwrap();
+
+        let context = StoryContext { player: &player, factions: &factions };
+        assert_eq!(template.render(&context, &story_tree), "Agnes prays (3). It is quiet. The smith glares.");
+
+        player.modify_resource(ResourcePoolEnum::Madness, 1000);
+        story_tree.story_flags.insert("forgiven".to_string());
+        let context = StoryContext { player: &player, factions: &factions };
+        assert_eq!(template.render(&context, &story_tree), "Agnes prays (3). The walls whisper.");
+        assert_eq!(render_text("{unset}{if unset}!{/if}", &context, &story_tree), Ok("0".to_string()));
+    }
+
+    #[test]
+    fn test_template_errors_point_at_the_tag() {
+        let error = |text: &str| StoryTemplate::parse(text).unwrap_err();
+
+        assert_eq!(error("Hello {name"), StoryTemplateError { line: 1, column: 7, message: "'{' is never closed".to_string() });
+        assert_eq!(error("One\n{if x}two").line, 2);
+        assert_eq!(error("{if x}{else}{else}{/if}").column, 13);
+        assert_eq!(error("{if x >}{/if}").message, "expected a value");
+        assert_eq!(error("{if x = 2}{/if}"), StoryTemplateError { line: 1, column: 7, message: "unexpected '='".to_string() });
+        assert_eq!(error("{/if}").message, "'{/if}' without a matching '{if}'");
+        assert_eq!(error("a } b").column, 3);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use serde::Serialize;
+use crate::story_tree::story_conditions::StoryCondition;
+use crate::story_tree::story_management::StoryNode;
+use crate::story_tree::story_text::StoryTemplate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum IssueSeverity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum StoryIssue {
+    DuplicateNode(u32),
+    NoStartNode,
+    // an option leading to a node that doesn't exist
+    DanglingOption { node_id: u32, option_index: usize, next_node_id: u32 },
+    // no path from any start node leads here
+    UnreachableNode(u32),
+    // a node the player can't leave that isn't marked as an end node
+    DeadEnd(u32),
+    // nodes that only lead to each other and never to an end
+    InescapableCycle(Vec<u32>),
+    UnsatisfiableOption { node_id: u32, option_index: usize },
+    // a broken `{...}` tag in the text of a node, or of one of its options
+    InvalidText { node_id: u32, option_index: Option<usize>, message: String },
+}
+
+impl StoryIssue {
+    pub fn get_severity(&self) -> IssueSeverity {
+        match self {
+            StoryIssue::UnreachableNode(_) | StoryIssue::UnsatisfiableOption { .. } => IssueSeverity::Warning,
+            _ => IssueSeverity::Error,
+        }
+    }
+
+    pub fn get_message(&self) -> String {
+        match self {
+            StoryIssue::DuplicateNode(node_id) => format!("node {} is defined more than once", node_id),
+            StoryIssue::NoStartNode => "no node is marked as a start node and every node has a way in".to_string(),
+            StoryIssue::DanglingOption { node_id, option_index, next_node_id } => {
+                format!("option {} on node {} leads to missing node {}", option_index, node_id, next_node_id)
+            }
+            StoryIssue::UnreachableNode(node_id) => format!("node {} can't be reached from any start node", node_id),
+            StoryIssue::DeadEnd(node_id) => format!("node {} has no way out but isn't an end node", node_id),
+            StoryIssue::InescapableCycle(node_ids) => {
+                let node_ids: Vec<String> = node_ids.iter().map(|node_id| node_id.to_string()).collect();
+                format!("nodes {} loop with no way out", node_ids.join(" -> "))
+            }
+            StoryIssue::UnsatisfiableOption { node_id, option_index } => {
+                format!("option {} on node {} can never be chosen", option_index, node_id)
+            }
+            StoryIssue::InvalidText { node_id, option_index: None, message } => format!("text of node {}: {}", node_id, message),
+            StoryIssue::InvalidText { node_id, option_index: Some(option_index), message } => {
+                format!("text of option {} on node {}: {}", option_index, node_id, message)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StoryReport {
+    pub node_count: usize,
+    pub issues: Vec<StoryIssue>,
+}
+
+impl StoryReport {
+    pub fn get_issue_count(&self, severity: IssueSeverity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.get_severity() == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.get_issue_count(IssueSeverity::Error) > 0
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.issues
+            .iter()
+            .map(|issue| {
+                let severity = match issue.get_severity() {
+                    IssueSeverity::Warning => "warning",
+                    IssueSeverity::Error => "error",
+                };
+                format!("{}: {}", severity, issue.get_message())
+            })
+            .collect();
+
+        lines.push(format!(
+            "{} nodes checked, {} errors, {} warnings",
+            self.node_count,
+            self.get_issue_count(IssueSeverity::Error),
+            self.get_issue_count(IssueSeverity::Warning)
+        ));
+        lines
+    }
+}
+
+/// Whether every condition could pass together, e.g. not both a flag and its negation.
+fn can_all_be_met(conditions: &[StoryCondition], nodes: &BTreeMap<u32, &StoryNode>) -> bool {
+    let contradicts = |condition: &StoryCondition| match condition {
+        StoryCondition::Not(negated) => conditions.contains(negated.as_ref()),
+        StoryCondition::MadnessAtLeast(minimum) => conditions
+            .iter()
+            .any(|other| matches!(other, StoryCondition::MadnessAtMost(maximum) if maximum < minimum)),
+        _ => false,
+    };
+
+    conditions
+        .iter()
+        .all(|condition| can_be_met(condition, nodes) && !contradicts(condition))
+}
+
+/// Whether some state of the game could ever meet the condition. Stats, items, reputation and flags
+/// can all change, so only conditions that contradict themselves or the story itself fail.
+fn can_be_met(condition: &StoryCondition, nodes: &BTreeMap<u32, &StoryNode>) -> bool {
+    match condition {
+        StoryCondition::VisitedNode(node_id) => nodes.contains_key(node_id),
+        StoryCondition::ChoseOption { node_id, next_node_id } => nodes
+            .get(node_id)
+            .is_some_and(|node| node.options.iter().any(|option| option.next_node_id == *next_node_id)),
+        StoryCondition::All(conditions) => can_all_be_met(conditions, nodes),
+        StoryCondition::Any(conditions) => conditions.iter().any(|condition| can_be_met(condition, nodes)),
+        StoryCondition::Not(negated) => match negated.as_ref() {
+            // an empty list of conditions always passes
+            StoryCondition::All(conditions) => !conditions.is_empty(),
+            StoryCondition::Not(condition) => can_be_met(condition, nodes),
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+/// Every node reachable from the given ones by following the edges, not counting the starting nodes themselves
+/// unless a path leads back to them.
+fn get_reachable(start_ids: &[u32], edges: &BTreeMap<u32, Vec<u32>>) -> HashSet<u32> {
+    let mut reachable = HashSet::new();
+    let mut queue: VecDeque<u32> = start_ids
+        .iter()
+        .flat_map(|node_id| edges.get(node_id).cloned().unwrap_or_default())
+        .collect();
+
+    while let Some(node_id) = queue.pop_front() {
+        if reachable.insert(node_id) {
+            queue.extend(edges.get(&node_id).cloned().unwrap_or_default());
+        }
+    }
+    reachable
+}
+
+/// The ids of the nodes flagged as start nodes, or failing that of every node no option leads to, sorted by id.
+/// Both `StoryGraph::start` and `validate_nodes` find start nodes this way.
+pub fn find_start_node_ids<'a, I: IntoIterator<Item = &'a StoryNode>>(nodes: I) -> Vec<u32> {
+    let nodes: Vec<&StoryNode> = nodes.into_iter().collect();
+    let mut start_ids: Vec<u32> = nodes
+        .iter()
+        .filter(|node| node.is_start_node)
+        .map(|node| node.id)
+        .collect();
+
+    if start_ids.is_empty() {
+        let destination_ids: HashSet<u32> = nodes
+            .iter()
+            .flat_map(|node| node.options.iter().flat_map(|option| option.get_destination_ids()))
+            .collect();
+        start_ids = nodes
+            .iter()
+            .map(|node| node.id)
+            .filter(|node_id| !destination_ids.contains(node_id))
+            .collect();
+    }
+
+    start_ids.sort();
+    start_ids.dedup();
+    start_ids
+}
+
+/// Checks nodes for broken branches. Takes the raw nodes rather than a `StoryGraph` so duplicate ids
+/// in story data can be caught before the graph refuses them.
+pub fn validate_nodes<'a, I: IntoIterator<Item = &'a StoryNode>>(nodes: I) -> StoryReport {
+    let mut report = StoryReport::default();
+    let mut unique: BTreeMap<u32, &StoryNode> = BTreeMap::new();
+    let mut duplicates = BTreeSet::new();
+
+    for node in nodes {
+        report.node_count += 1;
+        if unique.insert(node.id, node).is_some() {
+            duplicates.insert(node.id);
+        }
+    }
+    report.issues.extend(duplicates.into_iter().map(StoryIssue::DuplicateNode));
+
+    // only options the player could actually pick count as edges
+    let mut edges: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for node in unique.values() {
+        let mut next_node_ids = Vec::new();
+
+        let texts = std::iter::once((None, &node.text)).chain(node.options.iter().enumerate().map(|(index, option)| (Some(index), &option.text)));
+        for (option_index, text) in texts {
+            if let Err(error) = StoryTemplate::parse(text) {
+                report.issues.push(StoryIssue::InvalidText { node_id: node.id, option_index, message: error.to_string() });
+            }
+        }
+
+        for (option_index, option) in node.options.iter().enumerate() {
+            if !can_all_be_met(&option.conditions, &unique) {
+                report.issues.push(StoryIssue::UnsatisfiableOption { node_id: node.id, option_index });
+                continue;
+            }
+
+            for next_node_id in option.get_destination_ids() {
+                match unique.contains_key(&next_node_id) {
+                    true => next_node_ids.push(next_node_id),
+                    false => report.issues.push(StoryIssue::DanglingOption { node_id: node.id, option_index, next_node_id }),
+                }
+            }
+        }
+
+        if !node.is_end_node && !node.options.iter().any(|option| can_all_be_met(&option.conditions, &unique)) {
+            report.issues.push(StoryIssue::DeadEnd(node.id));
+        }
+
+        edges.insert(node.id, next_node_ids);
+    }
+
+    let start_ids = find_start_node_ids(unique.values().copied());
+
+    match start_ids.is_empty() {
+        true => report.issues.push(StoryIssue::NoStartNode),
+        false => {
+            let reachable = get_reachable(&start_ids, &edges);
+            report.issues.extend(unique
+                .keys()
+                .filter(|node_id| !start_ids.contains(node_id) && !reachable.contains(node_id))
+                .map(|node_id| StoryIssue::UnreachableNode(*node_id)));
+        }
+    }
+
+    // group nodes that can reach each other, then flag any group no edge leaves
+    let reachable_from: BTreeMap<u32, HashSet<u32>> = unique
+        .keys()
+        .map(|node_id| (*node_id, get_reachable(&[*node_id], &edges)))
+        .collect();
+    let mut grouped = HashSet::new();
+
+    for (node_id, reachable) in &reachable_from {
+        if grouped.contains(node_id) || !reachable.contains(node_id) {
+            continue;
+        }
+
+        let cycle: Vec<u32> = reachable_from
+            .iter()
+            .filter(|(other_id, other_reachable)| reachable.contains(other_id) && other_reachable.contains(node_id))
+            .map(|(other_id, _)| *other_id)
+            .collect();
+        grouped.extend(cycle.iter().copied());
+
+        let has_exit = cycle.iter().any(|cycle_id| {
+            unique[cycle_id].is_end_node || edges[cycle_id].iter().any(|next_node_id| !cycle.contains(next_node_id))
+        });
+        if !has_exit {
+            report.issues.push(StoryIssue::InescapableCycle(cycle));
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::story_tree::story_conditions::StoryCondition;
+    use crate::story_tree::story_graph::StoryGraph;
+    use crate::story_tree::story_management::{StoryNode, StoryOption};
+    use crate::story_tree::story_validation::{validate_nodes, StoryIssue};
+
+    fn node(id: u32, next_node_ids: &[u32], is_start_node: bool, is_end_node: bool) -> StoryNode {
+        let options = next_node_ids
+            .iter()
+            .map(|next_node_id| StoryOption::new(*next_node_id, ""))
+            .collect();
+        StoryNode::new(id, String::new(), String::new(), options, is_end_node, is_start_node)
+    }
+
+    #[test]
+    fn test_valid_story_is_clean() {
+        let graph = StoryGraph::from_nodes(vec![
+            node(1, &[2, 3], true, false),
+            node(2, &[1, 4], false, false),
+            node(3, &[4], false, false),
+            node(4, &[], false, true),
+        ]).unwrap();
+
+        let report = graph.validate();
+        assert!(report.is_clean());
+        assert_eq!(report.to_lines(), vec!["4 nodes checked, 0 errors, 0 warnings".to_string()]);
+    }
+
+    #[test]
+    fn test_unflagged_stories_start_where_nothing_leads() {
+        let graph = StoryGraph::from_nodes(vec![
+            node(1, &[2], false, false),
+            node(2, &[], false, true),
+        ]).unwrap();
+
+        assert!(graph.validate().is_clean());
+        assert_eq!(graph.get_start_nodes()[0].id, 1);
+
+        let looped = vec![node(1, &[2], false, false), node(2, &[1], false, true)];
+        assert_eq!(validate_nodes(&looped).issues, vec![StoryIssue::NoStartNode]);
+    }
+
+    #[test]
+    fn test_broken_branches_are_reported() {
+        let flag = StoryCondition::FlagSet("heretic".to_string());
+        let mut locked = node(6, &[7], false, false);
+        locked.options[0].conditions = vec![flag.clone(), StoryCondition::Not(Box::new(flag))];
+        let mut whispering = node(3, &[4], false, false);
+        whispering.text = "{if madness > 3}The walls whisper.".to_string();
+
+        let nodes = vec![
+            node(1, &[2, 3, 9], true, false),
+            node(2, &[], false, false),
+            whispering,
+            node(4, &[5], false, false),
+            node(5, &[4], false, false),
+            locked,
+            node(7, &[], false, true),
+            node(7, &[], false, true),
+        ];
+
+        let report = validate_nodes(&nodes);
+        assert!(report.has_errors());
+        assert_eq!(report.node_count, 8);
+        assert_eq!(report.issues, vec![
+            StoryIssue::DuplicateNode(7),
+            StoryIssue::DanglingOption { node_id: 1, option_index: 2, next_node_id: 9 },
+            StoryIssue::DeadEnd(2),
+            StoryIssue::InvalidText { node_id: 3, option_index: None, message: "line 1, column 1: '{if}' is never closed with '{/if}'".to_string() },
+            StoryIssue::UnsatisfiableOption { node_id: 6, option_index: 0 },
+            StoryIssue::DeadEnd(6),
+            StoryIssue::UnreachableNode(6),
+            StoryIssue::UnreachableNode(7),
+            StoryIssue::InescapableCycle(vec![4, 5]),
+        ]);
+    }
+}
@@ -0,0 +1,161 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// A value a story variable can hold. Written in data files as a plain number, boolean or string.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoryValue {
+    Int(i32),
+    Bool(bool),
+    Text(String),
+}
+
+impl StoryValue {
+    /// Zero, false and empty text are false, everything else is true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            StoryValue::Int(value) => *value != 0,
+            StoryValue::Bool(value) => *value,
+            StoryValue::Text(text) => !text.is_empty(),
+        }
+    }
+
+    /// Values of different kinds can't be compared.
+    pub fn compare(&self, other: &StoryValue) -> Option<Ordering> {
+        match (self, other) {
+            (StoryValue::Int(left), StoryValue::Int(right)) => Some(left.cmp(right)),
+            (StoryValue::Bool(left), StoryValue::Bool(right)) => Some(left.cmp(right)),
+            (StoryValue::Text(left), StoryValue::Text(right)) => Some(left.cmp(right)),
+            _ => None,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            StoryValue::Int(value) => value.to_string(),
+            StoryValue::Bool(value) => value.to_string(),
+            StoryValue::Text(text) => text.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariableScope {
+    Global,
+    Npc(String),
+    Quest(String),
+}
+
+impl VariableScope {
+    /// Splits a variable path into its scope and name: `npc.blacksmith.trust` is the blacksmith's `trust`,
+    /// `quest.cleanse_the_chapel.candles_lit` belongs to the quest and anything else is a global variable.
+    pub fn parse_path(path: &str) -> (VariableScope, &str) {
+        let mut parts = path.splitn(3, '.');
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("npc"), Some(npc_id), Some(name)) => (VariableScope::Npc(npc_id.to_string()), name),
+            (Some("quest"), Some(quest_id), Some(name)) => (VariableScope::Quest(quest_id.to_string()), name),
+            _ => (VariableScope::Global, path),
+        }
+    }
+}
+
+/// The variables set by the story, kept with the player's story progress.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoryVariables {
+    global: HashMap<String, StoryValue>,
+    npcs: HashMap<String, HashMap<String, StoryValue>>,
+    quests: HashMap<String, HashMap<String, StoryValue>>,
+}
+
+impl StoryVariables {
+    pub fn new() -> Self {
+        StoryVariables {
+            global: HashMap::new(),
+            npcs: HashMap::new(),
+            quests: HashMap::new(),
+        }
+    }
+
+    fn get_scope(&self, scope: &VariableScope) -> Option<&HashMap<String, StoryValue>> {
+        match scope {
+            VariableScope::Global => Some(&self.global),
+            VariableScope::Npc(npc_id) => self.npcs.get(npc_id),
+            VariableScope::Quest(quest_id) => self.quests.get(quest_id),
+        }
+    }
+
+    fn get_scope_mut(&mut self, scope: &VariableScope) -> &mut HashMap<String, StoryValue> {
+        match scope {
+            VariableScope::Global => &mut self.global,
+            VariableScope::Npc(npc_id) => self.npcs.entry(npc_id.clone()).or_default(),
+            VariableScope::Quest(quest_id) => self.quests.entry(quest_id.clone()).or_default(),
+        }
+    }
+
+    pub fn get(&self, scope: &VariableScope, name: &str) -> Option<&StoryValue> {
+        self.get_scope(scope).and_then(|variables| variables.get(name))
+    }
+
+    pub fn set(&mut self, scope: &VariableScope, name: &str, value: StoryValue) {
+        self.get_scope_mut(scope).insert(name.to_string(), value);
+    }
+
+    pub fn remove(&mut self, scope: &VariableScope, name: &str) -> Option<StoryValue> {
+        self.get_scope_mut(scope).remove(name)
+    }
+
+    /// Forgets every variable in a scope, e.g. when a quest is reset.
+    pub fn clear_scope(&mut self, scope: &VariableScope) {
+        self.get_scope_mut(scope).clear();
+    }
+
+    pub fn get_by_path(&self, path: &str) -> Option<&StoryValue> {
+        let (scope, name) = VariableScope::parse_path(path);
+        self.get(&scope, name)
+    }
+
+    pub fn set_by_path(&mut self, path: &str, value: StoryValue) {
+        let (scope, name) = VariableScope::parse_path(path);
+        self.set(&scope, name, value);
+    }
+
+    /// Adds to a number variable and returns its new value. Unset variables and ones that don't hold a number count as zero.
+    pub fn add_by_path(&mut self, path: &str, amount: i32) -> i32 {
+        let (scope, name) = VariableScope::parse_path(path);
+        let current = match self.get(&scope, name) {
+            Some(StoryValue::Int(value)) => *value,
+            _ => 0,
+        };
+
+        let value = current.saturating_add(amount);
+        self.set(&scope, name, StoryValue::Int(value));
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::story_tree::story_variables::{StoryValue, StoryVariables, VariableScope};
+
+    #[test]
+    fn test_variables_are_scoped() {
+        let mut variables = StoryVariables::new();
+        variables.set_by_path("trust", StoryValue::Int(1));
+        variables.set_by_path("npc.blacksmith.trust", StoryValue::Int(5));
+        variables.set_by_path("quest.cleanse_the_chapel.bell", StoryValue::Text("rung".to_string()));
+
+        assert_eq!(variables.get(&VariableScope::Global, "trust"), Some(&StoryValue::Int(1)));
+        assert_eq!(variables.get(&VariableScope::Npc("blacksmith".to_string()), "trust"), Some(&StoryValue::Int(5)));
+        assert_eq!(variables.get_by_path("npc.priest.trust"), None);
+        assert_eq!(variables.add_by_path("npc.blacksmith.trust", -2), 3);
+        assert_eq!(variables.add_by_path("npc.priest.trust", 2), 2);
+
+        variables.clear_scope(&VariableScope::Quest("cleanse_the_chapel".to_string()));
+        assert_eq!(variables.get_by_path("quest.cleanse_the_chapel.bell"), None);
+
+        let json = serde_json::to_string(&variables).unwrap();
+        assert_eq!(serde_json::from_str::<StoryVariables>(&json).unwrap(), variables);
+    }
+}
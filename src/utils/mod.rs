@@ -0,0 +1 @@
+pub mod rng;
\ No newline at end of file
@@ -0,0 +1,57 @@
+/// A small seedable random number generator (SplitMix64) so that anything rolled
+/// from the same seed plays out the same way, which keeps loot and combat reproducible in tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            state: seed,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    /// Returns a value in `0.0..1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a value in `min..=max`, or `min` if the range is empty.
+    pub fn range_u32(&mut self, min: u32, max: u32) -> u32 {
+        if max <= min {
+            return min;
+        }
+
+        let span = (max - min) as u64 + 1;
+        min + (self.next_u64() % span) as u32
+    }
+
+    /// Returns a value in `min..=max`, or `min` if the range is empty.
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+
+        let span = (max as i64 - min as i64) as u64 + 1;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    pub fn roll_die(&mut self, sides: u32) -> u32 {
+        self.range_u32(1, sides.max(1))
+    }
+
+    /// Returns true `percent` times out of a hundred.
+    pub fn chance(&mut self, percent: i32) -> bool {
+        self.range_i32(1, 100) <= percent
+    }
+}
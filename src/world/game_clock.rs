@@ -0,0 +1,166 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+pub const MINUTES_PER_HOUR: u32 = 60;
+pub const MINUTES_PER_DAY: u32 = 24 * MINUTES_PER_HOUR;
+
+// one real second is a minute of game time unless changed
+const DEFAULT_GAME_MINUTES_PER_SECOND: f32 = 1.0;
+
+/// A time of day on the in-game clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(from = "SavedGameTime")]
+pub struct GameTime {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+// a time as written in data files, loaded through `GameTime::new` so it ends up in range
+#[derive(Deserialize)]
+struct SavedGameTime {
+    hour: u32,
+    minute: u32,
+}
+
+impl From<SavedGameTime> for GameTime {
+    fn from(saved: SavedGameTime) -> Self {
+        GameTime::new(saved.hour, saved.minute)
+    }
+}
+
+impl GameTime {
+    pub fn new(hour: u32, minute: u32) -> Self {
+        GameTime {
+            hour: hour % 24,
+            minute: minute % MINUTES_PER_HOUR,
+        }
+    }
+
+    pub fn from_minute_of_day(minute_of_day: u32) -> Self {
+        let minute_of_day = minute_of_day % MINUTES_PER_DAY;
+        GameTime::new(minute_of_day / MINUTES_PER_HOUR, minute_of_day % MINUTES_PER_HOUR)
+    }
+
+    pub fn get_minute_of_day(&self) -> u32 {
+        self.hour * MINUTES_PER_HOUR + self.minute
+    }
+
+    /// Whether the time falls in `start..end`, wrapping past midnight when `end` is before `start`.
+    pub fn is_between(&self, start: GameTime, end: GameTime) -> bool {
+        match start <= end {
+            true => start <= *self && *self < end,
+            false => *self >= start || *self < end,
+        }
+    }
+}
+
+/// The in-game clock. It is advanced from the frame loop with the real time that passed
+/// (e.g. the delta from `TimingManager`) but keeps its own time, which can run faster, slower or be paused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameClock {
+    // minutes since midnight of day 0
+    total_minutes: u64,
+    // the part of a game minute that has passed but not ticked over yet
+    pending_minutes: f32,
+    game_minutes_per_second: f32,
+    is_paused: bool,
+}
+
+impl GameClock {
+    pub fn new(day: u32, time: GameTime) -> Self {
+        GameClock {
+            total_minutes: day as u64 * MINUTES_PER_DAY as u64 + time.get_minute_of_day() as u64,
+            pending_minutes: 0.0,
+            game_minutes_per_second: DEFAULT_GAME_MINUTES_PER_SECOND,
+            is_paused: false,
+        }
+    }
+
+    pub fn with_time_scale(mut self, game_minutes_per_second: f32) -> Self {
+        self.game_minutes_per_second = game_minutes_per_second;
+        self
+    }
+
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// Advances the clock by a frame's worth of real time, returning how many whole game minutes passed.
+    pub fn advance(&mut self, real_delta: Duration) -> u64 {
+        if self.is_paused {
+            return 0;
+        }
+
+        self.pending_minutes += real_delta.as_secs_f32() * self.game_minutes_per_second;
+        let minutes = self.pending_minutes.floor();
+        self.pending_minutes -= minutes;
+
+        self.advance_minutes(minutes as u64);
+        minutes as u64
+    }
+
+    /// Skips ahead regardless of pausing, e.g. when resting or travelling.
+    pub fn advance_minutes(&mut self, minutes: u64) {
+        self.total_minutes += minutes;
+    }
+
+    pub fn get_total_minutes(&self) -> u64 {
+        self.total_minutes
+    }
+
+    pub fn get_day(&self) -> u32 {
+        (self.total_minutes / MINUTES_PER_DAY as u64) as u32
+    }
+
+    pub fn get_time(&self) -> GameTime {
+        GameTime::from_minute_of_day((self.total_minutes % MINUTES_PER_DAY as u64) as u32)
+    }
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock::new(0, GameTime::new(8, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::world::game_clock::{GameClock, GameTime};
+
+    #[test]
+    fn test_clock_advances_from_frames() {
+        let mut clock = GameClock::new(0, GameTime::new(23, 30)).with_time_scale(2.0);
+
+        assert_eq!(clock.advance(Duration::from_millis(400)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(400)), 1);
+        assert_eq!(clock.get_time(), GameTime::new(23, 31));
+
+        clock.advance(Duration::from_secs(15));
+        assert_eq!(clock.get_day(), 1);
+        assert_eq!(clock.get_time(), GameTime::new(0, 1));
+
+        clock.set_paused(true);
+        assert_eq!(clock.advance(Duration::from_secs(60)), 0);
+        assert_eq!(clock.get_time(), GameTime::new(0, 1));
+    }
+
+    #[test]
+    fn test_is_between_wraps_midnight() {
+        assert!(GameTime::new(23, 0).is_between(GameTime::new(22, 0), GameTime::new(6, 0)));
+        assert!(GameTime::new(5, 59).is_between(GameTime::new(22, 0), GameTime::new(6, 0)));
+        assert!(!GameTime::new(6, 0).is_between(GameTime::new(22, 0), GameTime::new(6, 0)));
+        assert!(GameTime::new(9, 0).is_between(GameTime::new(8, 0), GameTime::new(17, 0)));
+    }
+
+    #[test]
+    fn test_loaded_times_are_normalised() {
+        let time: GameTime = serde_json::from_str(r#"{"hour": 26, "minute": 90}"#).unwrap();
+        assert_eq!(time, GameTime::new(2, 30));
+        assert_eq!(serde_json::to_string(&time).unwrap(), r#"{"hour":2,"minute":30}"#);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod game_clock;
+pub mod schedule;
\ No newline at end of file
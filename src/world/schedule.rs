@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use crate::world::game_clock::{GameClock, GameTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Activity {
+    Work,
+    Pray,
+    Drink,
+    Eat,
+    Sleep,
+    Patrol,
+    Wander,
+    Hide,
+    Flee,
+    Fight,
+}
+
+/// Events that pull an NPC away from what it would normally be doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScheduleEvent {
+    CombatNearby,
+    Curfew,
+    ChurchRaid,
+}
+
+/// What an NPC does between `start` and `end`, wrapping past midnight when `end` is earlier than `start`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleBlock {
+    pub start: GameTime,
+    pub end: GameTime,
+    pub location_id: String,
+    pub activity: Activity,
+}
+
+/// How an NPC reacts to an event. Without a location the NPC reacts wherever it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterruptionRule {
+    pub event: ScheduleEvent,
+    pub location_id: Option<String>,
+    pub activity: Activity,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub blocks: Vec<ScheduleBlock>,
+    pub interruptions: Vec<InterruptionRule>,
+    // where the NPC goes in any gap between blocks
+    pub home_location_id: String,
+}
+
+impl Schedule {
+    pub fn get_block_at(&self, time: GameTime) -> Option<&ScheduleBlock> {
+        self.blocks
+            .iter()
+            .find(|block| time.is_between(block.start, block.end))
+    }
+
+    pub fn get_interruption_rule(&self, event: ScheduleEvent) -> Option<&InterruptionRule> {
+        self.interruptions
+            .iter()
+            .find(|rule| rule.event == event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveInterruption {
+    pub event: ScheduleEvent,
+    pub location_id: String,
+    pub activity: Activity,
+    // the total game minute the interruption ends on its own, None until it is ended explicitly
+    pub ends_at: Option<u64>,
+}
+
+/// Where an NPC should be and what it should be doing right now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutineState {
+    pub location_id: String,
+    pub activity: Activity,
+    pub interrupted_by: Option<ScheduleEvent>,
+}
+
+/// An NPC following its schedule. The world calls `update` as the clock advances
+/// and moves the NPC whenever it reports a change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpcRoutine {
+    pub schedule: Schedule,
+    // where the NPC was last sent, interruption rules without a location happen here
+    pub current_location_id: String,
+    interruption: Option<ActiveInterruption>,
+    state: Option<RoutineState>,
+}
+
+impl NpcRoutine {
+    pub fn new(schedule: Schedule) -> Self {
+        NpcRoutine {
+            current_location_id: schedule.home_location_id.clone(),
+            schedule,
+            interruption: None,
+            state: None,
+        }
+    }
+
+    pub fn get_state(&self) -> Option<&RoutineState> {
+        self.state.as_ref()
+    }
+
+    pub fn get_interruption(&self) -> Option<&ActiveInterruption> {
+        self.interruption.as_ref()
+    }
+
+    /// Interrupts the routine if the schedule has a rule for the event, replacing any earlier interruption.
+    /// Returns false if the NPC doesn't care about the event.
+    pub fn interrupt(&mut self, event: ScheduleEvent, clock: &GameClock, duration_minutes: Option<u64>) -> bool {
+        let Some(rule) = self.schedule.get_interruption_rule(event) else {
+            return false;
+        };
+
+        self.interruption = Some(ActiveInterruption {
+            event,
+            location_id: rule.location_id.clone().unwrap_or_else(|| self.current_location_id.clone()),
+            activity: rule.activity,
+            ends_at: duration_minutes.map(|minutes| clock.get_total_minutes() + minutes),
+        });
+        true
+    }
+
+    /// Ends the interruption if it was caused by the given event so the schedule resumes on the next update.
+    pub fn end_interruption(&mut self, event: ScheduleEvent) {
+        if self.interruption.as_ref().is_some_and(|interruption| interruption.event == event) {
+            self.interruption = None;
+        }
+    }
+
+    /// Works out what the NPC should be doing now, returning the new state if it changed since the last update.
+    pub fn update(&mut self, clock: &GameClock) -> Option<&RoutineState> {
+        if self.interruption.as_ref().is_some_and(|interruption| interruption.ends_at.is_some_and(|ends_at| clock.get_total_minutes() >= ends_at)) {
+            self.interruption = None;
+        }
+
+        let state = match &self.interruption {
+            Some(interruption) => RoutineState {
+                location_id: interruption.location_id.clone(),
+                activity: interruption.activity,
+                interrupted_by: Some(interruption.event),
+            },
+            None => match self.schedule.get_block_at(clock.get_time()) {
+                Some(block) => RoutineState {
+                    location_id: block.location_id.clone(),
+                    activity: block.activity,
+                    interrupted_by: None,
+                },
+                None => RoutineState {
+                    location_id: self.schedule.home_location_id.clone(),
+                    activity: Activity::Wander,
+                    interrupted_by: None,
+                },
+            },
+        };
+
+        if self.state.as_ref() == Some(&state) {
+            return None;
+        }
+
+        self.current_location_id = state.location_id.clone();
+        self.state = Some(state);
+        self.state.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::game_clock::{GameClock, GameTime};
+    use crate::world::schedule::{Activity, InterruptionRule, NpcRoutine, Schedule, ScheduleBlock, ScheduleEvent};
+
+    fn create_blacksmith_schedule() -> Schedule {
+        let block = |start: u32, end: u32, location_id: &str, activity: Activity| ScheduleBlock {
+            start: GameTime::new(start, 0),
+            end: GameTime::new(end, 0),
+            location_id: location_id.to_string(),
+            activity,
+        };
+
+        Schedule {
+            id: "blacksmith".to_string(),
+            blocks: vec![
+                block(7, 8, "chapel", Activity::Pray),
+                block(8, 18, "smithy", Activity::Work),
+                block(18, 22, "tavern", Activity::Drink),
+                block(22, 7, "blacksmith_house", Activity::Sleep),
+            ],
+            interruptions: vec![
+                InterruptionRule { event: ScheduleEvent::ChurchRaid, location_id: Some("blacksmith_house".to_string()), activity: Activity::Hide },
+                InterruptionRule { event: ScheduleEvent::CombatNearby, location_id: None, activity: Activity::Flee },
+            ],
+            home_location_id: "blacksmith_house".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_routine_follows_the_clock() {
+        let mut clock = GameClock::new(0, GameTime::new(6, 30));
+        let mut routine = NpcRoutine::new(create_blacksmith_schedule());
+
+        assert_eq!(routine.update(&clock).unwrap().activity, Activity::Sleep);
+        assert_eq!(routine.update(&clock), None);
+
+        clock.advance_minutes(30);
+        assert_eq!(routine.update(&clock).unwrap().location_id, "chapel");
+
+        clock.advance_minutes(13 * 60);
+        assert_eq!(routine.update(&clock).unwrap().activity, Activity::Drink);
+    }
+
+    #[test]
+    fn test_interruptions_resume_the_schedule() {
+        let mut clock = GameClock::new(0, GameTime::new(9, 0));
+        let mut routine = NpcRoutine::new(create_blacksmith_schedule());
+        routine.update(&clock);
+
+        assert!(!routine.interrupt(ScheduleEvent::Curfew, &clock, None));
+        assert!(routine.interrupt(ScheduleEvent::CombatNearby, &clock, Some(30)));
+
+        let state = routine.update(&clock).unwrap();
+        assert_eq!(state.location_id, "smithy");
+        assert_eq!(state.activity, Activity::Flee);
+        assert_eq!(state.interrupted_by, Some(ScheduleEvent::CombatNearby));
+
+        clock.advance_minutes(30);
+        assert_eq!(routine.update(&clock).unwrap().activity, Activity::Work);
+
+        routine.interrupt(ScheduleEvent::ChurchRaid, &clock, None);
+        clock.advance_minutes(13 * 60);
+        assert_eq!(routine.update(&clock).unwrap().activity, Activity::Hide);
+
+        routine.end_interruption(ScheduleEvent::ChurchRaid);
+        assert_eq!(routine.update(&clock).unwrap().activity, Activity::Sleep);
+    }
+}